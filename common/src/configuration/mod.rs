@@ -1,4 +1,5 @@
 pub mod anchors;
+#[allow(clippy::module_inception)]
 pub mod configuration;
 pub mod http;
 pub mod ping;
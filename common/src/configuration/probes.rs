@@ -7,6 +7,6 @@ pub struct Probes {
 
 impl Probes {
     pub fn new(probes: Vec<u32>) -> Self {
-        Probes { probes: probes }
+        Probes { probes }
     }
 }
@@ -8,7 +8,7 @@ pub struct Topology {
 
 impl Topology {
     pub fn new(mode: String, pairs: Vec<TopologicalPair>) -> Self {
-        Topology { mode: mode, pairs: pairs }
+        Topology { mode, pairs }
     }
 }
 
@@ -20,6 +20,6 @@ pub struct TopologicalPair {
 
 impl TopologicalPair {
     pub fn new(from: String, to: String) -> Self {
-        TopologicalPair { from: from, to: to }
+        TopologicalPair { from, to }
     }
 }
\ No newline at end of file
@@ -15,7 +15,7 @@ pub enum RipeMeasurementCreationError {
     Network(#[source] reqwest::Error),
 
     #[error("Ripe Atlas API returned an error: {status} - {body}")]
-    API { status: StatusCode, body: String },
+    Api { status: StatusCode, body: String },
 
     #[error("Failed to parse expected JSON response body: {0}")]
     ResponseFormat(#[from] serde_json::Error),
@@ -43,7 +43,7 @@ pub async fn create_ripe_measurement(
         .map_err(RipeMeasurementCreationError::Network)?;
 
     if !status.is_success() {
-        return Err(RipeMeasurementCreationError::API { status, body: text });
+        return Err(RipeMeasurementCreationError::Api { status, body: text });
     }
 
     let measurement: Measurement =
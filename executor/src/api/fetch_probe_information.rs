@@ -70,7 +70,7 @@ pub enum FetchProbeInformationError {
     Network(#[source] reqwest::Error),
 
     #[error("RIPE Atlas API returned an error: {status} - {body}")]
-    API { status: StatusCode, body: String },
+    Api { status: StatusCode, body: String },
 
     #[error("Failed to parse expected JSON response body: {0}")]
     ResponseFormat(#[from] serde_json::Error),
@@ -121,16 +121,16 @@ async fn fetch_single(
         .get(url)
         .send()
         .await
-        .map_err(|err| FetchProbeInformationError::Network(err))?;
+        .map_err(FetchProbeInformationError::Network)?;
 
     let status = res.status();
     let body = res
         .text()
         .await
-        .map_err(|err| FetchProbeInformationError::Network(err))?;
+        .map_err(FetchProbeInformationError::Network)?;
 
     if !status.is_success() {
-        return Err(FetchProbeInformationError::API { status, body });
+        return Err(FetchProbeInformationError::Api { status, body });
     }
 
     let probe_information: ProbeInformation =
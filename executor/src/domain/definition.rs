@@ -249,7 +249,7 @@ impl HttpDefinition {
     }
 
     pub fn target(mut self, target: String) -> Self {
-        self.target = target.into();
+        self.target = target;
         self
     }
 
@@ -361,7 +361,7 @@ impl TracerouteDefinition {
     }
 
     pub fn target(mut self, target: String) -> Self {
-        self.target = target.into();
+        self.target = target;
         self
     }
 
@@ -38,7 +38,7 @@ pub fn generate_connections(
     }
 }
 
-fn generate_all_to_all_connections(probes: &Vec<ProbeInformation>) -> Vec<TargetWithSources> {
+fn generate_all_to_all_connections(probes: &[ProbeInformation]) -> Vec<TargetWithSources> {
     let mut configurations = Vec::with_capacity(probes.len());
 
     for (i, target_probe) in probes
@@ -0,0 +1,499 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, error::Error, fs, path::Path};
+
+use crate::{
+    analysis::{paths::TracePath, samples::Samples, stats},
+    api::results::AggregatedMeasurement,
+};
+
+/// An alert rule file, a list of `[[rule]]` tables such as
+///
+/// ```toml
+/// [[rule]]
+/// name = "slow-path"
+/// type = "ping"
+/// probes = [6817]
+/// target = "45.77.229.242"
+/// metric = "rtt"
+/// op = ">"
+/// value = 80
+/// consecutive = 3
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRules {
+    #[serde(rename = "rule", default)]
+    pub rules: Vec<AlertRule>,
+}
+
+/// A condition on one metric of the results of a pair. Results that do not match the filters
+/// are ignored by the rule.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRule {
+    pub name: String,
+    /// Measurement type, ping, http or traceroute
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub msm_id: Option<u32>,
+    /// Source probes, all if empty
+    #[serde(default)]
+    pub probes: Vec<u32>,
+    /// Target address, for HTTP also the host of the URI
+    pub target: Option<String>,
+    pub metric: Metric,
+    pub op: Comparison,
+    pub value: f64,
+    /// Number of consecutive results of a pair that have to match before the alert fires
+    #[serde(default = "one")]
+    pub consecutive: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Median RTT of the result in milliseconds
+    Rtt,
+    /// Share of lost probes in percent
+    Loss,
+    /// Status code of an HTTP result, 0 if the request got no response
+    HttpStatus,
+    /// 1 if a traceroute reached its target, 0 if not
+    Reached,
+    /// Number of hops of a traceroute
+    Hops,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Comparison {
+    #[serde(rename = ">")]
+    Greater,
+    #[serde(rename = ">=")]
+    GreaterOrEqual,
+    #[serde(rename = "<")]
+    Less,
+    #[serde(rename = "<=")]
+    LessOrEqual,
+    #[serde(rename = "==")]
+    Equal,
+    #[serde(rename = "!=")]
+    NotEqual,
+}
+
+impl Metric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Metric::Rtt => "rtt",
+            Metric::Loss => "loss",
+            Metric::HttpStatus => "http_status",
+            Metric::Reached => "reached",
+            Metric::Hops => "hops",
+        }
+    }
+
+    /// The value of the metric for one result, `None` if it does not apply to the result, such
+    /// as the RTT of a ping without replies or the hop count of an HTTP request.
+    pub fn value(&self, measurement: &AggregatedMeasurement) -> Option<f64> {
+        match (self, measurement) {
+            (Metric::Rtt, _) => {
+                stats::median(&Samples::from_measurement(measurement).rtts).map(f64::from)
+            }
+            (Metric::Loss, _) => Samples::from_measurement(measurement).loss().map(f64::from),
+            (Metric::HttpStatus, AggregatedMeasurement::Http(h)) => {
+                Some(h.result.iter().find_map(|r| r.res).unwrap_or(0) as f64)
+            }
+            (Metric::Reached, AggregatedMeasurement::TraceRoute(t)) => {
+                Some(TracePath::from_traceroute_measurement(t).reached as u8 as f64)
+            }
+            (Metric::Hops, AggregatedMeasurement::TraceRoute(t)) => {
+                Some(TracePath::from_traceroute_measurement(t).length() as f64)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Comparison {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+        }
+    }
+
+    pub fn holds(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Greater => value > threshold,
+            Comparison::GreaterOrEqual => value >= threshold,
+            Comparison::Less => value < threshold,
+            Comparison::LessOrEqual => value <= threshold,
+            Comparison::Equal => value == threshold,
+            Comparison::NotEqual => value != threshold,
+        }
+    }
+}
+
+impl AlertRule {
+    fn matches(&self, measurement: &AggregatedMeasurement) -> bool {
+        self.kind.as_deref().is_none_or(|k| k == measurement.kind())
+            && self.msm_id.is_none_or(|id| id == measurement.msm_id())
+            && (self.probes.is_empty() || self.probes.contains(&measurement.prb_id()))
+            && self.target.as_deref().is_none_or(|t| {
+                Some(t) == measurement.dst_addr() || Some(t) == measurement.target()
+            })
+    }
+}
+
+impl AlertRules {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let rules: AlertRules = toml::from_str(&fs::read_to_string(path)?)?;
+        for rule in &rules.rules {
+            if rule.consecutive == 0 {
+                return Err(format!(
+                    "{}: rule {}: consecutive must be at least 1",
+                    path.display(),
+                    rule.name
+                )
+                .into());
+            }
+        }
+        Ok(rules)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+/// A rule that started or stopped matching a pair.
+#[derive(Debug, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    pub status: AlertStatus,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub msm_id: u32,
+    pub prb_id: u32,
+    /// The target of the measurement, for HTTP the host of the URI
+    pub dst_addr: String,
+    /// Time of the result that fired or resolved the alert
+    pub timestamp: usize,
+    /// Time of the first of the consecutive matching results
+    pub since: usize,
+    pub metric: Metric,
+    pub op: Comparison,
+    pub threshold: f64,
+    pub value: f64,
+}
+
+/// How far a rule got with one pair.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PairState {
+    last_timestamp: usize,
+    matches: u32,
+    since: usize,
+    firing: bool,
+}
+
+/// Evaluates the rules against results as they come in. An alert fires once per episode, when
+/// a pair first matched a rule for `consecutive` results, and resolves with the first result
+/// that no longer matches. The state can be saved, so reruns over the same results or a
+/// restarted watch neither repeat nor forget alerts.
+#[derive(Debug)]
+pub struct Evaluator {
+    rules: Vec<AlertRule>,
+    pairs: BTreeMap<String, PairState>,
+}
+
+impl Evaluator {
+    pub fn new(rules: AlertRules) -> Self {
+        Evaluator {
+            rules: rules.rules,
+            pairs: BTreeMap::new(),
+        }
+    }
+
+    /// Reads saved state, an absent file starts without any firing alerts.
+    pub fn load_state(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if path.exists() {
+            self.pairs = serde_json::from_str(&fs::read_to_string(path)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn save_state(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&self.pairs)?)?;
+        Ok(())
+    }
+
+    /// Results are evaluated in time order. Results that are not newer than the last one
+    /// evaluated for their pair are skipped. Pairs are keyed on the measurement target rather
+    /// than the address, so HTTP requests that failed before connecting count as well.
+    pub fn evaluate(&mut self, measurements: &[AggregatedMeasurement]) -> Vec<Alert> {
+        let mut ordered: Vec<&AggregatedMeasurement> = measurements.iter().collect();
+        ordered.sort_by_key(|m| m.timestamp());
+
+        let mut alerts = Vec::new();
+        for measurement in ordered {
+            let Some(target) = measurement.target() else {
+                continue;
+            };
+            for rule in self.rules.iter().filter(|rule| rule.matches(measurement)) {
+                let Some(value) = rule.metric.value(measurement) else {
+                    continue;
+                };
+                let key = pair_key(
+                    &rule.name,
+                    measurement.msm_id(),
+                    measurement.prb_id(),
+                    target,
+                );
+                let state = self.pairs.entry(key).or_default();
+                let timestamp = measurement.timestamp();
+                if timestamp <= state.last_timestamp {
+                    continue;
+                }
+                state.last_timestamp = timestamp;
+
+                let status = if rule.op.holds(value, rule.value) {
+                    if state.matches == 0 {
+                        state.since = timestamp;
+                    }
+                    state.matches += 1;
+                    if state.firing || state.matches < rule.consecutive {
+                        continue;
+                    }
+                    state.firing = true;
+                    AlertStatus::Firing
+                } else {
+                    state.matches = 0;
+                    if !state.firing {
+                        continue;
+                    }
+                    state.firing = false;
+                    AlertStatus::Resolved
+                };
+
+                alerts.push(Alert {
+                    rule: rule.name.clone(),
+                    status,
+                    kind: measurement.kind().to_string(),
+                    msm_id: measurement.msm_id(),
+                    prb_id: measurement.prb_id(),
+                    dst_addr: target.to_string(),
+                    timestamp,
+                    since: state.since,
+                    metric: rule.metric,
+                    op: rule.op,
+                    threshold: rule.value,
+                    value,
+                });
+            }
+        }
+        alerts
+    }
+
+    /// Takes back an alert that could not be delivered. Its pair goes back to the previous
+    /// state, so the alert is raised again with the next result that still warrants it.
+    pub fn retract(&mut self, alert: &Alert) {
+        let key = pair_key(&alert.rule, alert.msm_id, alert.prb_id, &alert.dst_addr);
+        if let Some(state) = self.pairs.get_mut(&key) {
+            state.firing = alert.status == AlertStatus::Resolved;
+        }
+    }
+}
+
+fn pair_key(rule: &str, msm_id: u32, prb_id: u32, target: &str) -> String {
+    format!("{}|{}|{}|{}", rule, msm_id, prb_id, target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{http, http_dns_error, http_json, ping, temp_dir, traceroute};
+
+    fn evaluator(rules: &str) -> Evaluator {
+        Evaluator::new(toml::from_str(rules).unwrap())
+    }
+
+    const SLOW: &str = r#"
+        [[rule]]
+        name = "slow"
+        type = "ping"
+        metric = "rtt"
+        op = ">"
+        value = 50
+        consecutive = 3
+    "#;
+
+    fn pings(rtts: &[f32]) -> Vec<AggregatedMeasurement> {
+        rtts.iter()
+            .enumerate()
+            .map(|(i, rtt)| ping(1001, 10, 100 * (i + 1), &[Some(*rtt)]))
+            .collect()
+    }
+
+    fn statuses(alerts: &[Alert]) -> Vec<(AlertStatus, usize, usize)> {
+        alerts
+            .iter()
+            .map(|alert| (alert.status, alert.timestamp, alert.since))
+            .collect()
+    }
+
+    #[test]
+    fn fires_after_consecutive_matches_once_and_resolves() {
+        let mut evaluator = evaluator(SLOW);
+        let alerts = evaluator.evaluate(&pings(&[10.0, 60.0, 70.0, 10.0, 60.0, 70.0, 80.0, 90.0]));
+        assert_eq!(statuses(&alerts), [(AlertStatus::Firing, 700, 500)]);
+        assert_eq!(alerts[0].value, 80.0);
+
+        // Results are evaluated in time order, whatever order they arrive in.
+        let mut later = pings(&[0.0; 10]);
+        later.drain(..8);
+        later.reverse();
+        let alerts = evaluator.evaluate(&later);
+        assert_eq!(statuses(&alerts), [(AlertStatus::Resolved, 900, 500)]);
+
+        // Nothing new, nothing to report.
+        assert!(evaluator.evaluate(&later).is_empty());
+    }
+
+    #[test]
+    fn state_survives_a_restart() {
+        let path = temp_dir("alert-state").join("state.json");
+        let mut first = evaluator(SLOW);
+        let results = pings(&[60.0, 70.0, 80.0]);
+        assert_eq!(first.evaluate(&results).len(), 1);
+        first.save_state(&path).unwrap();
+
+        let mut second = evaluator(SLOW);
+        second.load_state(&path).unwrap();
+        assert!(second.evaluate(&results).is_empty());
+        let alerts = second.evaluate(&[ping(1001, 10, 400, &[Some(10.0)])]);
+        assert_eq!(statuses(&alerts), [(AlertStatus::Resolved, 400, 100)]);
+
+        let mut fresh = evaluator(SLOW);
+        fresh
+            .load_state(&path.with_file_name("absent.json"))
+            .unwrap();
+        assert_eq!(fresh.evaluate(&results).len(), 1);
+    }
+
+    #[test]
+    fn rules_only_apply_to_matching_results() {
+        let mut evaluator = evaluator(
+            r#"
+            [[rule]]
+            name = "server-error"
+            probes = [10]
+            metric = "http_status"
+            op = ">="
+            value = 500
+
+            [[rule]]
+            name = "unreachable"
+            msm_id = 2001
+            target = "192.0.2.1"
+            metric = "reached"
+            op = "=="
+            value = 0
+            "#,
+        );
+        let alerts = evaluator.evaluate(&[
+            http(1001, 10, 100, Some(503)),
+            http(1001, 11, 100, Some(503)),
+            http(1001, 12, 100, None),
+            ping(1001, 10, 100, &[None]),
+            traceroute(2001, 10, 100, &["10.0.0.1"], false),
+            traceroute(2002, 10, 100, &["10.0.0.1"], false),
+        ]);
+        let fired: Vec<(&str, &str, u32, u32)> = alerts
+            .iter()
+            .map(|a| (a.rule.as_str(), a.kind.as_str(), a.msm_id, a.prb_id))
+            .collect();
+        assert_eq!(
+            fired,
+            [
+                ("server-error", "http", 1001, 10),
+                ("unreachable", "traceroute", 2001, 10)
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_http_requests_count_against_the_target() {
+        let mut evaluator = evaluator(
+            r#"
+            [[rule]]
+            name = "down"
+            type = "http"
+            target = "www.example.org"
+            metric = "http_status"
+            op = "!="
+            value = 200
+            "#,
+        );
+        let alerts = evaluator.evaluate(&[http_dns_error(3001, 10, 100)]);
+        assert_eq!(statuses(&alerts), [(AlertStatus::Firing, 100, 100)]);
+        assert_eq!(
+            (alerts[0].dst_addr.as_str(), alerts[0].value),
+            ("www.example.org", 0.0)
+        );
+
+        // A request that got through goes to an address, but is still the same pair.
+        let mut ok = http_json(3001, 10, 200, Some(200));
+        ok["uri"] = "http://www.example.org/".into();
+        let alerts = evaluator.evaluate(&[serde_json::from_value(ok).unwrap()]);
+        assert_eq!(statuses(&alerts), [(AlertStatus::Resolved, 200, 100)]);
+    }
+
+    #[test]
+    fn retracted_alerts_are_raised_again() {
+        let mut evaluator = evaluator(SLOW);
+        let alerts = evaluator.evaluate(&pings(&[60.0, 70.0, 80.0]));
+        evaluator.retract(&alerts[0]);
+        let alerts = evaluator.evaluate(&[ping(1001, 10, 400, &[Some(90.0)])]);
+        assert_eq!(statuses(&alerts), [(AlertStatus::Firing, 400, 100)]);
+
+        // Same for resolving, the pair stays firing until a resolution got through.
+        let alerts = evaluator.evaluate(&[ping(1001, 10, 500, &[Some(10.0)])]);
+        evaluator.retract(&alerts[0]);
+        let alerts = evaluator.evaluate(&[ping(1001, 10, 600, &[Some(10.0)])]);
+        assert_eq!(statuses(&alerts), [(AlertStatus::Resolved, 600, 100)]);
+        assert!(
+            evaluator
+                .evaluate(&[ping(1001, 10, 700, &[Some(10.0)])])
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        let dir = temp_dir("alert-rules");
+        for (name, rules) in [
+            (
+                "zero.toml",
+                SLOW.replace("consecutive = 3", "consecutive = 0"),
+            ),
+            ("unknown.toml", SLOW.replace("consecutive", "consecutively")),
+            ("op.toml", SLOW.replace("\">\"", "\"=>\"")),
+        ] {
+            fs::write(dir.join(name), rules).unwrap();
+            assert!(AlertRules::load(&dir.join(name)).is_err(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{
+    analysis::{samples::Samples, stats},
+    api::results::AggregatedMeasurement,
+};
+
+/// Tuning of the anomaly detection.
+#[derive(Debug, Clone, Copy)]
+pub struct Detector {
+    /// Number of preceding results forming the baseline, and of following results a level
+    /// shift has to persist for.
+    pub window: usize,
+    /// How many robust standard deviations (scaled MAD) a value has to be off the baseline.
+    pub threshold: f32,
+    /// Smallest RTT deviation in milliseconds that counts, so flat series with a tiny MAD do not
+    /// flag jitter.
+    pub min_delta: f32,
+    /// Loss in percent from which a result counts towards a loss burst.
+    pub loss_threshold: f32,
+    /// Minimum number of consecutive lossy results that make a burst.
+    pub min_burst: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    LevelShift,
+    Spike,
+    LossBurst,
+}
+
+/// An anomaly in the time series of one pair. For RTT anomalies `baseline` and `value` are
+/// medians in milliseconds, for loss bursts mean loss in percent. `magnitude` is their difference.
+#[derive(Debug, Serialize)]
+pub struct AnomalyEvent {
+    pub anomaly: AnomalyKind,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub prb_id: u32,
+    pub dst_addr: String,
+    pub start: i64,
+    pub end: i64,
+    pub results: usize,
+    pub baseline: Option<f32>,
+    pub value: f32,
+    pub magnitude: Option<f32>,
+}
+
+/// Events of the same kind and measurement type that overlap in time, usually one incident
+/// seen by several pairs.
+#[derive(Debug, Serialize)]
+pub struct Incident {
+    pub anomaly: AnomalyKind,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub start: i64,
+    pub end: i64,
+    pub events: usize,
+    /// Affected pairs as `prb_id>dst_addr`, separated by `;`.
+    pub pairs: String,
+    pub max_magnitude: Option<f32>,
+}
+
+#[derive(Debug)]
+struct Point {
+    timestamp: i64,
+    rtt: Option<f32>,
+    loss: Option<f32>,
+}
+
+/// Splits the results into time series per measurement type, source probe and target. Every
+/// result contributes the median of its RTTs and its loss.
+fn series(measurements: &[AggregatedMeasurement]) -> BTreeMap<(String, u32, String), Vec<Point>> {
+    let mut series: BTreeMap<(String, u32, String), Vec<Point>> = BTreeMap::new();
+    for measurement in measurements {
+        let Some(dst_addr) = measurement.dst_addr() else {
+            continue;
+        };
+        let samples = Samples::from_measurement(measurement);
+        series
+            .entry((
+                measurement.kind().to_string(),
+                measurement.prb_id(),
+                dst_addr.to_string(),
+            ))
+            .or_default()
+            .push(Point {
+                timestamp: measurement.timestamp() as i64,
+                rtt: stats::median(&samples.rtts),
+                loss: samples.loss(),
+            });
+    }
+    for points in series.values_mut() {
+        points.sort_by_key(|point| point.timestamp);
+    }
+    series
+}
+
+impl Detector {
+    fn deviates(&self, baseline: &[f32], value: f32) -> bool {
+        let (Some(median), Some(mad)) = (stats::median(baseline), stats::mad(baseline)) else {
+            return false;
+        };
+        let delta = (value - median).abs();
+        delta >= self.min_delta && delta > self.threshold * mad
+    }
+
+    /// Level shifts compare the median of `window` results before and after every point, and
+    /// report the point where the difference is largest. Spikes are the remaining points above
+    /// the median of the preceding `window` results, consecutive ones merged into one event.
+    fn rtt_events(&self, rtts: &[(i64, f32)]) -> Vec<(AnomalyKind, usize, usize, f32, f32)> {
+        let w = self.window.max(1);
+        let values: Vec<f32> = rtts.iter().map(|(_, rtt)| *rtt).collect();
+        let mut events = Vec::new();
+        let mut shifted = vec![false; values.len()];
+
+        let mut i = w;
+        let mut floor = 0;
+        while i + w <= values.len() {
+            let before = &values[i - w..i];
+            let after_median = stats::median(&values[i..i + w]).unwrap_or_default();
+            if !self.deviates(before, after_median) {
+                i += 1;
+                continue;
+            }
+            // The shift may be detected a few points early, move to where it is strongest.
+            let mut best = i;
+            let mut best_delta = 0.0;
+            for j in i..(i + w).min(values.len() - w + 1) {
+                let delta = (stats::median(&values[j..j + w]).unwrap_or_default()
+                    - stats::median(&values[j - w..j]).unwrap_or_default())
+                .abs();
+                if delta > best_delta {
+                    best = j;
+                    best_delta = delta;
+                }
+            }
+            // Noise and equal medians around a short plateau can put the start a point off,
+            // align it with the first point that is closer to the new level than to the old one.
+            let value = stats::median(&values[best..best + w]).unwrap_or_default();
+            let closer = |j: usize| {
+                let baseline = stats::median(&values[j - w..j]).unwrap_or_default();
+                (values[j] - value).abs() <= (values[j] - baseline).abs()
+            };
+            while best > floor.max(w) && closer(best - 1) {
+                best -= 1;
+            }
+            while best + w < values.len() {
+                let baseline = stats::median(&values[best - w..best]).unwrap_or_default();
+                if (values[best] - value).abs() <= (values[best] - baseline).abs() {
+                    break;
+                }
+                best += 1;
+            }
+            let baseline = stats::median(&values[best - w..best]).unwrap_or_default();
+            let value = stats::median(&values[best..best + w]).unwrap_or_default();
+            events.push((AnomalyKind::LevelShift, best, best + w - 1, baseline, value));
+            shifted[best..best + w].fill(true);
+            i = best + w;
+            floor = i;
+        }
+
+        let mut spike: Option<(usize, usize, f32, f32)> = None;
+        for i in w..values.len() {
+            let baseline = &values[i - w..i];
+            let median = stats::median(baseline).unwrap_or_default();
+            if !shifted[i] && values[i] > median && self.deviates(baseline, values[i]) {
+                spike = Some(match spike {
+                    Some((start, _, base, peak)) => (start, i, base, peak.max(values[i])),
+                    None => (i, i, median, values[i]),
+                });
+                continue;
+            }
+            if let Some((start, end, baseline, peak)) = spike.take() {
+                events.push((AnomalyKind::Spike, start, end, baseline, peak));
+            }
+        }
+        if let Some((start, end, baseline, peak)) = spike {
+            events.push((AnomalyKind::Spike, start, end, baseline, peak));
+        }
+
+        events
+    }
+}
+
+pub fn detect(measurements: &[AggregatedMeasurement], detector: &Detector) -> Vec<AnomalyEvent> {
+    let mut events = Vec::new();
+
+    for ((kind, prb_id, dst_addr), points) in series(measurements) {
+        let event = |anomaly, start: i64, end: i64, results, baseline: Option<f32>, value: f32| {
+            AnomalyEvent {
+                anomaly,
+                kind: kind.clone(),
+                prb_id,
+                dst_addr: dst_addr.clone(),
+                start,
+                end,
+                results,
+                baseline,
+                value,
+                magnitude: baseline.map(|b| value - b),
+            }
+        };
+
+        let rtts: Vec<(i64, f32)> = points
+            .iter()
+            .filter_map(|point| point.rtt.map(|rtt| (point.timestamp, rtt)))
+            .collect();
+        for (anomaly, start, end, baseline, value) in detector.rtt_events(&rtts) {
+            events.push(event(
+                anomaly,
+                rtts[start].0,
+                rtts[end].0,
+                end - start + 1,
+                Some(baseline),
+                value,
+            ));
+        }
+
+        let mut burst: Vec<usize> = Vec::new();
+        for i in 0..=points.len() {
+            let lossy = points
+                .get(i)
+                .and_then(|point| point.loss)
+                .is_some_and(|loss| loss >= detector.loss_threshold);
+            if lossy {
+                burst.push(i);
+                continue;
+            }
+            if burst.len() >= detector.min_burst.max(1) {
+                let start = burst[0];
+                let losses: Vec<f32> = burst.iter().filter_map(|&j| points[j].loss).collect();
+                let before: Vec<f32> = points[start.saturating_sub(detector.window)..start]
+                    .iter()
+                    .filter_map(|point| point.loss)
+                    .collect();
+                events.push(event(
+                    AnomalyKind::LossBurst,
+                    points[start].timestamp,
+                    points[burst[burst.len() - 1]].timestamp,
+                    burst.len(),
+                    stats::mean(&before),
+                    stats::mean(&losses).unwrap_or_default(),
+                ));
+            }
+            burst.clear();
+        }
+    }
+
+    events.sort_by_key(|event| (event.start, event.anomaly));
+    events
+}
+
+/// Merges events of the same anomaly and measurement type whose time ranges overlap or lie at
+/// most `gap` seconds apart.
+pub fn incidents(events: &[AnomalyEvent], gap: i64) -> Vec<Incident> {
+    let mut groups: BTreeMap<(AnomalyKind, &str), Vec<&AnomalyEvent>> = BTreeMap::new();
+    for event in events {
+        groups
+            .entry((event.anomaly, event.kind.as_str()))
+            .or_default()
+            .push(event);
+    }
+
+    let mut incidents = Vec::new();
+    for ((anomaly, kind), mut group) in groups {
+        group.sort_by_key(|event| event.start);
+        let mut current: Vec<&AnomalyEvent> = Vec::new();
+        let mut end = i64::MIN;
+        for event in group.into_iter().map(Some).chain([None]) {
+            if let Some(event) = event
+                && (current.is_empty() || event.start <= end.saturating_add(gap))
+            {
+                end = end.max(event.end);
+                current.push(event);
+                continue;
+            }
+            if !current.is_empty() {
+                let mut pairs: Vec<String> = current
+                    .iter()
+                    .map(|event| format!("{}>{}", event.prb_id, event.dst_addr))
+                    .collect();
+                pairs.sort();
+                pairs.dedup();
+                incidents.push(Incident {
+                    anomaly,
+                    kind: kind.to_string(),
+                    start: current[0].start,
+                    end,
+                    events: current.len(),
+                    pairs: pairs.join(";"),
+                    max_magnitude: current
+                        .iter()
+                        .filter_map(|event| event.magnitude)
+                        .max_by(|a, b| a.abs().total_cmp(&b.abs())),
+                });
+            }
+            current.clear();
+            end = i64::MIN;
+            if let Some(event) = event {
+                end = event.end;
+                current.push(event);
+            }
+        }
+    }
+
+    incidents.sort_by_key(|incident| (incident.start, incident.anomaly));
+    incidents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ping;
+
+    const DETECTOR: Detector = Detector {
+        window: 5,
+        threshold: 3.0,
+        min_delta: 5.0,
+        loss_threshold: 50.0,
+        min_burst: 2,
+    };
+
+    /// One ping result a minute with a single reply per RTT, `None` for a result without any.
+    fn series(rtts: &[Option<f32>]) -> Vec<AggregatedMeasurement> {
+        rtts.iter()
+            .enumerate()
+            .map(|(i, rtt)| ping(1, 10, 60 * i, &[*rtt]))
+            .collect()
+    }
+
+    fn noisy(level: f32, len: usize) -> Vec<Option<f32>> {
+        (0..len)
+            .map(|i| Some(level + [0.0, 1.0, 0.5, 1.5][i % 4]))
+            .collect()
+    }
+
+    #[test]
+    fn stable_series_has_no_anomalies() {
+        assert!(detect(&series(&noisy(10.0, 30)), &DETECTOR).is_empty());
+    }
+
+    #[test]
+    fn detects_level_shift_where_it_starts() {
+        let mut rtts = noisy(10.0, 12);
+        rtts.extend(noisy(50.0, 12));
+        let events = detect(&series(&rtts), &DETECTOR);
+
+        assert_eq!(events.len(), 1, "{:?}", events);
+        let shift = &events[0];
+        assert_eq!(shift.anomaly, AnomalyKind::LevelShift);
+        assert_eq!(shift.start, 60 * 12);
+        assert_eq!(shift.results, DETECTOR.window);
+        assert!((shift.magnitude.unwrap() - 40.0).abs() <= 1.5);
+    }
+
+    #[test]
+    fn merges_consecutive_spikes() {
+        let mut rtts = noisy(10.0, 20);
+        rtts[8] = Some(80.0);
+        rtts[9] = Some(95.0);
+        rtts[15] = Some(60.0);
+        let events = detect(&series(&rtts), &DETECTOR);
+
+        let spikes: Vec<(i64, i64, f32)> = events
+            .iter()
+            .map(|e| {
+                assert_eq!(e.anomaly, AnomalyKind::Spike);
+                (e.start, e.end, e.value)
+            })
+            .collect();
+        assert_eq!(spikes, [(480, 540, 95.0), (900, 900, 60.0)]);
+    }
+
+    #[test]
+    fn detects_loss_bursts_of_minimum_length() {
+        let mut rtts = noisy(10.0, 20);
+        rtts[5] = None;
+        rtts[10] = None;
+        rtts[11] = None;
+        rtts[12] = None;
+        let events = detect(&series(&rtts), &DETECTOR);
+
+        assert_eq!(events.len(), 1, "{:?}", events);
+        let burst = &events[0];
+        assert_eq!(burst.anomaly, AnomalyKind::LossBurst);
+        assert_eq!((burst.start, burst.end, burst.results), (600, 720, 3));
+        assert_eq!(burst.baseline, Some(20.0));
+        assert_eq!(burst.value, 100.0);
+    }
+
+    #[test]
+    fn overlapping_events_of_several_pairs_form_one_incident() {
+        let mut measurements = Vec::new();
+        for prb_id in [10, 11] {
+            for i in 0..10 {
+                let rtt = if (4..7).contains(&i) {
+                    None
+                } else {
+                    Some(10.0)
+                };
+                measurements.push(ping(1, prb_id, 60 * i + prb_id as usize, &[rtt]));
+            }
+        }
+        let events = detect(&measurements, &DETECTOR);
+        assert_eq!(events.len(), 2);
+
+        let incidents = incidents(&events, 0);
+        assert_eq!(incidents.len(), 1);
+        assert_eq!(incidents[0].events, 2);
+        assert_eq!(incidents[0].pairs, "10>192.0.2.1;11>192.0.2.1");
+        assert_eq!((incidents[0].start, incidents[0].end), (250, 371));
+    }
+}
@@ -0,0 +1,156 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    api::results::{AggregatedMeasurement, TraceRouteMeasurement},
+    asn::AsnTable,
+};
+
+/// One address that answered on a traceroute hop together with its origin AS.
+#[derive(Debug, Serialize)]
+pub struct HopAsn {
+    pub prb_id: u32,
+    pub dst_addr: String,
+    pub msm_id: u32,
+    pub timestamp: usize,
+    pub hop: u32,
+    pub address: String,
+    /// Origin AS of the address, multiple origins are joined by `_`.
+    pub asn: Option<String>,
+}
+
+/// The AS-level path of one traceroute run.
+#[derive(Debug, Serialize)]
+pub struct AsPathRun {
+    pub prb_id: u32,
+    pub dst_addr: String,
+    pub msm_id: u32,
+    pub timestamp: usize,
+    /// Origin ASes in path order separated by spaces, consecutive hops in the same AS are
+    /// collapsed and hops without a known origin are left out.
+    pub as_path: String,
+    pub as_path_length: usize,
+    pub unmapped_hops: u32,
+    pub reached: bool,
+}
+
+/// The AS-level routes a source probe and target pair took over the campaign.
+#[derive(Debug, Serialize)]
+pub struct AsPathPair {
+    pub prb_id: u32,
+    pub dst_addr: String,
+    pub runs: usize,
+    /// Every AS seen on any run, separated by `;`.
+    pub ases: String,
+    pub distinct_as_paths: usize,
+    pub dominant_as_path: String,
+    pub dominant_share: f32,
+    pub min_length: usize,
+    pub max_length: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct AsPaths {
+    pub hops: Vec<HopAsn>,
+    pub runs: Vec<AsPathRun>,
+    pub pairs: Vec<AsPathPair>,
+}
+
+/// Origin ASes along the path of one run, without consecutive repetitions. A load balanced hop
+/// that spans several ASes contributes each of them. Also returns the number of answering hops
+/// that had no origin in the table.
+pub fn as_path(measurement: &TraceRouteMeasurement, table: &AsnTable) -> (Vec<String>, u32) {
+    let mut path: Vec<String> = Vec::new();
+    let mut unmapped = 0;
+
+    for hop in &measurement.result {
+        let addresses = hop.addresses();
+        let origins: Vec<String> = addresses
+            .iter()
+            .filter_map(|address| table.origin(address))
+            .collect();
+        if origins.is_empty() && !addresses.is_empty() {
+            unmapped += 1;
+        }
+        for origin in origins {
+            if path.last() != Some(&origin) {
+                path.push(origin);
+            }
+        }
+    }
+
+    (path, unmapped)
+}
+
+pub fn as_paths(measurements: &[AggregatedMeasurement], table: &AsnTable) -> AsPaths {
+    let mut result = AsPaths::default();
+    let mut pairs: BTreeMap<(u32, String), Vec<Vec<String>>> = BTreeMap::new();
+
+    let mut traceroutes: Vec<&TraceRouteMeasurement> = measurements
+        .iter()
+        .filter_map(|measurement| match measurement {
+            AggregatedMeasurement::TraceRoute(t) => Some(t),
+            _ => None,
+        })
+        .collect();
+    traceroutes.sort_by_key(|t| (t.prb_id, t.dst_addr.clone(), t.timestamp));
+
+    for t in traceroutes {
+        for hop in &t.result {
+            for address in hop.addresses() {
+                result.hops.push(HopAsn {
+                    prb_id: t.prb_id,
+                    dst_addr: t.dst_addr.clone(),
+                    msm_id: t.msm_id,
+                    timestamp: t.timestamp,
+                    hop: hop.hop,
+                    address: address.to_string(),
+                    asn: table.origin(address),
+                });
+            }
+        }
+
+        let (path, unmapped_hops) = as_path(t, table);
+        result.runs.push(AsPathRun {
+            prb_id: t.prb_id,
+            dst_addr: t.dst_addr.clone(),
+            msm_id: t.msm_id,
+            timestamp: t.timestamp,
+            as_path: path.join(" "),
+            as_path_length: path.len(),
+            unmapped_hops,
+            reached: t.destination_ip_responded,
+        });
+        pairs
+            .entry((t.prb_id, t.dst_addr.clone()))
+            .or_default()
+            .push(path);
+    }
+
+    for ((prb_id, dst_addr), paths) in pairs {
+        let ases: BTreeSet<&str> = paths.iter().flatten().map(String::as_str).collect();
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for path in &paths {
+            *counts.entry(path.join(" ")).or_default() += 1;
+        }
+        let (dominant_as_path, dominant_runs) = counts
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .map(|(path, count)| (path.clone(), *count))
+            .unwrap_or_default();
+
+        result.pairs.push(AsPathPair {
+            prb_id,
+            dst_addr,
+            runs: paths.len(),
+            ases: ases.into_iter().collect::<Vec<_>>().join(";"),
+            distinct_as_paths: counts.len(),
+            dominant_as_path,
+            dominant_share: dominant_runs as f32 / paths.len() as f32 * 100.0,
+            min_length: paths.iter().map(Vec::len).min().unwrap_or_default(),
+            max_length: paths.iter().map(Vec::len).max().unwrap_or_default(),
+        });
+    }
+
+    result
+}
@@ -0,0 +1,179 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{
+    analysis::{matrix::Node, paths::TracePath, samples::Samples, stats},
+    api::results::AggregatedMeasurement,
+    enrichment::ProbeDirectory,
+};
+
+type Pairs = BTreeMap<(String, u32, Node), (Side, Side)>;
+
+#[derive(Debug, Default)]
+struct Side {
+    results: u32,
+    samples: Samples,
+    ttfb: Vec<f32>,
+    path_lengths: Vec<f32>,
+}
+
+/// How a pair changed from the baseline to the candidate campaign. Deltas are candidate minus
+/// baseline, so positive RTT and loss deltas are regressions.
+#[derive(Debug, Serialize)]
+pub struct PairDelta {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub prb_id: u32,
+    /// Probe id of the target if the probe cache knows it, its address otherwise.
+    pub target: Node,
+    pub baseline_results: u32,
+    pub candidate_results: u32,
+    pub baseline_median: Option<f32>,
+    pub candidate_median: Option<f32>,
+    pub median_delta: Option<f32>,
+    /// Mann-Whitney U test of the RTT samples of both campaigns.
+    pub rtt_p_value: Option<f32>,
+    pub baseline_loss: Option<f32>,
+    pub candidate_loss: Option<f32>,
+    pub loss_delta: Option<f32>,
+    /// Two proportion z-test of the lost probes of both campaigns.
+    pub loss_p_value: Option<f32>,
+    pub baseline_ttfb: Option<f32>,
+    pub candidate_ttfb: Option<f32>,
+    pub ttfb_delta: Option<f32>,
+    pub baseline_path_length: Option<f32>,
+    pub candidate_path_length: Option<f32>,
+    pub path_length_delta: Option<f32>,
+    /// `regression`, `improvement`, `mixed` or `unchanged`, from the significant RTT and loss
+    /// changes only.
+    pub verdict: String,
+}
+
+/// Pairs are matched by measurement type, source probe id and target. With metadata in
+/// `probes`, targets are matched by the probe owning the address, so a re-run against anchors
+/// whose addresses changed still lines up.
+fn collect(
+    measurements: &[AggregatedMeasurement],
+    probes: &ProbeDirectory,
+    pairs: &mut Pairs,
+    candidate: bool,
+) {
+    for measurement in measurements {
+        let Some(dst_addr) = measurement.dst_addr() else {
+            continue;
+        };
+        let target = probes
+            .by_address(dst_addr)
+            .map(|probe| Node::Probe(probe.prb_id))
+            .unwrap_or_else(|| Node::Address(dst_addr.to_string()));
+        let sides = pairs
+            .entry((measurement.kind().to_string(), measurement.prb_id(), target))
+            .or_default();
+        let side = if candidate {
+            &mut sides.1
+        } else {
+            &mut sides.0
+        };
+
+        side.results += 1;
+        side.samples.add(Samples::from_measurement(measurement));
+        match measurement {
+            AggregatedMeasurement::Http(h) => side.ttfb.extend(
+                h.result
+                    .iter()
+                    .filter(|r| r.error_kind().is_none())
+                    .filter_map(|r| r.ttfb),
+            ),
+            AggregatedMeasurement::TraceRoute(t) => side
+                .path_lengths
+                .push(TracePath::from_traceroute_measurement(t).length() as f32),
+            AggregatedMeasurement::Ping(_) => {}
+        }
+    }
+}
+
+fn delta(baseline: Option<f32>, candidate: Option<f32>) -> Option<f32> {
+    Some(candidate? - baseline?)
+}
+
+/// Compares every pair measured in both campaigns. A change counts when its p-value is below
+/// `alpha`. The result is sorted from the biggest median RTT regression to the biggest
+/// improvement.
+pub fn compare(
+    baseline: &[AggregatedMeasurement],
+    candidate: &[AggregatedMeasurement],
+    probes: &ProbeDirectory,
+    alpha: f32,
+) -> Vec<PairDelta> {
+    let mut sides = Pairs::new();
+    collect(baseline, probes, &mut sides, false);
+    collect(candidate, probes, &mut sides, true);
+
+    let mut deltas: Vec<PairDelta> = sides
+        .into_iter()
+        .filter(|(_, (a, b))| a.results > 0 && b.results > 0)
+        .map(|((kind, prb_id, target), (a, b))| {
+            let baseline_median = stats::median(&a.samples.rtts);
+            let candidate_median = stats::median(&b.samples.rtts);
+            let median_delta = delta(baseline_median, candidate_median);
+            let rtt_p_value = stats::mann_whitney_p(&a.samples.rtts, &b.samples.rtts);
+            let loss_delta = delta(a.samples.loss(), b.samples.loss());
+            let loss_p_value = stats::two_proportion_p(
+                a.samples.lost,
+                a.samples.sent,
+                b.samples.lost,
+                b.samples.sent,
+            );
+
+            let significant = |p: Option<f32>, delta: Option<f32>| match (p, delta) {
+                (Some(p), Some(delta)) if p < alpha && delta != 0.0 => delta.signum() as i8,
+                _ => 0,
+            };
+            let changes = [
+                significant(rtt_p_value, median_delta),
+                significant(loss_p_value, loss_delta),
+            ];
+            let verdict = match (changes.contains(&1), changes.contains(&-1)) {
+                (true, true) => "mixed",
+                (true, false) => "regression",
+                (false, true) => "improvement",
+                (false, false) => "unchanged",
+            };
+
+            let baseline_ttfb = stats::median(&a.ttfb);
+            let candidate_ttfb = stats::median(&b.ttfb);
+            let baseline_path_length = stats::mean(&a.path_lengths);
+            let candidate_path_length = stats::mean(&b.path_lengths);
+
+            PairDelta {
+                kind,
+                prb_id,
+                target,
+                baseline_results: a.results,
+                candidate_results: b.results,
+                baseline_median,
+                candidate_median,
+                median_delta,
+                rtt_p_value,
+                baseline_loss: a.samples.loss(),
+                candidate_loss: b.samples.loss(),
+                loss_delta,
+                loss_p_value,
+                baseline_ttfb,
+                candidate_ttfb,
+                ttfb_delta: delta(baseline_ttfb, candidate_ttfb),
+                baseline_path_length,
+                candidate_path_length,
+                path_length_delta: delta(baseline_path_length, candidate_path_length),
+                verdict: verdict.to_string(),
+            }
+        })
+        .collect();
+
+    deltas.sort_by(|a, b| {
+        b.median_delta
+            .unwrap_or_default()
+            .total_cmp(&a.median_delta.unwrap_or_default())
+    });
+    deltas
+}
@@ -0,0 +1,188 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{
+    analysis::stats,
+    api::results::{AggregatedMeasurement, FlattenedTraceRouteMeasurement},
+    enrichment::ProbeDirectory,
+};
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Light travels at roughly two thirds of c in fibre, about 200 km per millisecond.
+const FIBRE_KM_PER_MS: f64 = 200.0;
+
+/// Great-circle distance between two points given in degrees, using the haversine formula.
+pub fn great_circle_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// Lowest RTT physically possible over a straight fibre of `distance_km`, there and back.
+pub fn fibre_rtt_ms(distance_km: f64) -> f64 {
+    2.0 * distance_km / FIBRE_KM_PER_MS
+}
+
+/// How many times slower than the fibre bound an observed RTT is. Undefined for co-located
+/// endpoints.
+pub fn inflation(rtt: f32, fibre_rtt: f64) -> Option<f64> {
+    (fibre_rtt > 0.0).then(|| rtt as f64 / fibre_rtt)
+}
+
+/// The RTT of a result compared against the fibre bound: the minimum for pings, the TCP connect
+/// time for HTTP, which is a single round trip, and the minimum of the final hop for traceroutes
+/// that reached their target.
+pub fn reference_rtt(measurement: &AggregatedMeasurement) -> Option<f32> {
+    match measurement {
+        AggregatedMeasurement::Ping(p) => (p.rcvd > 0 && p.min >= 0.0).then_some(p.min),
+        AggregatedMeasurement::Http(h) => h.result.first().and_then(|r| r.ttc),
+        AggregatedMeasurement::TraceRoute(t) => {
+            FlattenedTraceRouteMeasurement::from_traceroute_measurement(t)
+                .into_iter()
+                .last()
+                .and_then(|hop| final_hop_rtt(&hop))
+        }
+    }
+}
+
+/// The minimum RTT of a traceroute hop if it is the target answering.
+pub fn final_hop_rtt(hop: &FlattenedTraceRouteMeasurement) -> Option<f32> {
+    hop.addresses
+        .as_deref()
+        .is_some_and(|addresses| addresses.split(';').any(|a| a == hop.dst_addr))
+        .then_some(hop.min)
+        .flatten()
+}
+
+/// Distance and RTT statistics of one source probe and target pair.
+#[derive(Debug, Serialize)]
+pub struct PairDistance {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub prb_id: u32,
+    pub dst_addr: String,
+    pub dst_prb_id: Option<u32>,
+    pub src_country: Option<String>,
+    pub dst_country: Option<String>,
+    pub distance_km: Option<f64>,
+    pub fibre_rtt: Option<f64>,
+    pub count: u32,
+    pub min_rtt: Option<f32>,
+    pub median_rtt: Option<f32>,
+    pub min_inflation: Option<f64>,
+    pub median_inflation: Option<f64>,
+}
+
+pub fn pair_distances(
+    measurements: &[AggregatedMeasurement],
+    probes: &ProbeDirectory,
+) -> Vec<PairDistance> {
+    let mut pairs: BTreeMap<(&str, u32, &str), Vec<f32>> = BTreeMap::new();
+    for measurement in measurements {
+        let Some(dst_addr) = measurement.dst_addr() else {
+            continue;
+        };
+        let rtts = pairs
+            .entry((measurement.kind(), measurement.prb_id(), dst_addr))
+            .or_default();
+        rtts.extend(reference_rtt(measurement));
+    }
+
+    pairs
+        .into_iter()
+        .map(|((kind, prb_id, dst_addr), rtts)| {
+            let distance = probes.distance_km(prb_id, Some(dst_addr));
+            let fibre_rtt = distance.map(fibre_rtt_ms);
+            let min_rtt = stats::percentile(&rtts, 0.0);
+            let median_rtt = stats::median(&rtts);
+            let ratio = |rtt: Option<f32>| fibre_rtt.zip(rtt).and_then(|(f, r)| inflation(r, f));
+
+            PairDistance {
+                kind: kind.to_string(),
+                prb_id,
+                dst_addr: dst_addr.to_string(),
+                dst_prb_id: probes.by_address(dst_addr).map(|p| p.prb_id),
+                src_country: probes.probe(prb_id).and_then(|p| p.country_code.clone()),
+                dst_country: probes
+                    .by_address(dst_addr)
+                    .and_then(|p| p.country_code.clone()),
+                distance_km: distance,
+                fibre_rtt,
+                count: rtts.len() as u32,
+                min_rtt,
+                median_rtt,
+                min_inflation: ratio(min_rtt),
+                median_inflation: ratio(median_rtt),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{http, ping};
+    use serde_json::json;
+
+    const LONDON: (f64, f64) = (51.5074, -0.1278);
+    const PARIS: (f64, f64) = (48.8566, 2.3522);
+
+    #[test]
+    fn london_to_paris() {
+        let distance = great_circle_km(LONDON, PARIS);
+        assert!((distance - 343.56).abs() < 0.01, "{distance}");
+        assert_eq!(great_circle_km(PARIS, LONDON), distance);
+        assert_eq!(great_circle_km(PARIS, PARIS), 0.0);
+
+        let fibre_rtt = fibre_rtt_ms(distance);
+        assert!((fibre_rtt - 3.4356).abs() < 1e-4, "{fibre_rtt}");
+        assert!((inflation(7.0, fibre_rtt).unwrap() - 2.0375).abs() < 1e-4);
+        assert_eq!(inflation(7.0, 0.0), None);
+    }
+
+    #[test]
+    fn pair_distances_compare_rtts_with_the_fibre_bound() {
+        let probes: ProbeDirectory = serde_json::from_value(json!({
+            "probes": {
+                "10": { "prb_id": 10, "is_anchor": false, "country_code": "GB",
+                        "latitude": LONDON.0, "longitude": LONDON.1 },
+                "20": { "prb_id": 20, "is_anchor": true, "country_code": "FR",
+                        "latitude": PARIS.0, "longitude": PARIS.1 },
+            },
+            "addresses": { "192.0.2.1": 20 },
+        }))
+        .unwrap();
+        let measurements = [
+            ping(1001, 10, 100, &[Some(7.0), Some(12.0)]),
+            ping(1001, 10, 200, &[Some(9.0), None]),
+            ping(1001, 10, 300, &[None, None]),
+            http(3001, 10, 100, Some(200)),
+        ];
+
+        let pairs = pair_distances(&measurements, &probes);
+        assert_eq!(pairs.len(), 2);
+        let http = &pairs[0];
+        assert_eq!(
+            (http.kind.as_str(), http.count, http.min_rtt),
+            ("http", 0, None)
+        );
+
+        let ping = &pairs[1];
+        assert_eq!(
+            (ping.kind.as_str(), ping.prb_id, ping.dst_prb_id),
+            ("ping", 10, Some(20))
+        );
+        assert_eq!(ping.src_country.as_deref(), Some("GB"));
+        assert_eq!(ping.dst_country.as_deref(), Some("FR"));
+        assert_eq!(
+            (ping.count, ping.min_rtt, ping.median_rtt),
+            (2, Some(7.0), Some(8.0))
+        );
+        assert!((ping.min_inflation.unwrap() - 2.0375).abs() < 1e-4);
+        assert!((ping.median_inflation.unwrap() - 2.3286).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,169 @@
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    analysis::{samples::Samples, stats},
+    api::results::AggregatedMeasurement,
+    enrichment::ProbeDirectory,
+};
+
+/// A row or column of the matrix. Targets that belong to a probe share the node of that probe,
+/// so all-to-all campaigns end up square.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(untagged)]
+pub enum Node {
+    Probe(u32),
+    Address(String),
+}
+
+#[derive(Debug, Serialize)]
+pub struct NodeInfo {
+    pub label: String,
+    pub prb_id: Option<u32>,
+    pub address: Option<String>,
+    pub country: Option<String>,
+}
+
+/// Source by target statistics, `median[i][j]` is the median RTT from node `i` to node `j`.
+#[derive(Debug, Serialize)]
+pub struct LatencyMatrix {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    pub labels: Vec<String>,
+    pub nodes: Vec<NodeInfo>,
+    pub median: Vec<Vec<Option<f32>>>,
+    pub p95: Vec<Vec<Option<f32>>>,
+    pub loss: Vec<Vec<Option<f32>>>,
+    pub count: Vec<Vec<u32>>,
+}
+
+pub fn latency_matrix(
+    kind: &str,
+    measurements: &[AggregatedMeasurement],
+    probes: &ProbeDirectory,
+) -> LatencyMatrix {
+    let mut cells: HashMap<(Node, Node), (Samples, u32)> = HashMap::new();
+    let mut addresses: HashMap<Node, String> = HashMap::new();
+    let mut nodes: BTreeSet<Node> = BTreeSet::new();
+
+    for measurement in measurements.iter().filter(|m| m.kind() == kind) {
+        let Some(dst_addr) = measurement.dst_addr() else {
+            continue;
+        };
+        let source = Node::Probe(measurement.prb_id());
+        let target = match probes.by_address(dst_addr) {
+            Some(probe) => Node::Probe(probe.prb_id),
+            None => Node::Address(dst_addr.to_string()),
+        };
+        addresses.insert(target.clone(), dst_addr.to_string());
+        nodes.insert(source.clone());
+        nodes.insert(target.clone());
+
+        let (samples, count) = cells.entry((source, target)).or_default();
+        samples.add(Samples::from_measurement(measurement));
+        *count += 1;
+    }
+
+    let nodes: Vec<Node> = nodes.into_iter().collect();
+    let grid = |value: &dyn Fn(&Samples) -> Option<f32>| -> Vec<Vec<Option<f32>>> {
+        nodes
+            .iter()
+            .map(|source| {
+                nodes
+                    .iter()
+                    .map(|target| {
+                        cells
+                            .get(&(source.clone(), target.clone()))
+                            .and_then(|(samples, _)| value(samples))
+                    })
+                    .collect()
+            })
+            .collect()
+    };
+
+    let info: Vec<NodeInfo> = nodes
+        .iter()
+        .map(|node| match node {
+            Node::Probe(prb_id) => NodeInfo {
+                label: probes.label(*prb_id),
+                prb_id: Some(*prb_id),
+                address: addresses.get(node).cloned(),
+                country: probes.probe(*prb_id).and_then(|p| p.country_code.clone()),
+            },
+            Node::Address(address) => NodeInfo {
+                label: address.clone(),
+                prb_id: None,
+                address: Some(address.clone()),
+                country: None,
+            },
+        })
+        .collect();
+
+    LatencyMatrix {
+        kind: kind.to_string(),
+        start: None,
+        end: None,
+        labels: info.iter().map(|node| node.label.clone()).collect(),
+        median: grid(&|samples| stats::median(&samples.rtts)),
+        p95: grid(&|samples| stats::percentile(&samples.rtts, 95.0)),
+        loss: grid(&|samples| samples.loss()),
+        count: nodes
+            .iter()
+            .map(|source| {
+                nodes
+                    .iter()
+                    .map(|target| {
+                        cells
+                            .get(&(source.clone(), target.clone()))
+                            .map_or(0, |(_, count)| *count)
+                    })
+                    .collect()
+            })
+            .collect(),
+        nodes: info,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{http, ping, ping_json};
+    use serde_json::json;
+
+    #[test]
+    fn grids_by_source_and_target() {
+        let probes: ProbeDirectory = serde_json::from_value(json!({
+            "probes": {
+                "10": { "prb_id": 10, "is_anchor": false, "country_code": "GB" },
+                "20": { "prb_id": 20, "is_anchor": true, "country_code": "FR" },
+            },
+            "addresses": { "192.0.2.1": 20, "203.0.113.5": null },
+        }))
+        .unwrap();
+        let mut unanswered = ping_json(1002, 20, 100, &[None, None]);
+        unanswered["dst_addr"] = json!("203.0.113.5");
+        let measurements = [
+            ping(1001, 10, 100, &[Some(10.0), Some(20.0), None]),
+            ping(1001, 10, 200, &[Some(30.0), Some(40.0)]),
+            serde_json::from_value(unanswered).unwrap(),
+            http(3001, 10, 100, Some(200)),
+        ];
+
+        let matrix = latency_matrix("ping", &measurements, &probes);
+        assert_eq!(matrix.labels, ["10 (GB)", "20 (FR)", "203.0.113.5"]);
+        assert_eq!(matrix.nodes[1].address.as_deref(), Some("192.0.2.1"));
+        assert_eq!(matrix.nodes[2].prb_id, None);
+
+        assert_eq!(matrix.median[0], [None, Some(25.0), None]);
+        assert_eq!(matrix.p95[0], [None, Some(38.5), None]);
+        assert_eq!(matrix.loss[0], [None, Some(20.0), None]);
+        assert_eq!(matrix.count[0], [0, 2, 0]);
+
+        assert_eq!(matrix.median[1], [None, None, None]);
+        assert_eq!(matrix.loss[1], [None, None, Some(100.0)]);
+        assert_eq!(matrix.count[1], [0, 0, 1]);
+        assert_eq!(matrix.count[2], [0, 0, 0]);
+    }
+}
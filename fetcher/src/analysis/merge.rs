@@ -0,0 +1,207 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    analysis::{paths::TracePath, samples::Samples, stats},
+    api::results::AggregatedMeasurement,
+};
+
+#[derive(Debug, Default)]
+struct Bucket {
+    ping_results: u32,
+    ping: Samples,
+    http_results: u32,
+    http: Samples,
+    ttfb: Vec<f32>,
+    ttc: Vec<f32>,
+    statuses: BTreeMap<u32, u32>,
+    traceroute_results: u32,
+    traceroute: Samples,
+    hop_counts: Vec<f32>,
+    reached: u32,
+}
+
+/// Ping, HTTP and traceroute statistics of one source probe and target in one time bucket.
+/// Columns of a type without results in the bucket are empty.
+#[derive(Debug, Serialize)]
+pub struct MergedRow {
+    pub prb_id: u32,
+    pub dst_addr: String,
+    pub bucket_start: i64,
+    pub ping_results: u32,
+    pub ping_min: Option<f32>,
+    pub ping_median: Option<f32>,
+    pub ping_loss: Option<f32>,
+    pub http_results: u32,
+    pub http_rt: Option<f32>,
+    pub http_ttfb: Option<f32>,
+    pub http_ttc: Option<f32>,
+    pub http_loss: Option<f32>,
+    /// Status codes as `status:count` pairs separated by `;`.
+    pub http_status: Option<String>,
+    pub traceroute_results: u32,
+    pub hop_count: Option<f32>,
+    /// RTT of the target on the final hop.
+    pub traceroute_rtt: Option<f32>,
+    /// Share of runs that reached the target, in percent.
+    pub traceroute_reached: Option<f32>,
+}
+
+/// Joins the results of all types per source probe, target address and time bucket of
+/// `bucket` seconds, taking medians where a bucket holds several results of a type.
+pub fn merge(measurements: &[AggregatedMeasurement], bucket: i64) -> Vec<MergedRow> {
+    let mut buckets: BTreeMap<(u32, String, i64), Bucket> = BTreeMap::new();
+    // HTTP results that failed before they got to an address count against the address the
+    // other results of their measurement and probe went to, or else the host of the URI.
+    let addresses: HashMap<(u32, u32), &str> = measurements
+        .iter()
+        .filter_map(|m| Some(((m.msm_id(), m.prb_id()), m.dst_addr()?)))
+        .collect();
+
+    for measurement in measurements {
+        let Some(dst_addr) = measurement
+            .dst_addr()
+            .or_else(|| {
+                addresses
+                    .get(&(measurement.msm_id(), measurement.prb_id()))
+                    .copied()
+            })
+            .or_else(|| measurement.target())
+        else {
+            continue;
+        };
+        let timestamp = measurement.timestamp() as i64;
+        let start = timestamp - timestamp.rem_euclid(bucket.max(1));
+        let entry = buckets
+            .entry((measurement.prb_id(), dst_addr.to_string(), start))
+            .or_default();
+        let samples = Samples::from_measurement(measurement);
+
+        match measurement {
+            AggregatedMeasurement::Ping(_) => {
+                entry.ping_results += 1;
+                entry.ping.add(samples);
+            }
+            AggregatedMeasurement::Http(h) => {
+                entry.http_results += 1;
+                entry.http.add(samples);
+                for result in h.result.iter().filter(|r| r.error_kind().is_none()) {
+                    entry.ttfb.extend(result.ttfb);
+                    entry.ttc.extend(result.ttc);
+                }
+                for status in h.result.iter().filter_map(|r| r.res) {
+                    *entry.statuses.entry(status).or_default() += 1;
+                }
+            }
+            AggregatedMeasurement::TraceRoute(t) => {
+                let path = TracePath::from_traceroute_measurement(t);
+                entry.traceroute_results += 1;
+                entry.traceroute.add(samples);
+                entry.hop_counts.push(path.length() as f32);
+                entry.reached += path.reached as u32;
+            }
+        }
+    }
+
+    buckets
+        .into_iter()
+        .map(|((prb_id, dst_addr, bucket_start), b)| MergedRow {
+            prb_id,
+            dst_addr,
+            bucket_start,
+            ping_results: b.ping_results,
+            ping_min: stats::percentile(&b.ping.rtts, 0.0),
+            ping_median: stats::median(&b.ping.rtts),
+            ping_loss: b.ping.loss(),
+            http_results: b.http_results,
+            http_rt: stats::median(&b.http.rtts),
+            http_ttfb: stats::median(&b.ttfb),
+            http_ttc: stats::median(&b.ttc),
+            http_loss: b.http.loss(),
+            http_status: (!b.statuses.is_empty()).then(|| {
+                b.statuses
+                    .iter()
+                    .map(|(status, count)| format!("{}:{}", status, count))
+                    .collect::<Vec<_>>()
+                    .join(";")
+            }),
+            traceroute_results: b.traceroute_results,
+            hop_count: stats::median(&b.hop_counts),
+            traceroute_rtt: stats::median(&b.traceroute.rtts),
+            traceroute_reached: (b.traceroute_results > 0)
+                .then(|| b.reached as f32 / b.traceroute_results as f32 * 100.0),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{http, http_dns_error, ping, traceroute};
+
+    #[test]
+    fn joins_the_types_of_a_pair_per_bucket() {
+        let measurements = [
+            ping(1001, 10, 3_599, &[Some(10.0), None]),
+            ping(1001, 10, 3_600, &[Some(20.0), Some(30.0)]),
+            http(3001, 10, 3_700, Some(200)),
+            traceroute(2001, 10, 3_800, &["198.51.100.254", "192.0.2.1"], true),
+            ping(1001, 20, 3_600, &[Some(40.0)]),
+        ];
+
+        let rows = merge(&measurements, 3_600);
+        let keys: Vec<_> = rows
+            .iter()
+            .map(|row| (row.prb_id, row.dst_addr.as_str(), row.bucket_start))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                (10, "192.0.2.1", 0),
+                (10, "192.0.2.1", 3_600),
+                (20, "192.0.2.1", 3_600)
+            ]
+        );
+
+        let first = &rows[0];
+        assert_eq!((first.ping_results, first.ping_loss), (1, Some(50.0)));
+        assert_eq!((first.http_results, first.http_rt), (0, None));
+        assert_eq!(first.traceroute_results, 0);
+
+        let joined = &rows[1];
+        assert_eq!((joined.ping_results, joined.ping_median), (1, Some(25.0)));
+        assert_eq!((joined.http_results, joined.http_rt), (1, Some(20.0)));
+        assert_eq!(joined.http_status.as_deref(), Some("200:1"));
+        assert_eq!(
+            (joined.traceroute_results, joined.hop_count),
+            (1, Some(2.0))
+        );
+        assert_eq!(joined.traceroute_reached, Some(100.0));
+
+        // A bucket of zero seconds is one bucket per second instead of a division by zero.
+        assert_eq!(merge(&measurements, 0)[0].bucket_start, 3_599);
+    }
+
+    #[test]
+    fn failed_http_requests_count_against_the_target() {
+        let measurements = [
+            http(3001, 10, 100, Some(200)),
+            http_dns_error(3001, 10, 200),
+            http_dns_error(3002, 20, 300),
+        ];
+
+        let rows = merge(&measurements, 3_600);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            (rows[0].prb_id, rows[0].dst_addr.as_str()),
+            (10, "192.0.2.1")
+        );
+        assert_eq!((rows[0].http_results, rows[0].http_loss), (2, Some(50.0)));
+        // Without any other result of the probe, the host of the URI is the target.
+        assert_eq!(
+            (rows[1].prb_id, rows[1].dst_addr.as_str()),
+            (20, "www.example.org")
+        );
+        assert_eq!((rows[1].http_results, rows[1].http_loss), (1, Some(100.0)));
+    }
+}
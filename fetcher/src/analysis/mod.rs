@@ -0,0 +1,12 @@
+pub mod alert;
+pub mod anomaly;
+pub mod aspath;
+pub mod compare;
+pub mod distance;
+pub mod matrix;
+pub mod merge;
+pub mod paths;
+pub mod rolling;
+pub mod samples;
+pub mod stats;
+pub mod summary;
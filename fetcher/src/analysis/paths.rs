@@ -0,0 +1,315 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::api::results::{AggregatedMeasurement, TraceRouteMeasurement};
+
+/// The IP-level path of one traceroute run. Every hop holds the addresses that answered on it,
+/// an empty hop did not answer at all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TracePath {
+    pub timestamp: usize,
+    pub msm_id: u32,
+    pub hops: Vec<Vec<String>>,
+    pub reached: bool,
+}
+
+impl TracePath {
+    pub fn from_traceroute_measurement(measurement: &TraceRouteMeasurement) -> Self {
+        let hops: Vec<Vec<String>> = measurement
+            .result
+            .iter()
+            .map(|hop| {
+                let mut addresses: Vec<String> =
+                    hop.addresses().into_iter().map(str::to_string).collect();
+                addresses.sort();
+                addresses
+            })
+            .collect();
+        let reached = measurement.destination_ip_responded
+            || hops
+                .last()
+                .is_some_and(|hop| hop.contains(&measurement.dst_addr));
+
+        TracePath {
+            timestamp: measurement.timestamp,
+            msm_id: measurement.msm_id,
+            hops,
+            reached,
+        }
+    }
+
+    pub fn length(&self) -> usize {
+        self.hops.len()
+    }
+
+    /// Distinct addresses seen on the path, leaving out hops that did not answer in `other`.
+    pub fn addresses_answered_in(&self, other: &TracePath) -> BTreeSet<&str> {
+        self.hops
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| other.hops.get(*index).is_none_or(|hop| !hop.is_empty()))
+            .flat_map(|(_, hop)| hop.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Whether both runs took the same route as far as they answered: same length and the same
+    /// addresses on every hop that answered in both.
+    pub fn agrees_with(&self, other: &TracePath) -> bool {
+        self.length() == other.length()
+            && self
+                .hops
+                .iter()
+                .zip(&other.hops)
+                .all(|(a, b)| a.is_empty() || b.is_empty() || a == b)
+    }
+
+    pub fn silent_hops(&self) -> usize {
+        self.hops.iter().filter(|hop| hop.is_empty()).count()
+    }
+
+    /// The path as `a > b|c > *`, load balanced hops are joined by `|`, silent hops are `*`.
+    pub fn render(&self) -> String {
+        self.hops
+            .iter()
+            .map(|hop| match hop.is_empty() {
+                true => "*".to_string(),
+                false => hop.join("|"),
+            })
+            .collect::<Vec<_>>()
+            .join(" > ")
+    }
+}
+
+/// A route change between two consecutive runs of the same source probe and target.
+#[derive(Debug, Serialize)]
+pub struct PathChange {
+    pub prb_id: u32,
+    pub dst_addr: String,
+    pub msm_id: u32,
+    pub previous_timestamp: usize,
+    pub timestamp: usize,
+    pub previous_length: usize,
+    pub length: usize,
+    /// Addresses on the new path that were not on the previous one, separated by `;`.
+    pub added: String,
+    pub removed: String,
+    /// `lost` or `regained` when the destination reachability flipped.
+    pub reachability: Option<String>,
+    pub previous_path: String,
+    pub path: String,
+}
+
+/// How stable the route of one source probe and target pair was over the campaign.
+#[derive(Debug, Serialize)]
+pub struct PathStability {
+    pub prb_id: u32,
+    pub dst_addr: String,
+    pub runs: usize,
+    pub first: usize,
+    pub last: usize,
+    pub changes: usize,
+    /// Share of consecutive run pairs with a change, in percent.
+    pub change_rate: Option<f32>,
+    pub distinct_paths: usize,
+    pub dominant_path: String,
+    /// Share of runs that took the dominant path, in percent.
+    pub dominant_share: f32,
+    pub reachability: f32,
+    pub mean_length: f32,
+    pub min_length: usize,
+    pub max_length: usize,
+}
+
+/// Groups the traceroute runs by source probe and target, sorted by time.
+pub fn paths_by_pair(
+    measurements: &[AggregatedMeasurement],
+) -> BTreeMap<(u32, String), Vec<TracePath>> {
+    let mut pairs: BTreeMap<(u32, String), Vec<TracePath>> = BTreeMap::new();
+    for measurement in measurements {
+        if let AggregatedMeasurement::TraceRoute(t) = measurement {
+            pairs
+                .entry((t.prb_id, t.dst_addr.clone()))
+                .or_default()
+                .push(TracePath::from_traceroute_measurement(t));
+        }
+    }
+    for runs in pairs.values_mut() {
+        runs.sort_by_key(|run| run.timestamp);
+    }
+    pairs
+}
+
+/// Compares two consecutive runs. Hops that did not answer in either run are not treated as a
+/// change on their own, otherwise a single lost probe packet would count as a new route.
+pub fn compare(
+    prb_id: u32,
+    dst_addr: &str,
+    previous: &TracePath,
+    current: &TracePath,
+) -> Option<PathChange> {
+    let before = previous.addresses_answered_in(current);
+    let after = current.addresses_answered_in(previous);
+    let added: Vec<&str> = after.difference(&before).copied().collect();
+    let removed: Vec<&str> = before.difference(&after).copied().collect();
+    let reachability = match (previous.reached, current.reached) {
+        (true, false) => Some("lost".to_string()),
+        (false, true) => Some("regained".to_string()),
+        _ => None,
+    };
+
+    if added.is_empty()
+        && removed.is_empty()
+        && previous.length() == current.length()
+        && reachability.is_none()
+    {
+        return None;
+    }
+
+    Some(PathChange {
+        prb_id,
+        dst_addr: dst_addr.to_string(),
+        msm_id: current.msm_id,
+        previous_timestamp: previous.timestamp,
+        timestamp: current.timestamp,
+        previous_length: previous.length(),
+        length: current.length(),
+        added: added.join(";"),
+        removed: removed.join(";"),
+        reachability,
+        previous_path: previous.render(),
+        path: current.render(),
+    })
+}
+
+/// Detects route changes between consecutive runs of every pair and derives the stability
+/// metrics from them.
+pub fn path_changes(
+    measurements: &[AggregatedMeasurement],
+) -> (Vec<PathChange>, Vec<PathStability>) {
+    let mut changes = Vec::new();
+    let mut stability = Vec::new();
+
+    for ((prb_id, dst_addr), runs) in paths_by_pair(measurements) {
+        let pair_changes: Vec<PathChange> = runs
+            .windows(2)
+            .filter_map(|window| compare(prb_id, &dst_addr, &window[0], &window[1]))
+            .collect();
+
+        // Runs with silent hops count towards a path they agree with, so a lost probe packet
+        // does not make a distinct path. Complete runs go first to set up those paths.
+        let mut ordered: Vec<&TracePath> = runs.iter().collect();
+        ordered.sort_by_key(|run| run.silent_hops());
+        let mut counts: Vec<(&TracePath, usize)> = Vec::new();
+        for run in ordered {
+            match counts.iter_mut().find(|(path, _)| path.agrees_with(run)) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((run, 1)),
+            }
+        }
+        let (dominant_path, dominant_runs) = counts
+            .iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(path, count)| (path.render(), *count))
+            .unwrap_or_default();
+
+        let lengths: Vec<usize> = runs.iter().map(TracePath::length).collect();
+        let total = runs.len() as f32;
+        stability.push(PathStability {
+            prb_id,
+            dst_addr,
+            runs: runs.len(),
+            first: runs.first().map(|run| run.timestamp).unwrap_or_default(),
+            last: runs.last().map(|run| run.timestamp).unwrap_or_default(),
+            changes: pair_changes.len(),
+            change_rate: (runs.len() > 1)
+                .then(|| pair_changes.len() as f32 / (runs.len() - 1) as f32 * 100.0),
+            distinct_paths: counts.len(),
+            dominant_path,
+            dominant_share: dominant_runs as f32 / total * 100.0,
+            reachability: runs.iter().filter(|run| run.reached).count() as f32 / total * 100.0,
+            mean_length: lengths.iter().sum::<usize>() as f32 / total,
+            min_length: lengths.iter().copied().min().unwrap_or_default(),
+            max_length: lengths.iter().copied().max().unwrap_or_default(),
+        });
+        changes.extend(pair_changes);
+    }
+
+    (changes, stability)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::traceroute_json;
+    use serde_json::json;
+
+    /// A traceroute over `hops` that reached its target, `*` is a hop that did not answer.
+    fn run(timestamp: usize, hops: &[&str]) -> AggregatedMeasurement {
+        let mut raw = traceroute_json(2001, 10, timestamp, hops, true);
+        for (index, hop) in hops.iter().enumerate() {
+            if *hop == "*" {
+                raw["result"][index]["result"] = json!([{ "x": "*" }]);
+            }
+        }
+        serde_json::from_value(raw).unwrap()
+    }
+
+    fn path(measurement: &AggregatedMeasurement) -> TracePath {
+        match measurement {
+            AggregatedMeasurement::TraceRoute(t) => TracePath::from_traceroute_measurement(t),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn silent_hops_are_no_route_change() {
+        let complete = path(&run(100, &["198.51.100.254", "203.0.113.1", "192.0.2.1"]));
+        let silent = path(&run(200, &["198.51.100.254", "*", "192.0.2.1"]));
+        assert_eq!(silent.render(), "198.51.100.254 > * > 192.0.2.1");
+        assert!(compare(10, "192.0.2.1", &complete, &silent).is_none());
+        assert!(compare(10, "192.0.2.1", &silent, &complete).is_none());
+
+        let rerouted = path(&run(
+            300,
+            &["198.51.100.254", "*", "203.0.113.7", "192.0.2.1"],
+        ));
+        let change = compare(10, "192.0.2.1", &complete, &rerouted).unwrap();
+        assert_eq!((change.previous_length, change.length), (3, 4));
+        assert_eq!(
+            (change.added.as_str(), change.removed.as_str()),
+            ("203.0.113.7", "")
+        );
+
+        let moved = path(&run(400, &["198.51.100.254", "203.0.113.2", "192.0.2.1"]));
+        // The only difference is on a hop that did not answer before.
+        assert!(compare(10, "192.0.2.1", &silent, &moved).is_none());
+        let change = compare(10, "192.0.2.1", &complete, &moved).unwrap();
+        assert_eq!(
+            (change.added.as_str(), change.removed.as_str()),
+            ("203.0.113.2", "203.0.113.1")
+        );
+    }
+
+    #[test]
+    fn lost_packets_do_not_split_the_dominant_path() {
+        let measurements = [
+            run(100, &["198.51.100.254", "*", "192.0.2.1"]),
+            run(200, &["198.51.100.254", "203.0.113.1", "192.0.2.1"]),
+            run(300, &["198.51.100.254", "203.0.113.1", "192.0.2.1"]),
+            run(400, &["*", "203.0.113.1", "192.0.2.1"]),
+            run(500, &["198.51.100.254", "203.0.113.2", "192.0.2.1"]),
+        ];
+
+        let (changes, stability) = path_changes(&measurements);
+        let timestamps: Vec<_> = changes.iter().map(|c| c.timestamp).collect();
+        assert_eq!(timestamps, [500]);
+
+        let pair = &stability[0];
+        assert_eq!((pair.runs, pair.changes, pair.distinct_paths), (5, 1, 2));
+        assert_eq!(
+            pair.dominant_path,
+            "198.51.100.254 > 203.0.113.1 > 192.0.2.1"
+        );
+        assert_eq!(pair.dominant_share, 80.0);
+    }
+}
@@ -0,0 +1,85 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::{
+    analysis::{samples::Samples, stats},
+    api::results::AggregatedMeasurement,
+};
+
+/// Statistics over the most recent results of every pair, for live views of a running
+/// campaign.
+#[derive(Debug)]
+pub struct RollingSummary {
+    window: usize,
+    pairs: BTreeMap<(String, u32, String), VecDeque<(usize, Samples)>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RollingRow {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub prb_id: u32,
+    pub dst_addr: String,
+    pub results: usize,
+    pub last_timestamp: usize,
+    pub last_rtt: Option<f32>,
+    pub median: Option<f32>,
+    pub p95: Option<f32>,
+    pub loss: Option<f32>,
+}
+
+impl RollingSummary {
+    /// Keeps the last `window` results of every pair.
+    pub fn new(window: usize) -> Self {
+        RollingSummary {
+            window: window.max(1),
+            pairs: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, measurement: &AggregatedMeasurement) {
+        let Some(dst_addr) = measurement.dst_addr() else {
+            return;
+        };
+        let results = self
+            .pairs
+            .entry((
+                measurement.kind().to_string(),
+                measurement.prb_id(),
+                dst_addr.to_string(),
+            ))
+            .or_default();
+
+        // Results can arrive out of order, keep the window sorted by time.
+        let timestamp = measurement.timestamp();
+        let index = results.partition_point(|(t, _)| *t <= timestamp);
+        results.insert(index, (timestamp, Samples::from_measurement(measurement)));
+        while results.len() > self.window {
+            results.pop_front();
+        }
+    }
+
+    pub fn rows(&self) -> Vec<RollingRow> {
+        self.pairs
+            .iter()
+            .map(|((kind, prb_id, dst_addr), results)| {
+                let mut samples = Samples::default();
+                for (_, result) in results {
+                    samples.add(result.clone());
+                }
+                let last = results.back();
+                RollingRow {
+                    kind: kind.clone(),
+                    prb_id: *prb_id,
+                    dst_addr: dst_addr.clone(),
+                    results: results.len(),
+                    last_timestamp: last.map(|(t, _)| *t).unwrap_or_default(),
+                    last_rtt: last.and_then(|(_, s)| stats::median(&s.rtts)),
+                    median: stats::median(&samples.rtts),
+                    p95: stats::percentile(&samples.rtts, 95.0),
+                    loss: samples.loss(),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,66 @@
+use crate::api::results::AggregatedMeasurement;
+
+/// The RTTs a single result contributed and how many of its probes got no answer, the common
+/// ground on which pings, HTTP requests and traceroutes are compared.
+#[derive(Debug, Default, Clone)]
+pub struct Samples {
+    pub rtts: Vec<f32>,
+    pub sent: u32,
+    pub lost: u32,
+}
+
+impl Samples {
+    /// Pings contribute every reply RTT, HTTP results the response time of each successful
+    /// request and traceroutes the replies of the target on the final hop. A traceroute that
+    /// did not reach its target counts as one lost probe.
+    pub fn from_measurement(measurement: &AggregatedMeasurement) -> Self {
+        match measurement {
+            AggregatedMeasurement::Ping(p) => Samples {
+                rtts: p.rtts(),
+                sent: p.sent,
+                lost: p.sent.saturating_sub(p.rcvd),
+            },
+            AggregatedMeasurement::Http(h) => {
+                let rtts: Vec<f32> = h
+                    .result
+                    .iter()
+                    .filter(|r| r.error_kind().is_none())
+                    .filter_map(|r| r.rt)
+                    .collect();
+                Samples {
+                    sent: h.result.len() as u32,
+                    lost: (h.result.len() - rtts.len()) as u32,
+                    rtts,
+                }
+            }
+            AggregatedMeasurement::TraceRoute(t) => {
+                let rtts: Vec<f32> = t
+                    .result
+                    .last()
+                    .map(|hop| {
+                        hop.answered()
+                            .filter(|reply| reply.from.as_deref() == Some(t.dst_addr.as_str()))
+                            .filter_map(|reply| reply.rtt)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                Samples {
+                    sent: 1,
+                    lost: rtts.is_empty() as u32,
+                    rtts,
+                }
+            }
+        }
+    }
+
+    pub fn add(&mut self, other: Samples) {
+        self.rtts.extend(other.rtts);
+        self.sent += other.sent;
+        self.lost += other.lost;
+    }
+
+    /// Share of lost probes in percent.
+    pub fn loss(&self) -> Option<f32> {
+        (self.sent > 0).then(|| self.lost as f32 / self.sent as f32 * 100.0)
+    }
+}
@@ -0,0 +1,171 @@
+/// Linear interpolated percentile of `values`, `p` in 0..=100. Returns `None` for an empty
+/// slice. The slice does not have to be sorted.
+pub fn percentile(values: &[f32], p: f32) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+
+    let rank = (p / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as f32;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let weight = rank - lower as f32;
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * weight)
+}
+
+pub fn median(values: &[f32]) -> Option<f32> {
+    percentile(values, 50.0)
+}
+
+pub fn mean(values: &[f32]) -> Option<f32> {
+    (!values.is_empty()).then(|| values.iter().sum::<f32>() / values.len() as f32)
+}
+
+/// Population standard deviation.
+pub fn stddev(values: &[f32]) -> Option<f32> {
+    let mean = mean(values)?;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / values.len() as f32;
+    Some(variance.sqrt())
+}
+
+/// Median absolute deviation around the median, scaled by 1.4826 so it estimates the standard
+/// deviation of normally distributed values while ignoring outliers.
+pub fn mad(values: &[f32]) -> Option<f32> {
+    let center = median(values)?;
+    let deviations: Vec<f32> = values.iter().map(|v| (v - center).abs()).collect();
+    median(&deviations).map(|m| m * 1.4826)
+}
+
+/// Two-sided p-value of the Mann-Whitney U test, whether the values of `a` tend to be larger or
+/// smaller than those of `b`. Uses the normal approximation with tie correction, so it needs a
+/// handful of values on both sides to be meaningful.
+pub fn mann_whitney_p(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let mut all: Vec<(f32, bool)> = a
+        .iter()
+        .map(|v| (*v, true))
+        .chain(b.iter().map(|v| (*v, false)))
+        .collect();
+    all.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    let n = all.len() as f64;
+    let mut rank_sum_a = 0.0;
+    let mut ties = 0.0;
+    let mut i = 0;
+    while i < all.len() {
+        let mut j = i;
+        while j < all.len() && all[j].0 == all[i].0 {
+            j += 1;
+        }
+        // Tied values share the mean of the ranks they span.
+        let rank = (i + j + 1) as f64 / 2.0;
+        rank_sum_a += rank * all[i..j].iter().filter(|(_, in_a)| *in_a).count() as f64;
+        let t = (j - i) as f64;
+        ties += t.powi(3) - t;
+        i = j;
+    }
+
+    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+    let u = rank_sum_a - n_a * (n_a + 1.0) / 2.0;
+    let mean = n_a * n_b / 2.0;
+    let variance = n_a * n_b / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return Some(1.0);
+    }
+    let z = ((u - mean).abs() - 0.5).max(0.0) / variance.sqrt();
+    Some((2.0 * normal_sf(z)).min(1.0) as f32)
+}
+
+/// Two-sided p-value of the two proportion z-test, whether `lost_a / sent_a` differs from
+/// `lost_b / sent_b`.
+pub fn two_proportion_p(lost_a: u32, sent_a: u32, lost_b: u32, sent_b: u32) -> Option<f32> {
+    if sent_a == 0 || sent_b == 0 {
+        return None;
+    }
+    let (n_a, n_b) = (sent_a as f64, sent_b as f64);
+    let pooled = (lost_a + lost_b) as f64 / (n_a + n_b);
+    let variance = pooled * (1.0 - pooled) * (1.0 / n_a + 1.0 / n_b);
+    if variance <= 0.0 {
+        return Some(1.0);
+    }
+    let z = (lost_a as f64 / n_a - lost_b as f64 / n_b).abs() / variance.sqrt();
+    Some((2.0 * normal_sf(z)).min(1.0) as f32)
+}
+
+/// Upper tail of the standard normal distribution, Abramowitz and Stegun 7.1.26.
+fn normal_sf(z: f64) -> f64 {
+    let x = z / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    0.5 * poly * (-x * x).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reference values from a direct implementation of the tests, with U counted over all
+    // pairs and the normal tail from erfc.
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn normal_sf_matches_reference() {
+        assert_close(normal_sf(0.0), 0.5, 1e-7);
+        assert_close(normal_sf(1.0), 0.158655254, 1e-7);
+        assert_close(normal_sf(1.96), 0.024997895, 1e-7);
+        assert_close(normal_sf(3.0), 0.001349898, 1e-7);
+    }
+
+    #[test]
+    fn mann_whitney_p_matches_reference() {
+        let a = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+        let b = [5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0];
+        assert_close(mann_whitney_p(&a, &b).unwrap() as f64, 0.013313003, 1e-5);
+        assert_close(mann_whitney_p(&b, &a).unwrap() as f64, 0.013313003, 1e-5);
+
+        let a = [10.0, 12.0, 12.0, 14.0, 15.0, 15.0, 15.0, 18.0];
+        let b = [11.0, 12.0, 15.0, 15.0, 20.0, 21.0, 22.0, 22.0, 25.0];
+        assert_close(mann_whitney_p(&a, &b).unwrap() as f64, 0.087202615, 1e-5);
+    }
+
+    #[test]
+    fn mann_whitney_p_of_equal_or_missing_samples() {
+        assert_eq!(
+            mann_whitney_p(&[1.0, 2.0, 3.0], &[1.0, 2.0, 3.0]),
+            Some(1.0)
+        );
+        assert_eq!(mann_whitney_p(&[4.0, 4.0], &[4.0, 4.0, 4.0]), Some(1.0));
+        assert_eq!(mann_whitney_p(&[], &[1.0]), None);
+    }
+
+    #[test]
+    fn two_proportion_p_matches_reference() {
+        assert_close(
+            two_proportion_p(10, 100, 25, 100).unwrap() as f64,
+            0.005247204,
+            1e-6,
+        );
+        assert_close(
+            two_proportion_p(1, 1000, 30, 1000).unwrap() as f64,
+            1.526e-7,
+            5e-7,
+        );
+        assert_eq!(two_proportion_p(5, 50, 5, 50), Some(1.0));
+        assert_eq!(two_proportion_p(0, 50, 0, 50), Some(1.0));
+        assert_eq!(two_proportion_p(1, 0, 1, 10), None);
+    }
+}
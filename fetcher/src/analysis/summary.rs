@@ -0,0 +1,278 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{
+    analysis::{samples::Samples, stats},
+    api::results::{
+        AggregatedMeasurement, FlattenedHttpMeasurement, FlattenedPingMeasurement, Outcome,
+    },
+};
+
+/// What results are grouped by before they are summarized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GroupKey {
+    Type,
+    Source,
+    Target,
+    Measurement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Bucket {
+    Hour,
+    Day,
+}
+
+impl Bucket {
+    pub fn seconds(&self) -> i64 {
+        match self {
+            Bucket::Hour => 3_600,
+            Bucket::Day => 86_400,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Key {
+    kind: Option<String>,
+    prb_id: Option<u32>,
+    dst_addr: Option<String>,
+    msm_id: Option<u32>,
+    bucket: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+struct Accumulator {
+    results: u32,
+    samples: Samples,
+    timeouts: u32,
+    statuses: BTreeMap<u32, u32>,
+    hop_counts: BTreeMap<u32, u32>,
+}
+
+/// Statistics of one group. Key columns that were not grouped by are left empty, distributions
+/// are written as `value:count` pairs separated by `;`.
+#[derive(Debug, Serialize)]
+pub struct SummaryRow {
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub prb_id: Option<u32>,
+    pub dst_addr: Option<String>,
+    pub msm_id: Option<u32>,
+    pub bucket_start: Option<i64>,
+    pub results: u32,
+    pub sent: u32,
+    pub lost: u32,
+    pub loss: Option<f32>,
+    pub min: Option<f32>,
+    pub median: Option<f32>,
+    pub mean: Option<f32>,
+    pub p90: Option<f32>,
+    pub p99: Option<f32>,
+    pub max: Option<f32>,
+    pub stddev: Option<f32>,
+    /// Ping replies and HTTP requests that timed out, and traceroute runs that did not reach
+    /// their target.
+    pub timeouts: u32,
+    pub http_status: Option<String>,
+    pub hop_counts: Option<String>,
+}
+
+pub fn summarize(
+    measurements: &[AggregatedMeasurement],
+    keys: &[GroupKey],
+    bucket: Option<Bucket>,
+) -> Vec<SummaryRow> {
+    let mut groups: BTreeMap<Key, Accumulator> = BTreeMap::new();
+
+    for measurement in measurements {
+        let key = Key {
+            kind: keys
+                .contains(&GroupKey::Type)
+                .then(|| measurement.kind().to_string()),
+            prb_id: keys
+                .contains(&GroupKey::Source)
+                .then(|| measurement.prb_id()),
+            dst_addr: keys
+                .contains(&GroupKey::Target)
+                .then(|| measurement.dst_addr().map(str::to_string))
+                .flatten(),
+            msm_id: keys
+                .contains(&GroupKey::Measurement)
+                .then(|| measurement.msm_id()),
+            bucket: bucket.map(|b| {
+                let timestamp = measurement.timestamp() as i64;
+                timestamp - timestamp.rem_euclid(b.seconds())
+            }),
+        };
+
+        let group = groups.entry(key).or_default();
+        group.results += 1;
+        let samples = Samples::from_measurement(measurement);
+
+        match measurement {
+            AggregatedMeasurement::Ping(p) => {
+                group.timeouts += FlattenedPingMeasurement::from_ping_measurement(p).timeouts;
+            }
+            AggregatedMeasurement::Http(h) => {
+                for row in FlattenedHttpMeasurement::from_http_measurement(h) {
+                    if row.outcome == Outcome::Timeout {
+                        group.timeouts += 1;
+                    }
+                    if let Some(status) = row.res {
+                        *group.statuses.entry(status).or_default() += 1;
+                    }
+                }
+            }
+            AggregatedMeasurement::TraceRoute(t) => {
+                // A run without a reply from the target on the final hop, same as for loss.
+                group.timeouts += samples.lost;
+                if let Some(last) = t.result.iter().map(|hop| hop.hop).max() {
+                    *group.hop_counts.entry(last).or_default() += 1;
+                }
+            }
+        }
+        group.samples.add(samples);
+    }
+
+    groups
+        .into_iter()
+        .map(|(key, group)| {
+            let rtts = &group.samples.rtts;
+            SummaryRow {
+                kind: key.kind,
+                prb_id: key.prb_id,
+                dst_addr: key.dst_addr,
+                msm_id: key.msm_id,
+                bucket_start: key.bucket,
+                results: group.results,
+                sent: group.samples.sent,
+                lost: group.samples.lost,
+                loss: group.samples.loss(),
+                min: stats::percentile(rtts, 0.0),
+                median: stats::median(rtts),
+                mean: stats::mean(rtts),
+                p90: stats::percentile(rtts, 90.0),
+                p99: stats::percentile(rtts, 99.0),
+                max: stats::percentile(rtts, 100.0),
+                stddev: stats::stddev(rtts),
+                timeouts: group.timeouts,
+                http_status: distribution(&group.statuses),
+                hop_counts: distribution(&group.hop_counts),
+            }
+        })
+        .collect()
+}
+
+fn distribution(counts: &BTreeMap<u32, u32>) -> Option<String> {
+    (!counts.is_empty()).then(|| {
+        counts
+            .iter()
+            .map(|(value, count)| format!("{}:{}", value, count))
+            .collect::<Vec<_>>()
+            .join(";")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{http, ping, traceroute};
+
+    #[test]
+    fn summarizes_each_type() {
+        let measurements = [
+            ping(1001, 10, 3_599, &[Some(10.0), None, Some(30.0)]),
+            ping(1001, 10, 3_600, &[Some(20.0), Some(40.0)]),
+            http(3001, 10, 100, Some(200)),
+            http(3001, 10, 200, Some(200)),
+            http(3001, 10, 300, Some(503)),
+            traceroute(2001, 10, 100, &["198.51.100.254", "192.0.2.1"], true),
+            traceroute(
+                2001,
+                10,
+                200,
+                &["198.51.100.254", "203.0.113.9", "203.0.113.10"],
+                false,
+            ),
+        ];
+
+        let rows = summarize(&measurements, &[GroupKey::Type], None);
+        let kinds: Vec<_> = rows
+            .iter()
+            .map(|row| row.kind.as_deref().unwrap())
+            .collect();
+        assert_eq!(kinds, ["http", "ping", "traceroute"]);
+
+        let http = &rows[0];
+        assert_eq!(
+            (http.results, http.sent, http.lost, http.timeouts),
+            (3, 3, 0, 0)
+        );
+        assert_eq!(http.http_status.as_deref(), Some("200:2;503:1"));
+        assert_eq!(http.hop_counts, None);
+
+        let ping = &rows[1];
+        assert_eq!(
+            (ping.results, ping.sent, ping.lost, ping.timeouts),
+            (2, 5, 1, 1)
+        );
+        assert_eq!(ping.loss, Some(20.0));
+        assert_eq!(
+            (ping.min, ping.median, ping.max),
+            (Some(10.0), Some(25.0), Some(40.0))
+        );
+        assert_eq!(ping.mean, Some(25.0));
+
+        // Timeouts of traceroutes are runs, not silent hops.
+        let traceroute = &rows[2];
+        assert_eq!(
+            (traceroute.results, traceroute.sent, traceroute.lost),
+            (2, 2, 1)
+        );
+        assert_eq!(traceroute.timeouts, 1);
+        assert_eq!(traceroute.hop_counts.as_deref(), Some("2:1;3:1"));
+        assert_eq!(traceroute.median, Some(2.0));
+    }
+
+    #[test]
+    fn groups_by_key_and_bucket() {
+        let measurements = [
+            ping(1001, 10, 3_599, &[Some(10.0)]),
+            ping(1001, 10, 3_600, &[Some(20.0)]),
+            ping(1002, 20, 7_000, &[Some(30.0)]),
+        ];
+
+        let rows = summarize(&measurements, &[GroupKey::Source], Some(Bucket::Hour));
+        let keys: Vec<_> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.kind.is_some(),
+                    row.prb_id,
+                    row.bucket_start,
+                    row.results,
+                )
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                (false, Some(10), Some(0), 1),
+                (false, Some(10), Some(3_600), 1),
+                (false, Some(20), Some(3_600), 1)
+            ]
+        );
+
+        let rows = summarize(
+            &measurements,
+            &[GroupKey::Measurement, GroupKey::Target],
+            None,
+        );
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].msm_id, Some(1001));
+        assert_eq!(rows[0].dst_addr.as_deref(), Some("192.0.2.1"));
+        assert_eq!((rows[0].results, rows[0].median), (2, Some(15.0)));
+    }
+}
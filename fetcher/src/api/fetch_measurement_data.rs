@@ -1,7 +1,9 @@
+use chrono::{DateTime, Utc};
 use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::api::results::AggregatedMeasurement;
+use crate::{api::results::AggregatedMeasurement, io::cache::ResultCache};
 
 #[derive(Debug, Error)]
 pub enum FetchMeasurementDataError {
@@ -9,39 +11,334 @@ pub enum FetchMeasurementDataError {
     Network(#[source] reqwest::Error),
 
     #[error("RIPE Atlas API returned an error: {status} - {body}")]
-    API { status: StatusCode, body: String },
+    Api { status: StatusCode, body: String },
 
     #[error("Failed to parse expected JSON response body: {0}")]
     ResponseFormat(#[from] serde_json::Error),
+
+    #[error("Failed to use the result cache: {0}")]
+    Cache(#[from] std::io::Error),
+
+    #[error("Measurement {0} is not cached, fetch it once without --offline")]
+    NotCached(String),
+}
+
+/// A result that could not be parsed, kept with its raw JSON so it can be inspected later.
+#[derive(Debug, Serialize)]
+pub struct SkippedResult {
+    pub msm_id: String,
+    pub error: String,
+    pub raw: serde_json::Value,
+}
+
+#[derive(Debug, Default)]
+pub struct MeasurementData {
+    pub measurements: Vec<AggregatedMeasurement>,
+    pub skipped: Vec<SkippedResult>,
+}
+
+/// Where [`get_measurement_data`] takes results from.
+#[derive(Debug, Clone, Copy)]
+pub enum ResultSource<'a> {
+    /// Always download
+    Api,
+    /// Use cached windows and only download and cache what is missing
+    Cache(&'a ResultCache),
+    /// Only use cached windows
+    Offline(&'a ResultCache),
 }
 
+/// Returns the results between `start` and `stop` (unix seconds, inclusive, open if `None`).
+///
+/// With a cache, the cached windows that cover the range without gaps are read first, and only
+/// the rest up to now is downloaded. Of that, only the window that is older than the cache's
+/// settle time is cached, probes can still upload results into the newer part, so it is
+/// downloaded again every time.
 pub async fn get_measurement_data(
     client: &Client,
     measurement_id: &str,
-) -> Result<Vec<AggregatedMeasurement>, FetchMeasurementDataError> {
+    start: Option<i64>,
+    stop: Option<i64>,
+    source: ResultSource<'_>,
+) -> Result<MeasurementData, FetchMeasurementDataError> {
+    let cache = match source {
+        ResultSource::Api => {
+            let text = fetch_raw(client, measurement_id, start, stop).await?;
+            return Ok(parse_results(measurement_id, serde_json::from_str(&text)?));
+        }
+        ResultSource::Cache(cache) | ResultSource::Offline(cache) => cache,
+    };
+
+    let now = Utc::now().timestamp();
+    let start = start.unwrap_or(0);
+    let stop = stop.map_or(now, |stop| stop.min(now));
+    let (mut entries, cursor) = read_cached(cache, measurement_id, start, stop)?;
+
+    if cursor <= stop {
+        if let ResultSource::Offline(_) = source {
+            if cursor == start {
+                return Err(FetchMeasurementDataError::NotCached(
+                    measurement_id.to_string(),
+                ));
+            }
+            let time = |timestamp: i64| {
+                DateTime::from_timestamp(timestamp, 0)
+                    .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                    .unwrap_or_else(|| timestamp.to_string())
+            };
+            println!(
+                "Warning: Measurement {} is not cached from {} to {}, those results are missing",
+                measurement_id,
+                time(cursor),
+                time(stop)
+            );
+        } else {
+            let text = fetch_raw(
+                client,
+                measurement_id,
+                (cursor > 0).then_some(cursor),
+                Some(stop),
+            )
+            .await?;
+            entries.extend(store(
+                cache,
+                measurement_id,
+                cursor,
+                stop.min(cache.settled(now)),
+                &text,
+            )?);
+        }
+    }
+
+    Ok(parse_results(measurement_id, entries))
+}
+
+/// Reads the cached results from `start` up to `stop`, as far as the cached windows cover the
+/// range without gaps. Also returns where the covered range ends, `stop + 1` if it covers all
+/// of it.
+fn read_cached(
+    cache: &ResultCache,
+    measurement_id: &str,
+    start: i64,
+    stop: i64,
+) -> Result<(Vec<serde_json::Value>, i64), FetchMeasurementDataError> {
+    let mut cursor = start;
+    let mut entries: Vec<serde_json::Value> = Vec::new();
+    for page in cache.pages(measurement_id)? {
+        if cursor > stop {
+            break;
+        }
+        if page.start > cursor || page.stop < cursor {
+            continue;
+        }
+        let page_entries: Vec<serde_json::Value> = serde_json::from_str(&cache.read(&page)?)?;
+        let until = page.stop.min(stop);
+        entries.extend(page_entries.into_iter().filter(|raw| {
+            raw["timestamp"]
+                .as_i64()
+                .is_some_and(|timestamp| timestamp >= cursor && timestamp <= until)
+        }));
+        cursor = page.stop + 1;
+    }
+    Ok((entries, cursor))
+}
+
+/// Parses results downloaded from `start` on and caches those up to `settled` as a window of
+/// their own, unless nothing of the download is settled yet. Returns all of them.
+fn store(
+    cache: &ResultCache,
+    measurement_id: &str,
+    start: i64,
+    settled: i64,
+    text: &str,
+) -> Result<Vec<serde_json::Value>, FetchMeasurementDataError> {
+    let entries: Vec<serde_json::Value> = serde_json::from_str(text)?;
+    if settled >= start {
+        let window: Vec<&serde_json::Value> = entries
+            .iter()
+            .filter(|raw| {
+                raw["timestamp"]
+                    .as_i64()
+                    .is_some_and(|timestamp| timestamp <= settled)
+            })
+            .collect();
+        cache.write(
+            measurement_id,
+            start,
+            settled,
+            &serde_json::to_string(&window)?,
+        )?;
+    }
+    Ok(entries)
+}
+
+/// Downloads the results at or after `start` (unix seconds), so a running measurement can be
+/// polled without downloading everything again.
+pub async fn get_measurement_data_since(
+    client: &Client,
+    measurement_id: &str,
+    start: Option<i64>,
+) -> Result<MeasurementData, FetchMeasurementDataError> {
+    get_measurement_data(client, measurement_id, start, None, ResultSource::Api).await
+}
+
+/// Downloads the results of a measurement as the JSON array the API returns.
+async fn fetch_raw(
+    client: &Client,
+    measurement_id: &str,
+    start: Option<i64>,
+    stop: Option<i64>,
+) -> Result<String, FetchMeasurementDataError> {
     let url = format!(
         "https://atlas.ripe.net/api/v2/measurements/{}/results/",
         measurement_id
     );
 
-    let response = client
-        .get(url)
+    let mut request = client.get(url);
+    if let Some(start) = start {
+        request = request.query(&[("start", start)]);
+    }
+    if let Some(stop) = stop {
+        request = request.query(&[("stop", stop)]);
+    }
+    let response = request
         .send()
         .await
-        .map_err(|err| FetchMeasurementDataError::Network(err))?;
+        .map_err(FetchMeasurementDataError::Network)?;
 
     let status = response.status();
     let text = response
         .text()
         .await
-        .map_err(|err| FetchMeasurementDataError::Network(err))?;
+        .map_err(FetchMeasurementDataError::Network)?;
 
     if !status.is_success() {
-        return Err(FetchMeasurementDataError::API { status, body: text });
+        return Err(FetchMeasurementDataError::Api { status, body: text });
+    }
+
+    Ok(text)
+}
+
+/// Parses every result on its own, so a single entry with an unexpected shape only skips that
+/// entry instead of failing the whole measurement.
+pub fn parse_results(measurement_id: &str, entries: Vec<serde_json::Value>) -> MeasurementData {
+    let mut data = MeasurementData::default();
+
+    for raw in entries {
+        match AggregatedMeasurement::deserialize(&raw) {
+            Ok(measurement) => data.measurements.push(measurement.with_raw(raw)),
+            Err(error) => data.skipped.push(SkippedResult {
+                msm_id: measurement_id.to_string(),
+                error: error.to_string(),
+                raw,
+            }),
+        }
     }
 
-    let measurement_data = serde_json::from_str(&text)
-        .map_err(|err| FetchMeasurementDataError::ResponseFormat(err))?;
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ping_json, temp_dir};
+
+    /// Caches a window with one result at each of `timestamps`.
+    fn write(cache: &ResultCache, start: i64, stop: i64, timestamps: &[usize]) {
+        let entries: Vec<serde_json::Value> = timestamps
+            .iter()
+            .map(|timestamp| ping_json(1001, 10, *timestamp, &[Some(1.0)]))
+            .collect();
+        cache
+            .write(
+                "1001",
+                start,
+                stop,
+                &serde_json::to_string(&entries).unwrap(),
+            )
+            .unwrap();
+    }
+
+    fn timestamps(entries: &[serde_json::Value]) -> Vec<i64> {
+        entries
+            .iter()
+            .map(|raw| raw["timestamp"].as_i64().unwrap())
+            .collect()
+    }
 
-    Ok(measurement_data)
+    #[test]
+    fn reads_contiguous_windows_within_the_range() {
+        let cache = ResultCache::new(temp_dir("cached-contiguous"));
+        write(&cache, 100, 199, &[100, 150, 199]);
+        write(&cache, 200, 299, &[200, 250, 299]);
+        // Covered by the first window already.
+        write(&cache, 120, 180, &[150]);
+
+        let (entries, cursor) = read_cached(&cache, "1001", 150, 250).unwrap();
+        assert_eq!(timestamps(&entries), [150, 199, 200, 250]);
+        assert_eq!(cursor, 300);
+
+        let (entries, cursor) = read_cached(&cache, "1001", 50, 250).unwrap();
+        assert!(entries.is_empty());
+        assert_eq!(cursor, 50);
+    }
+
+    #[test]
+    fn stops_reading_at_a_gap() {
+        let cache = ResultCache::new(temp_dir("cached-gap"));
+        write(&cache, 100, 199, &[100, 150]);
+        write(&cache, 250, 299, &[250]);
+
+        let (entries, cursor) = read_cached(&cache, "1001", 100, 299).unwrap();
+        assert_eq!(timestamps(&entries), [100, 150]);
+        assert_eq!(cursor, 200);
+    }
+
+    #[test]
+    fn caches_only_the_settled_part_of_a_download() {
+        let cache = ResultCache::new(temp_dir("cached-settled"));
+        let text = serde_json::to_string(&[
+            ping_json(1001, 10, 100, &[Some(1.0)]),
+            ping_json(1001, 10, 200, &[Some(1.0)]),
+            ping_json(1001, 10, 300, &[Some(1.0)]),
+        ])
+        .unwrap();
+
+        let entries = store(&cache, "1001", 50, 250, &text).unwrap();
+        assert_eq!(timestamps(&entries), [100, 200, 300]);
+        let pages = cache.pages("1001").unwrap();
+        assert_eq!((pages[0].start, pages[0].stop), (50, 250));
+        let (cached, cursor) = read_cached(&cache, "1001", 50, 400).unwrap();
+        assert_eq!(timestamps(&cached), [100, 200]);
+        assert_eq!(cursor, 251);
+
+        // Nothing of a download that only covers unsettled time is cached.
+        let entries = store(&cache, "1001", 251, 250, &text).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(cache.pages("1001").unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn offline_uses_what_is_cached() {
+        let cache = ResultCache::new(temp_dir("cached-offline"));
+        write(&cache, 100, 199, &[100, 150]);
+        write(&cache, 200, 299, &[250]);
+        let offline = ResultSource::Offline(&cache);
+        let client = Client::new();
+        let get = |start, stop| get_measurement_data(&client, "1001", start, stop, offline);
+
+        let data = get(Some(100), Some(299)).await.unwrap();
+        assert_eq!(data.measurements.len(), 3);
+        // Partly cached, only the cached part is returned and the rest reported.
+        let data = get(Some(150), Some(400)).await.unwrap();
+        assert_eq!(data.measurements.len(), 2);
+        assert!(matches!(
+            get(Some(50), Some(299)).await,
+            Err(FetchMeasurementDataError::NotCached(_))
+        ));
+        assert!(matches!(
+            get(None, None).await,
+            Err(FetchMeasurementDataError::NotCached(_))
+        ));
+    }
 }
@@ -0,0 +1,67 @@
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use thiserror::Error;
+
+/// How a measurement was set up, as far as the API tells us.
+#[derive(Debug, Deserialize)]
+pub struct MeasurementDefinition {
+    pub id: u32,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub description: Option<String>,
+    pub target: Option<String>,
+    pub target_ip: Option<String>,
+    pub interval: Option<u32>,
+    pub start_time: Option<i64>,
+    pub stop_time: Option<i64>,
+    pub participant_count: Option<u32>,
+    pub status: Option<MeasurementStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MeasurementStatus {
+    pub name: String,
+}
+
+#[derive(Debug, Error)]
+pub enum FetchMeasurementDefinitionError {
+    #[error("Failed to reach RIPE Atlas API: {0}")]
+    Network(#[source] reqwest::Error),
+
+    #[error("RIPE Atlas API returned an error: {status} - {body}")]
+    Api { status: StatusCode, body: String },
+
+    #[error("Failed to parse expected JSON response body: {0}")]
+    ResponseFormat(#[from] serde_json::Error),
+}
+
+pub async fn get_measurement_definition(
+    client: &Client,
+    measurement_id: &str,
+) -> Result<MeasurementDefinition, FetchMeasurementDefinitionError> {
+    let url = format!(
+        "https://atlas.ripe.net/api/v2/measurements/{}/",
+        measurement_id
+    );
+
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(FetchMeasurementDefinitionError::Network)?;
+
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(FetchMeasurementDefinitionError::Network)?;
+
+    if !status.is_success() {
+        return Err(FetchMeasurementDefinitionError::Api { status, body: text });
+    }
+
+    let definition =
+        serde_json::from_str(&text).map_err(FetchMeasurementDefinitionError::ResponseFormat)?;
+
+    Ok(definition)
+}
@@ -0,0 +1,159 @@
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The parts of a RIPE Atlas probe description we attach to results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeMetadata {
+    pub prb_id: u32,
+    pub country_code: Option<String>,
+    pub asn_v4: Option<u32>,
+    pub asn_v6: Option<u32>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub is_anchor: bool,
+    pub fqdn: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeResponse {
+    id: u32,
+    country_code: Option<String>,
+    asn_v4: Option<u32>,
+    asn_v6: Option<u32>,
+    geometry: Option<Geometry>,
+    #[serde(default)]
+    is_anchor: bool,
+    fqdn: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Geometry {
+    /// GeoJSON order, longitude first.
+    coordinates: Option<(f64, f64)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    results: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnchorResponse {
+    fqdn: Option<String>,
+}
+
+impl From<ProbeResponse> for ProbeMetadata {
+    fn from(probe: ProbeResponse) -> Self {
+        let coordinates = probe.geometry.and_then(|g| g.coordinates);
+        ProbeMetadata {
+            prb_id: probe.id,
+            country_code: probe.country_code,
+            asn_v4: probe.asn_v4,
+            asn_v6: probe.asn_v6,
+            latitude: coordinates.map(|(_, lat)| lat),
+            longitude: coordinates.map(|(lon, _)| lon),
+            is_anchor: probe.is_anchor,
+            fqdn: probe.fqdn,
+        }
+    }
+}
+
+/// Probes per request of [`get_probes`], one page of the probe list.
+pub const PROBE_BATCH: usize = 100;
+
+#[derive(Debug, Error)]
+pub enum FetchProbeMetadataError {
+    #[error("Failed to reach RIPE Atlas API: {0}")]
+    Network(#[source] reqwest::Error),
+
+    #[error("RIPE Atlas API returned an error: {status} - {body}")]
+    Api { status: StatusCode, body: String },
+
+    #[error("Failed to parse expected JSON response body: {0}")]
+    ResponseFormat(#[from] serde_json::Error),
+}
+
+/// Fetches the metadata of up to [`PROBE_BATCH`] probes with a single request. Probes the API
+/// does not know are left out.
+pub async fn get_probes(
+    client: &Client,
+    prb_ids: &[u32],
+) -> Result<Vec<ProbeMetadata>, FetchProbeMetadataError> {
+    let ids: Vec<String> = prb_ids.iter().map(u32::to_string).collect();
+    let url = format!(
+        "https://atlas.ripe.net/api/v2/probes/?id__in={}&page_size={}",
+        ids.join(","),
+        PROBE_BATCH
+    );
+    let page: Page<ProbeResponse> = get_json(client, &url).await?;
+
+    let mut probes = Vec::new();
+    for probe in page.results {
+        probes.push(with_anchor_fqdn(client, probe.into()).await?);
+    }
+    Ok(probes)
+}
+
+/// Looks up the probe or anchor that owns `address`. Returns `None` for addresses that do not
+/// belong to any probe, e.g. plain web servers.
+pub async fn find_probe_by_address(
+    client: &Client,
+    address: &str,
+) -> Result<Option<ProbeMetadata>, FetchProbeMetadataError> {
+    let field = if address.contains(':') {
+        "address_v6"
+    } else {
+        "address_v4"
+    };
+    let url = format!(
+        "https://atlas.ripe.net/api/v2/probes/?{}={}",
+        field, address
+    );
+    let page: Page<ProbeResponse> = get_json(client, &url).await?;
+
+    match page.results.into_iter().next() {
+        Some(probe) => Ok(Some(with_anchor_fqdn(client, probe.into()).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Probe descriptions do not carry the host name of anchors, it has to come from the anchor
+/// endpoint.
+async fn with_anchor_fqdn(
+    client: &Client,
+    mut probe: ProbeMetadata,
+) -> Result<ProbeMetadata, FetchProbeMetadataError> {
+    if probe.is_anchor && probe.fqdn.is_none() {
+        let url = format!(
+            "https://atlas.ripe.net/api/v2/anchors/?probe={}",
+            probe.prb_id
+        );
+        let page: Page<AnchorResponse> = get_json(client, &url).await?;
+        probe.fqdn = page.results.into_iter().find_map(|anchor| anchor.fqdn);
+    }
+    Ok(probe)
+}
+
+async fn get_json<T: for<'de> Deserialize<'de>>(
+    client: &Client,
+    url: &str,
+) -> Result<T, FetchProbeMetadataError> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(FetchProbeMetadataError::Network)?;
+
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(FetchProbeMetadataError::Network)?;
+
+    if !status.is_success() {
+        return Err(FetchProbeMetadataError::Api { status, body: text });
+    }
+
+    Ok(serde_json::from_str(&text)?)
+}
@@ -1,2 +1,5 @@
 pub mod fetch_measurement_data;
+pub mod fetch_measurement_definition;
+pub mod fetch_probe_metadata;
 pub mod results;
+pub mod stream;
@@ -21,10 +21,267 @@ impl AggregatedMeasurement {
             AggregatedMeasurement::TraceRoute(_) => "traceroute",
         }
     }
+
+    pub fn msm_id(&self) -> u32 {
+        match self {
+            AggregatedMeasurement::Http(m) => m.msm_id,
+            AggregatedMeasurement::Ping(m) => m.msm_id,
+            AggregatedMeasurement::TraceRoute(m) => m.msm_id,
+        }
+    }
+
+    pub fn timestamp(&self) -> usize {
+        match self {
+            AggregatedMeasurement::Http(m) => m.timestamp,
+            AggregatedMeasurement::Ping(m) => m.timestamp,
+            AggregatedMeasurement::TraceRoute(m) => m.timestamp,
+        }
+    }
+
+    pub fn prb_id(&self) -> u32 {
+        match self {
+            AggregatedMeasurement::Http(m) => m.prb_id,
+            AggregatedMeasurement::Ping(m) => m.prb_id,
+            AggregatedMeasurement::TraceRoute(m) => m.prb_id,
+        }
+    }
+
+    /// Keeps the result as received from the API, so the JSON Lines saver can write back fields
+    /// that are not parsed.
+    pub fn with_raw(mut self, val: serde_json::Value) -> Self {
+        match &mut self {
+            AggregatedMeasurement::Http(m) => m.raw = Some(val),
+            AggregatedMeasurement::Ping(m) => m.raw = Some(val),
+            AggregatedMeasurement::TraceRoute(m) => m.raw = Some(val),
+        }
+        self
+    }
+
+    pub fn raw(&self) -> Option<&serde_json::Value> {
+        match self {
+            AggregatedMeasurement::Http(m) => m.raw.as_ref(),
+            AggregatedMeasurement::Ping(m) => m.raw.as_ref(),
+            AggregatedMeasurement::TraceRoute(m) => m.raw.as_ref(),
+        }
+    }
+
+    /// The target address, for HTTP the address the first request went to. HTTP results whose
+    /// requests failed before connecting, e.g. on DNS, have none.
+    pub fn dst_addr(&self) -> Option<&str> {
+        match self {
+            AggregatedMeasurement::Http(m) => m.result.iter().find_map(|r| r.dst_addr.as_deref()),
+            AggregatedMeasurement::Ping(m) => Some(&m.dst_addr),
+            AggregatedMeasurement::TraceRoute(m) => Some(&m.dst_addr),
+        }
+    }
+
+    /// The target of the measurement, for HTTP the host of the URI, which is known even when
+    /// no request got to an address.
+    pub fn target(&self) -> Option<&str> {
+        match self {
+            AggregatedMeasurement::Http(m) => m
+                .uri
+                .as_deref()
+                .and_then(uri_host)
+                .or_else(|| self.dst_addr()),
+            _ => self.dst_addr(),
+        }
+    }
+}
+
+/// The host of a URI such as `https://user@[2001:db8::1]:8443/path`, without brackets or port.
+fn uri_host(uri: &str) -> Option<&str> {
+    let rest = uri.split_once("://").map_or(uri, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = match host.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next()?,
+        None => host.split(':').next()?,
+    };
+    (!host.is_empty()).then_some(host)
+}
+
+/// How a single flattened result ended, so missing values can be told apart from real zeros.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Ok,
+    Timeout,
+    Error,
+}
+
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Timeout => "timeout",
+            Outcome::Error => "error",
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PingMeasurement {
+    pub dst_addr: String,
+    pub src_addr: String,
+    pub proto: String,
+    pub rcvd: u32,
+    pub sent: u32,
+    pub min: f32,
+    pub max: f32,
+    pub avg: f32,
+    #[serde(default)]
+    pub result: Vec<PingReply>,
+    pub msm_id: u32,
+    pub timestamp: usize,
+    pub prb_id: u32,
+    /// The result as received, see [`AggregatedMeasurement::with_raw`].
+    #[serde(skip)]
+    pub raw: Option<serde_json::Value>,
+}
+
+impl PingMeasurement {
+    /// RTTs of the replies in the order they arrived, without duplicates.
+    pub fn rtts(&self) -> Vec<f32> {
+        self.result
+            .iter()
+            .filter(|reply| !reply.is_duplicate())
+            .filter_map(|reply| reply.rtt)
+            .collect()
+    }
+
+    pub fn loss(&self) -> Option<f32> {
+        (self.sent > 0)
+            .then(|| self.sent.saturating_sub(self.rcvd) as f32 / self.sent as f32 * 100.0)
+    }
+
+    pub fn stddev(&self) -> Option<f32> {
+        let rtts = self.rtts();
+        if rtts.len() < 2 {
+            return None;
+        }
+
+        let mean = rtts.iter().sum::<f32>() / rtts.len() as f32;
+        let variance = rtts.iter().map(|rtt| (rtt - mean).powi(2)).sum::<f32>() / rtts.len() as f32;
+        Some(variance.sqrt())
+    }
+
+    /// Mean absolute difference between the RTTs of consecutive replies.
+    pub fn jitter(&self) -> Option<f32> {
+        let rtts = self.rtts();
+        if rtts.len() < 2 {
+            return None;
+        }
+
+        let total: f32 = rtts.windows(2).map(|pair| (pair[1] - pair[0]).abs()).sum();
+        Some(total / (rtts.len() - 1) as f32)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PingReply {
+    pub rtt: Option<f32>,
+    pub ttl: Option<u32>,
+    pub dup: Option<u32>,
+    pub srcaddr: Option<String>,
+    pub x: Option<String>,
+    pub error: Option<String>,
+}
+
+impl PingReply {
+    pub fn is_duplicate(&self) -> bool {
+        self.dup.is_some_and(|dup| dup > 0)
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        self.x.as_deref() == Some("*")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlattenedPingMeasurement {
+    pub dst_addr: String,
+    pub src_addr: String,
+    pub proto: String,
+    pub rcvd: u32,
+    pub sent: u32,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+    pub avg: Option<f32>,
+    pub msm_id: u32,
+    pub timestamp: usize,
+    pub prb_id: u32,
+    pub outcome: Outcome,
+    pub loss: Option<f32>,
+    pub stddev: Option<f32>,
+    pub jitter: Option<f32>,
+    pub duplicates: u32,
+    pub timeouts: u32,
+    pub errors: u32,
+}
+
+impl FlattenedPingMeasurement {
+    pub fn from_ping_measurement(measurement: &PingMeasurement) -> Self {
+        let count = |predicate: fn(&PingReply) -> bool| {
+            measurement.result.iter().filter(|r| predicate(r)).count() as u32
+        };
+        // RIPE Atlas reports -1 for min/avg/max when no reply arrived.
+        let rtt = |value: f32| (measurement.rcvd > 0 && value >= 0.0).then_some(value);
+        let errors = count(|r| r.error.is_some());
+        let outcome = if measurement.rcvd > 0 {
+            Outcome::Ok
+        } else if errors > 0 {
+            Outcome::Error
+        } else {
+            Outcome::Timeout
+        };
+
+        Self {
+            dst_addr: measurement.dst_addr.clone(),
+            src_addr: measurement.src_addr.clone(),
+            proto: measurement.proto.clone(),
+            rcvd: measurement.rcvd,
+            sent: measurement.sent,
+            min: rtt(measurement.min),
+            max: rtt(measurement.max),
+            avg: rtt(measurement.avg),
+            msm_id: measurement.msm_id,
+            timestamp: measurement.timestamp,
+            prb_id: measurement.prb_id,
+            outcome,
+            loss: measurement.loss(),
+            stddev: measurement.stddev(),
+            jitter: measurement.jitter(),
+            duplicates: count(PingReply::is_duplicate),
+            timeouts: count(PingReply::is_timeout),
+            errors,
+        }
+    }
+
+    /// The columns earlier versions wrote, with missing RTTs as -1 like RIPE Atlas reports them.
+    pub fn with_legacy_sentinels(self) -> LegacyPingMeasurement {
+        LegacyPingMeasurement {
+            dst_addr: self.dst_addr,
+            src_addr: self.src_addr,
+            proto: self.proto,
+            rcvd: self.rcvd,
+            sent: self.sent,
+            min: self.min.unwrap_or(-1.0),
+            max: self.max.unwrap_or(-1.0),
+            avg: self.avg.unwrap_or(-1.0),
+            msm_id: self.msm_id,
+            timestamp: self.timestamp,
+            prb_id: self.prb_id,
+        }
+    }
+}
+
+/// A ping row in the format of earlier versions, see
+/// [`FlattenedPingMeasurement::with_legacy_sentinels`].
+#[derive(Debug, Serialize)]
+pub struct LegacyPingMeasurement {
     pub dst_addr: String,
     pub src_addr: String,
     pub proto: String,
@@ -38,72 +295,247 @@ pub struct PingMeasurement {
     pub prb_id: u32,
 }
 
+/// One row per ping reply, for analyses that need more than the min/avg/max summary.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlattenedPingPacket {
+    pub msm_id: u32,
+    pub prb_id: u32,
+    pub timestamp: usize,
+    pub src_addr: String,
+    pub dst_addr: String,
+    pub seq: u32,
+    pub from: String,
+    pub rtt: Option<f32>,
+    pub ttl: Option<u32>,
+    pub dup: bool,
+    pub timed_out: bool,
+    pub error: Option<String>,
+}
+
+impl FlattenedPingPacket {
+    pub fn from_ping_measurement(measurement: &PingMeasurement) -> Vec<Self> {
+        measurement
+            .result
+            .iter()
+            .enumerate()
+            .map(|(seq, reply)| FlattenedPingPacket {
+                msm_id: measurement.msm_id,
+                prb_id: measurement.prb_id,
+                timestamp: measurement.timestamp,
+                src_addr: measurement.src_addr.clone(),
+                dst_addr: measurement.dst_addr.clone(),
+                seq: seq as u32,
+                from: reply
+                    .srcaddr
+                    .clone()
+                    .unwrap_or_else(|| measurement.dst_addr.clone()),
+                rtt: reply.rtt,
+                ttl: reply.ttl,
+                dup: reply.is_duplicate(),
+                timed_out: reply.is_timeout(),
+                error: reply.error.clone(),
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HttpMeasurement {
-    pub ttr: f32,
+    pub ttr: Option<f32>,
+    pub uri: Option<String>,
     pub result: Vec<HttpResult>,
     pub msm_id: u32,
     pub timestamp: usize,
     pub prb_id: u32,
+    /// The result as received, see [`AggregatedMeasurement::with_raw`].
+    #[serde(skip)]
+    pub raw: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HttpResult {
-    pub method: String,
-    pub dst_addr: String,
+    pub method: Option<String>,
+    pub dst_addr: Option<String>,
     pub src_addr: Option<String>,
     pub rt: Option<f32>,
     pub res: Option<u32>,
     pub ver: Option<String>,
     pub hsize: Option<u32>,
     pub bsize: Option<u32>,
+    pub ttr: Option<f32>,
+    pub ttc: Option<f32>,
+    pub ttfb: Option<f32>,
+    pub readtiming: Option<Vec<ReadTiming>>,
+    pub err: Option<String>,
+    pub dnserr: Option<String>,
+}
+
+/// Offset into the response and the time it was read at, reported with `more_extended_timing`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadTiming {
+    pub o: String,
+    pub t: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpErrorKind {
+    Dns,
+    Connect,
+    Tls,
+    Timeout,
+    Other,
+}
+
+impl HttpErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpErrorKind::Dns => "dns",
+            HttpErrorKind::Connect => "connect",
+            HttpErrorKind::Tls => "tls",
+            HttpErrorKind::Timeout => "timeout",
+            HttpErrorKind::Other => "other",
+        }
+    }
+}
+
+impl HttpResult {
+    /// Classifies the free text error RIPE Atlas reports in `err`/`dnserr`.
+    pub fn error_kind(&self) -> Option<HttpErrorKind> {
+        if self.dnserr.is_some() {
+            return Some(HttpErrorKind::Dns);
+        }
+
+        let err = self.err.as_deref()?.to_lowercase();
+        let kind = if err.contains("timeout") || err.contains("timed out") {
+            HttpErrorKind::Timeout
+        } else if ["ssl", "tls", "certificate"]
+            .iter()
+            .any(|needle| err.contains(needle))
+        {
+            HttpErrorKind::Tls
+        } else if err.contains("connect") || err.contains("refused") || err.contains("unreachable")
+        {
+            HttpErrorKind::Connect
+        } else if err.contains("dns") || err.contains("resolv") {
+            HttpErrorKind::Dns
+        } else {
+            HttpErrorKind::Other
+        };
+        Some(kind)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FlattenedHttpMeasurement {
-    pub ttr: f32,
-    pub method: String,
-    pub dst_addr: String,
-    pub src_addr: String,
-    pub rt: f32,
-    pub res: u32,
-    pub ver: String,
-    pub hsize: u32,
-    pub bsize: u32,
+    pub ttr: Option<f32>,
+    pub method: Option<String>,
+    pub dst_addr: Option<String>,
+    pub src_addr: Option<String>,
+    pub rt: Option<f32>,
+    pub res: Option<u32>,
+    pub ver: Option<String>,
+    pub hsize: Option<u32>,
+    pub bsize: Option<u32>,
     pub msm_id: u32,
     pub timestamp: usize,
     pub prb_id: u32,
+    pub outcome: Outcome,
+    pub result_index: u32,
+    pub uri: Option<String>,
+    pub ttc: Option<f32>,
+    pub ttfb: Option<f32>,
+    pub read_time: Option<f32>,
+    pub error_kind: Option<HttpErrorKind>,
+    pub error: Option<String>,
 }
 
 impl FlattenedHttpMeasurement {
-    pub fn from_http_measurement(measurement: &HttpMeasurement) -> Self {
-        let http_result = measurement
+    /// Creates one row per request in the result, a measurement may contain several or none.
+    pub fn from_http_measurement(measurement: &HttpMeasurement) -> Vec<Self> {
+        measurement
             .result
-            .first()
-            .expect("Http measurement should contain exactly one result, first() is none");
-        Self {
-            ttr: measurement.ttr,
-            method: http_result.method.clone(),
-            dst_addr: http_result.dst_addr.clone(),
-            src_addr: http_result
-                .src_addr
-                .clone()
-                .unwrap_or_else(|| "timeout".to_string()),
-            rt: http_result.rt.unwrap_or_else(|| 0.0),
-            res: http_result.res.unwrap_or_else(|| 408),
-            ver: http_result
-                .ver
-                .clone()
-                .unwrap_or_else(|| "timeout".to_string()),
-            hsize: http_result.hsize.unwrap_or_else(|| 0),
-            bsize: http_result.bsize.unwrap_or_else(|| 0),
-            msm_id: measurement.msm_id,
-            timestamp: measurement.timestamp,
-            prb_id: measurement.prb_id,
+            .iter()
+            .enumerate()
+            .map(|(index, http_result)| {
+                let error_kind = http_result.error_kind();
+                let outcome = match (error_kind, http_result.res) {
+                    (Some(HttpErrorKind::Timeout), _) => Outcome::Timeout,
+                    (Some(_), _) => Outcome::Error,
+                    (None, Some(_)) => Outcome::Ok,
+                    (None, None) => Outcome::Timeout,
+                };
+
+                Self {
+                    ttr: http_result.ttr.or(measurement.ttr),
+                    method: http_result.method.clone(),
+                    dst_addr: http_result.dst_addr.clone(),
+                    src_addr: http_result.src_addr.clone(),
+                    rt: http_result.rt,
+                    res: http_result.res,
+                    ver: http_result.ver.clone(),
+                    hsize: http_result.hsize,
+                    bsize: http_result.bsize,
+                    msm_id: measurement.msm_id,
+                    timestamp: measurement.timestamp,
+                    prb_id: measurement.prb_id,
+                    outcome,
+                    result_index: index as u32,
+                    uri: measurement.uri.clone(),
+                    ttc: http_result.ttc,
+                    ttfb: http_result.ttfb,
+                    read_time: http_result
+                        .readtiming
+                        .as_ref()
+                        .and_then(|timings| timings.last())
+                        .map(|timing| timing.t),
+                    error_kind,
+                    error: http_result
+                        .dnserr
+                        .clone()
+                        .or_else(|| http_result.err.clone()),
+                }
+            })
+            .collect()
+    }
+
+    /// The columns earlier versions wrote, with the placeholders they used for timeouts.
+    pub fn with_legacy_sentinels(self) -> LegacyHttpMeasurement {
+        LegacyHttpMeasurement {
+            ttr: self.ttr,
+            method: self.method,
+            dst_addr: self.dst_addr,
+            src_addr: self.src_addr.unwrap_or_else(|| "timeout".to_string()),
+            rt: self.rt.unwrap_or(0.0),
+            res: self.res.unwrap_or(408),
+            ver: self.ver.unwrap_or_else(|| "timeout".to_string()),
+            hsize: self.hsize.unwrap_or(0),
+            bsize: self.bsize.unwrap_or(0),
+            msm_id: self.msm_id,
+            timestamp: self.timestamp,
+            prb_id: self.prb_id,
         }
     }
 }
 
+/// An HTTP row in the format of earlier versions, see
+/// [`FlattenedHttpMeasurement::with_legacy_sentinels`].
+#[derive(Debug, Serialize)]
+pub struct LegacyHttpMeasurement {
+    pub ttr: Option<f32>,
+    pub method: Option<String>,
+    pub dst_addr: Option<String>,
+    pub src_addr: String,
+    pub rt: f32,
+    pub res: u32,
+    pub ver: String,
+    pub hsize: u32,
+    pub bsize: u32,
+    pub msm_id: u32,
+    pub timestamp: usize,
+    pub prb_id: u32,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TraceRouteMeasurement {
     pub endtime: usize,
@@ -116,12 +548,38 @@ pub struct TraceRouteMeasurement {
     pub prb_id: u32,
     pub msm_id: u32,
     pub timestamp: usize,
+    /// The result as received, see [`AggregatedMeasurement::with_raw`].
+    #[serde(skip)]
+    pub raw: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TraceRouteResult {
     pub hop: u32,
+    #[serde(default)]
     pub result: Vec<HopResult>,
+    pub error: Option<String>,
+}
+
+impl TraceRouteResult {
+    /// Replies that count towards the hop statistics, i.e. neither late nor duplicated.
+    pub fn answered(&self) -> impl Iterator<Item = &HopResult> {
+        self.result
+            .iter()
+            .filter(|reply| reply.rtt.is_some() && !reply.is_late() && !reply.is_duplicate())
+    }
+
+    /// All distinct addresses that answered on this hop, in the order they were first seen.
+    /// More than one address usually means the path is load balanced (ECMP).
+    pub fn addresses(&self) -> Vec<&str> {
+        let mut addresses: Vec<&str> = Vec::new();
+        for from in self.result.iter().filter_map(|reply| reply.from.as_deref()) {
+            if !addresses.contains(&from) {
+                addresses.push(from);
+            }
+        }
+        addresses
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -131,8 +589,82 @@ pub struct HopResult {
     pub size: Option<u32>,
     pub rtt: Option<f32>,
     pub x: Option<String>,
+    /// ICMP error indication, either a letter such as `H` or `N` or the numeric ICMP code.
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub err: Option<String>,
+    pub late: Option<u32>,
+    pub dup: Option<bool>,
+    pub itos: Option<u32>,
+    pub ittl: Option<u32>,
+    pub flags: Option<String>,
+    pub icmpext: Option<IcmpExtension>,
+}
+
+impl HopResult {
+    pub fn is_timeout(&self) -> bool {
+        self.x.as_deref() == Some("*")
+    }
+
+    pub fn is_late(&self) -> bool {
+        self.late.is_some_and(|late| late > 0)
+    }
+
+    pub fn is_duplicate(&self) -> bool {
+        self.dup.unwrap_or(false)
+    }
+
+    pub fn mpls_labels(&self) -> Vec<u32> {
+        self.icmpext
+            .iter()
+            .flat_map(|ext| &ext.obj)
+            .flat_map(|obj| &obj.mpls)
+            .filter_map(|entry| entry.label)
+            .collect()
+    }
+}
+
+/// ICMP extension structure (RFC 4884) attached to a hop reply. Every field is optional, so a
+/// malformed extension does not cost the whole result.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IcmpExtension {
+    pub version: Option<u32>,
+    pub rfc4884: Option<u32>,
+    #[serde(default)]
+    pub obj: Vec<IcmpExtensionObject>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IcmpExtensionObject {
+    pub class: Option<u32>,
+    #[serde(rename = "type")]
+    pub kind: Option<u32>,
+    #[serde(default)]
+    pub mpls: Vec<MplsLabel>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MplsLabel {
+    pub label: Option<u32>,
+    pub exp: Option<u32>,
+    pub s: Option<u32>,
+    pub ttl: Option<u32>,
 }
 
+fn string_or_number<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(
+        match Option::<serde_json::Value>::deserialize(deserializer)? {
+            Some(serde_json::Value::String(value)) => Some(value),
+            Some(serde_json::Value::Null) | None => None,
+            Some(other) => Some(other.to_string()),
+        },
+    )
+}
+
+/// Per-hop summary of a traceroute. `from` is the first responding address, `addresses` lists
+/// every distinct responder separated by `;`.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FlattenedTraceRouteMeasurement {
     pub msm_id: u32,
@@ -141,17 +673,25 @@ pub struct FlattenedTraceRouteMeasurement {
     pub hop: u32,
     pub sent: u32,
     pub received: u32,
-    pub min: f32,
-    pub avg: f32,
-    pub max: f32,
-    pub from: String,
+    pub min: Option<f32>,
+    pub avg: Option<f32>,
+    pub max: Option<f32>,
+    pub from: Option<String>,
     pub timed_out: bool,
+    pub outcome: Outcome,
     pub timestamp: usize,
     pub prb_id: u32,
     pub endtime: usize,
     pub proto: String,
     pub paris_id: u32,
     pub destination_ip_responded: bool,
+    pub addresses: Option<String>,
+    pub address_count: u32,
+    pub late: u32,
+    pub duplicates: u32,
+    pub errors: u32,
+    pub mpls: Option<String>,
+    pub error: Option<String>,
 }
 
 impl FlattenedTraceRouteMeasurement {
@@ -162,9 +702,13 @@ impl FlattenedTraceRouteMeasurement {
             .result
             .iter()
             .map(|hop_result| {
-                let rtts: Vec<f32> = hop_result.result.iter().filter_map(|hr| hr.rtt).collect();
+                let rtts: Vec<f32> = hop_result.answered().filter_map(|hr| hr.rtt).collect();
 
-                let sent = hop_result.result.len() as u32;
+                let sent = hop_result
+                    .result
+                    .iter()
+                    .filter(|hr| !hr.is_late() && !hr.is_duplicate())
+                    .count() as u32;
                 let received = rtts.len() as u32;
                 let timed_out = received == 0;
 
@@ -172,16 +716,20 @@ impl FlattenedTraceRouteMeasurement {
                     let min = rtts.iter().cloned().fold(f32::INFINITY, f32::min);
                     let max = rtts.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
                     let avg = rtts.iter().sum::<f32>() / received as f32;
-                    (min, max, avg)
+                    (Some(min), Some(max), Some(avg))
                 } else {
-                    (0.0, 0.0, 0.0)
+                    (None, None, None)
                 };
 
-                let from = hop_result
-                        .result
-                        .iter()
-                        .find_map(|hr| hr.from.clone())
-                        .unwrap_or_else(|| "unknown".to_string());
+                let from = hop_result.result.iter().find_map(|hr| hr.from.clone());
+                let addresses = hop_result.addresses();
+                let mut mpls: Vec<String> = Vec::new();
+                for label in hop_result.result.iter().flat_map(|hr| hr.mpls_labels()) {
+                    let label = label.to_string();
+                    if !mpls.contains(&label) {
+                        mpls.push(label);
+                    }
+                }
 
                 FlattenedTraceRouteMeasurement {
                     msm_id: traceroute_measurement.msm_id,
@@ -195,14 +743,343 @@ impl FlattenedTraceRouteMeasurement {
                     max,
                     from,
                     timed_out,
+                    outcome: if hop_result.error.is_some() {
+                        Outcome::Error
+                    } else if timed_out {
+                        Outcome::Timeout
+                    } else {
+                        Outcome::Ok
+                    },
                     timestamp: traceroute_measurement.timestamp,
                     prb_id: traceroute_measurement.prb_id,
                     endtime: traceroute_measurement.endtime,
                     proto: traceroute_measurement.proto.clone(),
                     paris_id: traceroute_measurement.paris_id,
                     destination_ip_responded: traceroute_measurement.destination_ip_responded,
+                    addresses: (!addresses.is_empty()).then(|| addresses.join(";")),
+                    address_count: addresses.len() as u32,
+                    late: hop_result.result.iter().filter(|hr| hr.is_late()).count() as u32,
+                    duplicates: hop_result
+                        .result
+                        .iter()
+                        .filter(|hr| hr.is_duplicate())
+                        .count() as u32,
+                    errors: hop_result
+                        .result
+                        .iter()
+                        .filter(|hr| hr.err.is_some())
+                        .count() as u32,
+                    mpls: (!mpls.is_empty()).then(|| mpls.join(";")),
+                    error: hop_result.error.clone(),
                 }
             })
             .collect()
     }
+
+    /// The columns earlier versions wrote, with the placeholders they used for timeouts.
+    pub fn with_legacy_sentinels(self) -> LegacyTraceRouteMeasurement {
+        LegacyTraceRouteMeasurement {
+            msm_id: self.msm_id,
+            src_addr: self.src_addr,
+            dst_addr: self.dst_addr,
+            hop: self.hop,
+            sent: self.sent,
+            received: self.received,
+            min: self.min.unwrap_or(0.0),
+            avg: self.avg.unwrap_or(0.0),
+            max: self.max.unwrap_or(0.0),
+            from: self.from.unwrap_or_else(|| "unknown".to_string()),
+            timed_out: self.timed_out,
+            timestamp: self.timestamp,
+            prb_id: self.prb_id,
+            endtime: self.endtime,
+            proto: self.proto,
+            paris_id: self.paris_id,
+            destination_ip_responded: self.destination_ip_responded,
+        }
+    }
+}
+
+/// A per-hop row in the format of earlier versions, see
+/// [`FlattenedTraceRouteMeasurement::with_legacy_sentinels`].
+#[derive(Debug, Serialize)]
+pub struct LegacyTraceRouteMeasurement {
+    pub msm_id: u32,
+    pub src_addr: String,
+    pub dst_addr: String,
+    pub hop: u32,
+    pub sent: u32,
+    pub received: u32,
+    pub min: f32,
+    pub avg: f32,
+    pub max: f32,
+    pub from: String,
+    pub timed_out: bool,
+    pub timestamp: usize,
+    pub prb_id: u32,
+    pub endtime: usize,
+    pub proto: String,
+    pub paris_id: u32,
+    pub destination_ip_responded: bool,
+}
+
+/// One row per traceroute reply, keeping everything the per-hop summary folds away.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlattenedHopReply {
+    pub msm_id: u32,
+    pub prb_id: u32,
+    pub timestamp: usize,
+    pub src_addr: String,
+    pub dst_addr: String,
+    pub hop: u32,
+    pub seq: u32,
+    pub from: Option<String>,
+    pub rtt: Option<f32>,
+    pub ttl: Option<u32>,
+    pub size: Option<u32>,
+    pub timed_out: bool,
+    pub late: Option<u32>,
+    pub dup: bool,
+    pub err: Option<String>,
+    pub itos: Option<u32>,
+    pub ittl: Option<u32>,
+    pub flags: Option<String>,
+    pub mpls: Option<String>,
+}
+
+impl FlattenedHopReply {
+    pub fn from_traceroute_measurement(measurement: &TraceRouteMeasurement) -> Vec<Self> {
+        measurement
+            .result
+            .iter()
+            .flat_map(|hop| {
+                hop.result
+                    .iter()
+                    .enumerate()
+                    .map(move |(seq, reply)| (hop.hop, seq, reply))
+            })
+            .map(|(hop, seq, reply)| {
+                let mpls: Vec<String> = reply
+                    .mpls_labels()
+                    .iter()
+                    .map(|label| label.to_string())
+                    .collect();
+
+                FlattenedHopReply {
+                    msm_id: measurement.msm_id,
+                    prb_id: measurement.prb_id,
+                    timestamp: measurement.timestamp,
+                    src_addr: measurement.src_addr.clone(),
+                    dst_addr: measurement.dst_addr.clone(),
+                    hop,
+                    seq: seq as u32,
+                    from: reply.from.clone(),
+                    rtt: reply.rtt,
+                    ttl: reply.ttl,
+                    size: reply.size,
+                    timed_out: reply.is_timeout(),
+                    late: reply.late,
+                    dup: reply.is_duplicate(),
+                    err: reply.err.clone(),
+                    itos: reply.itos,
+                    ittl: reply.ittl,
+                    flags: reply.flags.clone(),
+                    mpls: (!mpls.is_empty()).then(|| mpls.join(";")),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn ping(sent: u32, rcvd: u32, result: Value) -> PingMeasurement {
+        serde_json::from_value(json!({
+            "dst_addr": "192.0.2.1", "src_addr": "198.51.100.1", "proto": "ICMP",
+            "sent": sent, "rcvd": rcvd, "min": -1, "max": -1, "avg": -1,
+            "result": result, "msm_id": 1001, "timestamp": 100, "prb_id": 10,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn ping_statistics_ignore_duplicates() {
+        let measurement = ping(
+            3,
+            2,
+            json!([{ "rtt": 10.0 }, { "rtt": 10.5, "dup": 1 }, { "x": "*" }, { "rtt": 14.0 }]),
+        );
+
+        assert_eq!(measurement.rtts(), [10.0, 14.0]);
+        assert!((measurement.loss().unwrap() - 100.0 / 3.0).abs() < 1e-4);
+        assert_eq!(measurement.stddev(), Some(2.0));
+        assert_eq!(measurement.jitter(), Some(4.0));
+
+        let row = FlattenedPingMeasurement::from_ping_measurement(&measurement);
+        assert_eq!(row.outcome, Outcome::Ok);
+        assert_eq!((row.duplicates, row.timeouts, row.errors), (1, 1, 0));
+    }
+
+    #[test]
+    fn ping_without_replies() {
+        let measurement = ping(2, 0, json!([{ "x": "*" }, { "x": "*" }]));
+        assert_eq!(measurement.loss(), Some(100.0));
+        assert_eq!((measurement.stddev(), measurement.jitter()), (None, None));
+
+        let row = FlattenedPingMeasurement::from_ping_measurement(&measurement);
+        assert_eq!(row.outcome, Outcome::Timeout);
+        assert_eq!((row.min, row.avg, row.max), (None, None, None));
+        assert_eq!(row.with_legacy_sentinels().avg, -1.0);
+
+        let measurement = ping(
+            1,
+            0,
+            json!([{ "error": "sendto failed: Network is unreachable" }]),
+        );
+        let row = FlattenedPingMeasurement::from_ping_measurement(&measurement);
+        assert_eq!((row.outcome, row.errors), (Outcome::Error, 1));
+        assert_eq!(ping(0, 0, json!([])).loss(), None);
+    }
+
+    fn http_rows(result: Value) -> Vec<FlattenedHttpMeasurement> {
+        let measurement: HttpMeasurement = serde_json::from_value(json!({
+            "uri": "https://example.org/", "result": result,
+            "msm_id": 3001, "timestamp": 100, "prb_id": 10,
+        }))
+        .unwrap();
+        FlattenedHttpMeasurement::from_http_measurement(&measurement)
+    }
+
+    #[test]
+    fn http_error_kinds() {
+        let kind = |result: Value| http_rows(json!([result]))[0].error_kind;
+
+        assert_eq!(
+            kind(json!({ "dnserr": "non-recoverable failure in name resolution" })),
+            Some(HttpErrorKind::Dns)
+        );
+        assert_eq!(
+            kind(json!({ "err": "timeout reading chunk" })),
+            Some(HttpErrorKind::Timeout)
+        );
+        assert_eq!(
+            kind(json!({ "err": "connect: Connection timed out" })),
+            Some(HttpErrorKind::Timeout)
+        );
+        assert_eq!(
+            kind(json!({ "err": "SSL_connect: certificate verify failed" })),
+            Some(HttpErrorKind::Tls)
+        );
+        assert_eq!(
+            kind(json!({ "err": "connect: Network is unreachable" })),
+            Some(HttpErrorKind::Connect)
+        );
+        assert_eq!(
+            kind(json!({ "err": "bad chunk line" })),
+            Some(HttpErrorKind::Other)
+        );
+        assert_eq!(kind(json!({ "res": 200 })), None);
+    }
+
+    #[test]
+    fn http_rows_per_request() {
+        let rows = http_rows(json!([
+            {
+                "method": "GET", "dst_addr": "192.0.2.1", "src_addr": "198.51.100.1", "rt": 30.0,
+                "res": 200, "ver": "1.1", "hsize": 100, "bsize": 1000, "ttc": 5.0, "ttfb": 20.0,
+                "readtiming": [{ "o": "0", "t": 20.0 }, { "o": "1100", "t": 28.5 }],
+            },
+            { "dnserr": "non-recoverable failure in name resolution" },
+            { "err": "timeout reading chunk", "dst_addr": "192.0.2.1" },
+        ]));
+
+        assert_eq!(rows.len(), 3);
+        let ok = &rows[0];
+        assert_eq!((ok.outcome, ok.result_index), (Outcome::Ok, 0));
+        assert_eq!(
+            (ok.ttc, ok.ttfb, ok.read_time),
+            (Some(5.0), Some(20.0), Some(28.5))
+        );
+        assert_eq!(ok.uri.as_deref(), Some("https://example.org/"));
+
+        let dns = &rows[1];
+        assert_eq!((dns.outcome, dns.result_index), (Outcome::Error, 1));
+        assert_eq!(
+            (dns.method.as_deref(), dns.dst_addr.as_deref(), dns.res),
+            (None, None, None)
+        );
+        assert_eq!(
+            dns.error.as_deref(),
+            Some("non-recoverable failure in name resolution")
+        );
+
+        assert_eq!(rows[2].outcome, Outcome::Timeout);
+        assert!(http_rows(json!([])).is_empty());
+    }
+
+    #[test]
+    fn mpls_labels_from_icmp_extensions() {
+        let measurement: TraceRouteMeasurement = serde_json::from_value(json!({
+            "endtime": 110, "dst_addr": "192.0.2.1", "src_addr": "198.51.100.1", "proto": "ICMP",
+            "paris_id": 1, "destination_ip_responded": true,
+            "prb_id": 10, "msm_id": 2001, "timestamp": 100,
+            "result": [{
+                "hop": 1,
+                "result": [
+                    {
+                        "from": "203.0.113.1", "rtt": 5.0, "size": 140, "ttl": 254,
+                        "icmpext": { "version": 2, "rfc4884": 1, "obj": [{
+                            "class": 1, "type": 1,
+                            "mpls": [
+                                { "label": 24005, "exp": 0, "s": 0, "ttl": 1 },
+                                { "label": 16, "exp": 0, "s": 1, "ttl": 1 },
+                            ],
+                        }] },
+                    },
+                    {
+                        "from": "203.0.113.1", "rtt": 5.5, "size": 140, "ttl": 254,
+                        "icmpext": { "obj": [{ "mpls": [{ "label": 24005 }, { "exp": 0 }] }] },
+                    },
+                    { "from": "203.0.113.1", "rtt": 6.0, "size": 140, "ttl": 254, "icmpext": {} },
+                ],
+            }],
+        }))
+        .unwrap();
+
+        let replies = &measurement.result[0].result;
+        assert_eq!(replies[0].mpls_labels(), [24005, 16]);
+        assert_eq!(replies[1].mpls_labels(), [24005]);
+        assert!(replies[2].mpls_labels().is_empty());
+
+        let rows = FlattenedTraceRouteMeasurement::from_traceroute_measurement(&measurement);
+        assert_eq!(rows[0].mpls.as_deref(), Some("24005;16"));
+        let packets = FlattenedHopReply::from_traceroute_measurement(&measurement);
+        assert_eq!(packets[0].mpls.as_deref(), Some("24005;16"));
+    }
+
+    #[test]
+    fn http_target_is_the_uri_host() {
+        assert_eq!(uri_host("http://example.org/"), Some("example.org"));
+        assert_eq!(
+            uri_host("https://user@example.org:8443/a?b#c"),
+            Some("example.org")
+        );
+        assert_eq!(uri_host("http://[2001:db8::1]:8080/"), Some("2001:db8::1"));
+        assert_eq!(uri_host("http://192.0.2.1"), Some("192.0.2.1"));
+        assert_eq!(uri_host("http:///path"), None);
+
+        let failed: AggregatedMeasurement = serde_json::from_value(json!({
+            "type": "http", "uri": "https://example.org/",
+            "result": [{ "dnserr": "non-recoverable failure in name resolution" }],
+            "msm_id": 3001, "timestamp": 100, "prb_id": 10,
+        }))
+        .unwrap();
+        assert_eq!(
+            (failed.dst_addr(), failed.target()),
+            (None, Some("example.org"))
+        );
+    }
 }
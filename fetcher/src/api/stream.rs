@@ -0,0 +1,224 @@
+use futures::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream, connect_async,
+    tungstenite::{self, Message},
+};
+
+use crate::api::fetch_measurement_data::{self, MeasurementData};
+
+pub const STREAM_URL: &str = "wss://atlas-stream.ripe.net/stream/";
+
+#[derive(Debug, Error)]
+pub enum StreamError {
+    #[error("Failed to talk to the RIPE Atlas stream: {0}")]
+    Network(#[source] tungstenite::Error),
+
+    #[error("RIPE Atlas stream reported an error: {0}")]
+    Api(String),
+
+    #[error("Failed to parse expected JSON message: {0}")]
+    ResponseFormat(#[from] serde_json::Error),
+}
+
+/// Something that arrived on the stream.
+#[derive(Debug)]
+pub enum StreamEvent {
+    /// A result of one of the subscribed measurements, parsed like a page of the results endpoint.
+    Results(MeasurementData),
+    /// Confirmation of a subscription, with the measurement id if the server echoed it.
+    Subscribed(Option<u32>),
+    /// The server asked to slow down or dropped results because the client was too slow.
+    Warning(String),
+}
+
+/// A subscription to the result stream. Messages are JSON arrays of the form
+/// `["atlas_result", {...}]`, the same framing the RIPE Atlas stream uses over plain websockets.
+pub struct ResultStream {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl ResultStream {
+    /// Connects to `url` and subscribes to the results of every measurement in `ids`.
+    pub async fn subscribe(url: &str, ids: &[String]) -> Result<Self, StreamError> {
+        let (mut socket, _) = connect_async(url).await.map_err(StreamError::Network)?;
+
+        for id in ids {
+            let msm: Value = match id.parse::<u64>() {
+                Ok(number) => json!(number),
+                Err(_) => json!(id),
+            };
+            let subscribe = json!(["atlas_subscribe", {"stream_type": "result", "msm": msm}]);
+            socket
+                .send(Message::text(subscribe.to_string()))
+                .await
+                .map_err(StreamError::Network)?;
+        }
+
+        Ok(ResultStream { socket })
+    }
+
+    /// Waits for the next message. Returns `None` once the server closed the connection.
+    pub async fn next(&mut self) -> Option<Result<StreamEvent, StreamError>> {
+        loop {
+            let message = match self.socket.next().await? {
+                Ok(message) => message,
+                Err(error) => return Some(Err(StreamError::Network(error))),
+            };
+            let text = match message {
+                Message::Text(text) => text,
+                Message::Ping(payload) => {
+                    if let Err(error) = self.socket.send(Message::Pong(payload)).await {
+                        return Some(Err(StreamError::Network(error)));
+                    }
+                    continue;
+                }
+                Message::Close(_) => return None,
+                _ => continue,
+            };
+            match parse_message(&text) {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => continue,
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+
+    pub async fn close(mut self) {
+        let _ = self.socket.close(None).await;
+    }
+}
+
+fn parse_message(text: &str) -> Result<Option<StreamEvent>, StreamError> {
+    let (kind, payload): (String, Value) = serde_json::from_str(text)?;
+
+    Ok(match kind.as_str() {
+        "atlas_result" => {
+            let msm_id = payload
+                .get("msm_id")
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| "stream".to_string());
+            Some(StreamEvent::Results(fetch_measurement_data::parse_results(
+                &msm_id,
+                vec![payload],
+            )))
+        }
+        "atlas_subscribed" => Some(StreamEvent::Subscribed(
+            payload
+                .get("msm")
+                .and_then(Value::as_u64)
+                .map(|id| id as u32),
+        )),
+        "atlas_error" => return Err(StreamError::Api(payload.to_string())),
+        "atlas_turbulence" | "atlas_overflow" => {
+            Some(StreamEvent::Warning(format!("{}: {}", kind, payload)))
+        }
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::results::AggregatedMeasurement,
+        test_support::{ping_json, stream_server, traceroute_json},
+    };
+
+    #[test]
+    fn parses_framing() {
+        let result = json!(["atlas_result", ping_json(1001, 10, 100, &[Some(1.0)])]);
+        let Ok(Some(StreamEvent::Results(data))) = parse_message(&result.to_string()) else {
+            panic!("expected results");
+        };
+        assert_eq!(data.measurements.len(), 1);
+
+        let broken = json!(["atlas_result", {"type": "ping", "msm_id": 1001}]);
+        let Ok(Some(StreamEvent::Results(data))) = parse_message(&broken.to_string()) else {
+            panic!("expected results");
+        };
+        assert!(data.measurements.is_empty());
+        assert_eq!(data.skipped[0].msm_id, "1001");
+
+        assert!(matches!(
+            parse_message(r#"["atlas_subscribed", {"stream_type": "result", "msm": 1001}]"#),
+            Ok(Some(StreamEvent::Subscribed(Some(1001))))
+        ));
+        assert!(matches!(
+            parse_message(r#"["atlas_turbulence", {}]"#),
+            Ok(Some(StreamEvent::Warning(_)))
+        ));
+        assert!(matches!(
+            parse_message(r#"["atlas_metadata", {}]"#),
+            Ok(None)
+        ));
+        assert!(matches!(
+            parse_message(r#"["atlas_error", "Invalid measurement"]"#),
+            Err(StreamError::Api(_))
+        ));
+        assert!(matches!(
+            parse_message(r#"{"type": "ping"}"#),
+            Err(StreamError::ResponseFormat(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn subscribes_and_receives_results() {
+        let (url, server) = stream_server(
+            2,
+            vec![vec![
+                json!(["atlas_subscribed", {"stream_type": "result", "msm": 1001}]),
+                json!([
+                    "atlas_result",
+                    ping_json(1001, 10, 100, &[Some(1.0), Some(3.0)])
+                ]),
+                json!([
+                    "atlas_result",
+                    traceroute_json(1002, 11, 110, &["192.0.2.1"], true)
+                ]),
+            ]],
+        )
+        .await;
+
+        let ids = ["1001".to_string(), "named".to_string()];
+        let mut results = ResultStream::subscribe(&url, &ids).await.unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = results.next().await {
+            events.push(event.unwrap());
+        }
+
+        let received = server.await.unwrap();
+        let subscriptions: Vec<Value> = received[0]
+            .iter()
+            .map(|text| serde_json::from_str(text).unwrap())
+            .collect();
+        assert_eq!(
+            subscriptions,
+            [
+                json!(["atlas_subscribe", {"stream_type": "result", "msm": 1001}]),
+                json!(["atlas_subscribe", {"stream_type": "result", "msm": "named"}]),
+            ]
+        );
+
+        assert_eq!(events.len(), 3);
+        assert!(matches!(events[0], StreamEvent::Subscribed(Some(1001))));
+        let StreamEvent::Results(ping) = &events[1] else {
+            panic!("expected results");
+        };
+        let [AggregatedMeasurement::Ping(ping)] = ping.measurements.as_slice() else {
+            panic!("expected a ping");
+        };
+        assert_eq!((ping.msm_id, ping.prb_id, ping.timestamp), (1001, 10, 100));
+        assert_eq!(ping.rcvd, 2);
+        let StreamEvent::Results(traceroute) = &events[2] else {
+            panic!("expected results");
+        };
+        let [AggregatedMeasurement::TraceRoute(traceroute)] = traceroute.measurements.as_slice()
+        else {
+            panic!("expected a traceroute");
+        };
+        assert_eq!((traceroute.msm_id, traceroute.prb_id), (1002, 11));
+    }
+}
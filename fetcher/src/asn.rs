@@ -0,0 +1,188 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs,
+    net::IpAddr,
+    path::Path,
+};
+
+/// Offline prefix to origin AS table, looked up by longest prefix match.
+///
+/// Reads the CAIDA pfx2as format (`prefix<TAB>length<TAB>asn`) as well as `prefix/length<TAB>asn`
+/// as written by most MRT dump converters. Multi-origin prefixes (`3303_13030`) and AS sets
+/// (`3303,13030`) keep all of their origins.
+#[derive(Debug, Default)]
+pub struct AsnTable {
+    v4: BTreeMap<u8, HashMap<u32, Vec<u32>>>,
+    v6: BTreeMap<u8, HashMap<u128, Vec<u32>>>,
+}
+
+impl AsnTable {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        let mut table = AsnTable::default();
+
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            table
+                .insert_line(line)
+                .map_err(|e| format!("{}:{}: {}", path.display(), number + 1, e))?;
+        }
+
+        Ok(table)
+    }
+
+    fn insert_line(&mut self, line: &str) -> Result<(), Box<dyn Error>> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (prefix, length, origins) = match fields.as_slice() {
+            [prefix, length, origins] => (*prefix, length.parse::<u8>()?, *origins),
+            [cidr, origins] => {
+                let (prefix, length) = cidr
+                    .split_once('/')
+                    .ok_or_else(|| format!("Prefix without length: {}", cidr))?;
+                (prefix, length.parse::<u8>()?, *origins)
+            }
+            _ => return Err(format!("Expected 2 or 3 fields, found {}", fields.len()).into()),
+        };
+        let origins = origins
+            .split(['_', ','])
+            .map(|asn| asn.trim_start_matches("AS").parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match prefix.parse::<IpAddr>()? {
+            IpAddr::V4(address) if length <= 32 => {
+                let key = u32::from(address) & mask_v4(length);
+                self.v4.entry(length).or_default().insert(key, origins);
+            }
+            IpAddr::V6(address) if length <= 128 => {
+                let key = u128::from(address) & mask_v6(length);
+                self.v6.entry(length).or_default().insert(key, origins);
+            }
+            _ => return Err(format!("Invalid prefix length: {}/{}", prefix, length).into()),
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.v4.values().map(HashMap::len).sum::<usize>()
+            + self.v6.values().map(HashMap::len).sum::<usize>()
+    }
+
+    /// Origin ASes of the most specific prefix covering `address`. Addresses that are not valid
+    /// IPs or not announced, such as private ranges, have none.
+    pub fn lookup(&self, address: &str) -> Option<&[u32]> {
+        match address.parse::<IpAddr>().ok()? {
+            IpAddr::V4(address) => {
+                let address = u32::from(address);
+                self.v4
+                    .iter()
+                    .rev()
+                    .find_map(|(length, prefixes)| prefixes.get(&(address & mask_v4(*length))))
+            }
+            IpAddr::V6(address) => {
+                let address = u128::from(address);
+                self.v6
+                    .iter()
+                    .rev()
+                    .find_map(|(length, prefixes)| prefixes.get(&(address & mask_v6(*length))))
+            }
+        }
+        .map(Vec::as_slice)
+    }
+
+    /// The origins of `address` joined by `_`, the way pfx2as writes multi-origin prefixes.
+    pub fn origin(&self, address: &str) -> Option<String> {
+        self.lookup(address).map(join_asns)
+    }
+}
+
+pub fn join_asns(asns: &[u32]) -> String {
+    asns.iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+fn mask_v4(length: u8) -> u32 {
+    u32::MAX.checked_shl(32 - length as u32).unwrap_or(0)
+}
+
+fn mask_v6(length: u8) -> u128 {
+    u128::MAX.checked_shl(128 - length as u32).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    fn table(name: &str, content: &str) -> Result<AsnTable, Box<dyn Error>> {
+        let path = temp_dir(name).join("pfx2as.txt");
+        fs::write(&path, content)?;
+        AsnTable::load(&path)
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        let table = table(
+            "asn-longest",
+            "# default routes and nested prefixes\n\
+             0.0.0.0\t0\t1\n\
+             10.0.0.0\t8\t2\n\
+             10.1.0.0/16\t3\n\
+             10.1.2.3\t32\t4\n\
+             ::/0\t5\n\
+             2001:db8::\t32\t6\n\
+             2001:db8:1::/48\tAS7\n",
+        )
+        .unwrap();
+        assert_eq!(table.len(), 7);
+
+        assert_eq!(table.lookup("192.0.2.1"), Some(&[1][..]));
+        assert_eq!(table.lookup("10.200.0.1"), Some(&[2][..]));
+        assert_eq!(table.lookup("10.1.2.4"), Some(&[3][..]));
+        assert_eq!(table.lookup("10.1.2.3"), Some(&[4][..]));
+        assert_eq!(table.lookup("2001:db9::1"), Some(&[5][..]));
+        assert_eq!(table.lookup("2001:db8:2::1"), Some(&[6][..]));
+        assert_eq!(table.lookup("2001:db8:1::1"), Some(&[7][..]));
+    }
+
+    #[test]
+    fn unannounced_and_invalid_addresses_have_no_origin() {
+        let table = table("asn-unannounced", "192.0.2.0\t24\t64496\n").unwrap();
+        assert_eq!(table.lookup("198.51.100.1"), None);
+        assert_eq!(table.lookup("2001:db8::1"), None);
+        assert_eq!(table.lookup("not an address"), None);
+    }
+
+    #[test]
+    fn keeps_all_origins_of_multi_origin_prefixes() {
+        let table = table(
+            "asn-origins",
+            "192.0.2.0\t24\t3303_13030\n198.51.100.0/24\t64496,64497\n",
+        )
+        .unwrap();
+        assert_eq!(table.origin("192.0.2.1").as_deref(), Some("3303_13030"));
+        assert_eq!(table.lookup("198.51.100.1"), Some(&[64496, 64497][..]));
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        for line in [
+            "192.0.2.0\t24",
+            "192.0.2.0 24 1 2",
+            "192.0.2.0\t33\t1",
+            "2001:db8::/129\t1",
+            "192.0.2.0/x\t1",
+            "192.0.2.0\t24\tASx",
+            "192.0.2\t24\t1",
+        ] {
+            assert!(table("asn-malformed", line).is_err(), "{}", line);
+        }
+        let error = table("asn-malformed", "192.0.2.0\t24\t1\n\n192.0.2.0\t24\n").unwrap_err();
+        assert!(error.to_string().contains("pfx2as.txt:3:"), "{}", error);
+    }
+}
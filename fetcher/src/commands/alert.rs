@@ -0,0 +1,28 @@
+use clap::Args;
+use reqwest::Client;
+use std::error::Error;
+
+use crate::commands::{AlertRuleArgs, InputArgs};
+
+#[derive(Debug, Args)]
+pub struct AlertArgs {
+    #[clap(flatten)]
+    pub input: InputArgs,
+    #[clap(flatten)]
+    pub alerts: AlertRuleArgs,
+}
+
+pub async fn run(args: AlertArgs, client: &Client) -> Result<(), Box<dyn Error>> {
+    let Some(mut alerting) = args.alerts.load()? else {
+        return Err("--rules is required".into());
+    };
+    let measurements = args.input.load(client).await?;
+    let alerts = alerting.check(client, &measurements).await?;
+
+    println!(
+        "Evaluated {} results, {} alerts",
+        measurements.len(),
+        alerts
+    );
+    Ok(())
+}
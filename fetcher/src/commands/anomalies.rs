@@ -0,0 +1,70 @@
+use clap::Args;
+use reqwest::Client;
+use std::{error::Error, fs, path::PathBuf};
+
+use crate::{
+    analysis::anomaly::{self, Detector},
+    commands::InputArgs,
+    io,
+};
+
+#[derive(Debug, Args)]
+pub struct AnomaliesArgs {
+    #[clap(flatten)]
+    pub input: InputArgs,
+    /// Number of preceding results that form the baseline of a pair
+    #[clap(short, long, default_value_t = 10)]
+    pub window: usize,
+    /// Robust standard deviations a value has to be away from the baseline
+    #[clap(short, long, default_value_t = 3.0)]
+    pub threshold: f32,
+    /// Smallest RTT change in milliseconds that is reported
+    #[clap(long, default_value_t = 5.0)]
+    pub min_delta: f32,
+    /// Loss in percent from which a result counts towards a loss burst
+    #[clap(long, default_value_t = 50.0)]
+    pub loss_threshold: f32,
+    /// Consecutive lossy results that make a loss burst
+    #[clap(long, default_value_t = 2)]
+    pub min_burst: usize,
+    /// Seconds between events of different pairs that still belong to the same incident
+    #[clap(long, default_value_t = 600)]
+    pub gap: i64,
+    /// Output format, csv or json
+    #[clap(short, long, default_value = "csv")]
+    pub output_format: String,
+    #[clap(short = 'd', long, default_value = ".")]
+    pub output_dir: PathBuf,
+    /// Prefix of the written event and incident files
+    #[clap(short = 'n', long, default_value = "anomalies")]
+    pub name: String,
+}
+
+pub async fn run(args: AnomaliesArgs, client: &Client) -> Result<(), Box<dyn Error>> {
+    let measurements = args.input.load(client).await?;
+    let detector = Detector {
+        window: args.window,
+        threshold: args.threshold,
+        min_delta: args.min_delta,
+        loss_threshold: args.loss_threshold,
+        min_burst: args.min_burst,
+    };
+    let events = anomaly::detect(&measurements, &detector);
+    let incidents = anomaly::incidents(&events, args.gap);
+
+    fs::create_dir_all(&args.output_dir)?;
+    let file = |table: &str| {
+        args.output_dir
+            .join(format!("{}_{}.{}", args.name, table, args.output_format))
+    };
+    io::write_table(&file("events"), &args.output_format, &events)?;
+    io::write_table(&file("incidents"), &args.output_format, &incidents)?;
+
+    println!(
+        "Found {} anomalies in {} incidents, wrote {}",
+        events.len(),
+        incidents.len(),
+        args.output_dir.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use clap::Args;
+use reqwest::Client;
+use std::{error::Error, fs, path::PathBuf};
+
+use crate::{analysis::aspath, asn::AsnTable, commands::InputArgs, io};
+
+#[derive(Debug, Args)]
+pub struct AsPathsArgs {
+    #[clap(flatten)]
+    pub input: InputArgs,
+    /// Prefix to ASN table, CAIDA pfx2as or `prefix/length<TAB>asn` lines
+    #[clap(short = 'p', long)]
+    pub pfx2as: PathBuf,
+    /// Output format, csv or json
+    #[clap(short, long, default_value = "csv")]
+    pub output_format: String,
+    #[clap(short = 'd', long, default_value = ".")]
+    pub output_dir: PathBuf,
+    /// Prefix of the written hop, run and pair files
+    #[clap(short = 'n', long, default_value = "aspaths")]
+    pub name: String,
+}
+
+pub async fn run(args: AsPathsArgs, client: &Client) -> Result<(), Box<dyn Error>> {
+    let table = AsnTable::load(&args.pfx2as)?;
+    println!(
+        "Loaded {} prefixes from {}",
+        table.len(),
+        args.pfx2as.display()
+    );
+
+    let measurements = args.input.load(client).await?;
+    let paths = aspath::as_paths(&measurements, &table);
+
+    fs::create_dir_all(&args.output_dir)?;
+    let file = |table: &str| {
+        args.output_dir
+            .join(format!("{}_{}.{}", args.name, table, args.output_format))
+    };
+    io::write_table(&file("hops"), &args.output_format, &paths.hops)?;
+    io::write_table(&file("runs"), &args.output_format, &paths.runs)?;
+    io::write_table(&file("pairs"), &args.output_format, &paths.pairs)?;
+
+    println!(
+        "Mapped {} runs of {} pairs to AS paths, wrote {}",
+        paths.runs.len(),
+        paths.pairs.len(),
+        args.output_dir.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use chrono::{DateTime, Utc};
+use clap::{Args, Subcommand};
+use std::{collections::BTreeMap, error::Error, path::PathBuf};
+
+use crate::{
+    commands::parse_duration,
+    io::cache::{CachedPage, ResultCache},
+};
+
+#[derive(Debug, Args)]
+pub struct CacheArgs {
+    /// Directory of the cache of downloaded result pages, as given to --cache-dir of fetch
+    #[clap(long)]
+    pub cache_dir: PathBuf,
+    #[clap(subcommand)]
+    pub action: CacheAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheAction {
+    /// List the cached time windows of every measurement
+    Inspect {
+        /// Only list these measurements
+        #[clap(long, value_delimiter = ',')]
+        msm_id: Vec<String>,
+        /// List every cached window instead of one line per measurement
+        #[clap(long)]
+        pages: bool,
+    },
+    /// Delete cached windows, all of them unless filtered
+    Purge {
+        /// Only delete windows of these measurements
+        #[clap(long, value_delimiter = ',')]
+        msm_id: Vec<String>,
+        /// Only delete windows downloaded longer ago than this, e.g. 12h or 7d
+        #[clap(long, value_parser = parse_duration)]
+        older_than: Option<i64>,
+    },
+}
+
+pub fn run(args: CacheArgs) -> Result<(), Box<dyn Error>> {
+    let cache = ResultCache::new(args.cache_dir);
+
+    match args.action {
+        CacheAction::Inspect { msm_id, pages } => {
+            let cached = select(&cache, &msm_id)?;
+            if pages {
+                print_pages(&cached);
+            } else {
+                print_measurements(&cached);
+            }
+            println!(
+                "{} windows, {} bytes in {}",
+                cached.len(),
+                cached.iter().map(|page| page.size).sum::<u64>(),
+                cache.dir().display()
+            );
+        }
+        CacheAction::Purge { msm_id, older_than } => {
+            let (removed, freed) = purge(&cache, &msm_id, older_than, Utc::now().timestamp())?;
+            println!("Removed {} windows, freed {} bytes", removed, freed);
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes the selected windows that were downloaded at least `older_than` seconds before
+/// `now`. Returns the number of windows and bytes removed.
+fn purge(
+    cache: &ResultCache,
+    ids: &[String],
+    older_than: Option<i64>,
+    now: i64,
+) -> Result<(usize, u64), Box<dyn Error>> {
+    let mut removed = 0;
+    let mut freed = 0;
+    for page in select(cache, ids)? {
+        if older_than.is_some_and(|age| now - page.fetched < age) {
+            continue;
+        }
+        cache.remove(&page)?;
+        removed += 1;
+        freed += page.size;
+    }
+    Ok((removed, freed))
+}
+
+fn select(cache: &ResultCache, ids: &[String]) -> Result<Vec<CachedPage>, Box<dyn Error>> {
+    if ids.is_empty() {
+        return Ok(cache.all_pages()?);
+    }
+    let mut pages = Vec::new();
+    for id in ids {
+        pages.extend(cache.pages(id)?);
+    }
+    Ok(pages)
+}
+
+fn time(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+fn print_pages(pages: &[CachedPage]) {
+    println!(
+        "{:<10} {:<19} {:<19} {:>10} {:<19}",
+        "msm_id", "start", "stop", "bytes", "fetched"
+    );
+    for page in pages {
+        println!(
+            "{:<10} {:<19} {:<19} {:>10} {:<19}",
+            page.msm_id,
+            time(page.start),
+            time(page.stop),
+            page.size,
+            time(page.fetched)
+        );
+    }
+}
+
+/// One line per measurement with the range its windows span and the number of gaps in it.
+fn print_measurements(pages: &[CachedPage]) {
+    let mut by_id: BTreeMap<&str, Vec<&CachedPage>> = BTreeMap::new();
+    for page in pages {
+        by_id.entry(&page.msm_id).or_default().push(page);
+    }
+
+    println!(
+        "{:<10} {:>7} {:<19} {:<19} {:>4} {:>10} {:<19}",
+        "msm_id", "windows", "from", "until", "gaps", "bytes", "last fetched"
+    );
+    for (id, pages) in by_id {
+        let mut until = pages[0].stop;
+        let mut gaps = 0;
+        for page in &pages[1..] {
+            if page.start > until + 1 {
+                gaps += 1;
+            }
+            until = until.max(page.stop);
+        }
+        println!(
+            "{:<10} {:>7} {:<19} {:<19} {:>4} {:>10} {:<19}",
+            id,
+            pages.len(),
+            time(pages[0].start),
+            time(until),
+            gaps,
+            pages.iter().map(|page| page.size).sum::<u64>(),
+            time(pages.iter().map(|page| page.fetched).max().unwrap_or(0))
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        fs,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use crate::test_support::temp_dir;
+
+    /// Caches a window downloaded at `fetched`.
+    fn write(cache: &ResultCache, msm_id: &str, start: i64, fetched: u64) {
+        cache.write(msm_id, start, start + 99, "[]").unwrap();
+        let path = cache
+            .dir()
+            .join(msm_id)
+            .join(format!("{}-{}.json.gz", start, start + 99));
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(fetched))
+            .unwrap();
+    }
+
+    fn windows(cache: &ResultCache) -> Vec<(String, i64)> {
+        cache
+            .all_pages()
+            .unwrap()
+            .into_iter()
+            .map(|page| (page.msm_id, page.start))
+            .collect()
+    }
+
+    #[test]
+    fn purges_windows_older_than_the_given_age() {
+        let cache = ResultCache::new(temp_dir("purge-older"));
+        write(&cache, "1001", 0, 1_000);
+        write(&cache, "1001", 100, 5_000);
+        write(&cache, "1002", 0, 2_000);
+        write(&cache, "1003", 0, 9_000);
+
+        let (removed, freed) = purge(&cache, &[], Some(4_000), 6_000).unwrap();
+        assert_eq!(removed, 2);
+        assert!(freed > 0);
+        assert_eq!(
+            windows(&cache),
+            [("1001".to_string(), 100), ("1003".to_string(), 0)]
+        );
+
+        // Only the selected measurements, and exactly as old counts.
+        let (removed, _) = purge(&cache, &["1001".to_string()], Some(1_000), 6_000).unwrap();
+        assert_eq!(removed, 1);
+        assert_eq!(windows(&cache), [("1003".to_string(), 0)]);
+
+        let (removed, _) = purge(&cache, &[], None, 0).unwrap();
+        assert_eq!(removed, 1);
+        assert!(windows(&cache).is_empty());
+    }
+}
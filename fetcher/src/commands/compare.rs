@@ -0,0 +1,126 @@
+use clap::Args;
+use reqwest::Client;
+use std::{error::Error, fs, path::PathBuf};
+
+use crate::{
+    analysis::{
+        compare::{self, PairDelta},
+        matrix::Node,
+    },
+    commands::{InputArgs, ProbeArgs, ResultCacheArgs},
+    enrichment::ProbeDirectory,
+    io,
+};
+
+#[derive(Debug, Args)]
+pub struct CompareArgs {
+    /// Measurement ids file (.toml) and/or JSON Lines files of the baseline campaign
+    #[clap(short = 'a', long, num_args = 1.., required = true)]
+    pub baseline: Vec<PathBuf>,
+    /// Measurement ids file (.toml) and/or JSON Lines files of the candidate campaign
+    #[clap(short = 'b', long, num_args = 1.., required = true)]
+    pub candidate: Vec<PathBuf>,
+    #[clap(flatten)]
+    pub probes: ProbeArgs,
+    #[clap(flatten)]
+    pub cache: ResultCacheArgs,
+    /// Significance level of the RTT and loss tests
+    #[clap(long, default_value_t = 0.05)]
+    pub alpha: f32,
+    /// Number of regressions and improvements to print
+    #[clap(long, default_value_t = 10)]
+    pub top: usize,
+    /// Output format, csv or json
+    #[clap(short, long, default_value = "csv")]
+    pub output_format: String,
+    #[clap(short = 'd', long, default_value = ".")]
+    pub output_dir: PathBuf,
+    /// Prefix of the written pair, regression and improvement files
+    #[clap(short = 'n', long, default_value = "compare")]
+    pub name: String,
+}
+
+/// Splits the campaign paths into the measurement ids file to fetch and the files to read.
+fn campaign(paths: &[PathBuf], cache: &ResultCacheArgs) -> Result<InputArgs, Box<dyn Error>> {
+    let (ids, input): (Vec<PathBuf>, Vec<PathBuf>) = paths
+        .iter()
+        .cloned()
+        .partition(|path| path.extension().is_some_and(|e| e == "toml"));
+    if ids.len() > 1 {
+        return Err("Only one measurement ids file per campaign is supported".into());
+    }
+    Ok(InputArgs {
+        measurements: ids.first().map(|path| path.display().to_string()),
+        input,
+        start: None,
+        end: None,
+        cache: cache.clone(),
+    })
+}
+
+pub async fn run(args: CompareArgs, client: &Client) -> Result<(), Box<dyn Error>> {
+    let mut measurements = campaign(&args.baseline, &args.cache)?.load(client).await?;
+    let split = measurements.len();
+    measurements.extend(campaign(&args.candidate, &args.cache)?.load(client).await?);
+    let probes = args
+        .probes
+        .load(client, &measurements, &args.output_dir)
+        .await?;
+
+    let (baseline, candidate) = measurements.split_at(split);
+    let deltas = compare::compare(baseline, candidate, &probes, args.alpha);
+    let regressions: Vec<&PairDelta> = deltas
+        .iter()
+        .filter(|delta| delta.verdict == "regression")
+        .collect();
+    let improvements: Vec<&PairDelta> = deltas
+        .iter()
+        .rev()
+        .filter(|delta| delta.verdict == "improvement")
+        .collect();
+
+    fs::create_dir_all(&args.output_dir)?;
+    let file = |table: &str| {
+        args.output_dir
+            .join(format!("{}_{}.{}", args.name, table, args.output_format))
+    };
+    io::write_table(&file("pairs"), &args.output_format, &deltas)?;
+    io::write_table(&file("regressions"), &args.output_format, &regressions)?;
+    io::write_table(&file("improvements"), &args.output_format, &improvements)?;
+
+    println!(
+        "Compared {} pairs: {} regressions, {} improvements",
+        deltas.len(),
+        regressions.len(),
+        improvements.len()
+    );
+    print_top("Regressions", &regressions, &probes, args.top);
+    print_top("Improvements", &improvements, &probes, args.top);
+    Ok(())
+}
+
+fn print_top(title: &str, deltas: &[&PairDelta], probes: &ProbeDirectory, top: usize) {
+    if deltas.is_empty() {
+        return;
+    }
+    println!("{}:", title);
+    for delta in deltas.iter().take(top) {
+        let format = |value: Option<f32>| {
+            value
+                .map(|v| format!("{:+.2}", v))
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let target = match &delta.target {
+            Node::Probe(prb_id) => probes.label(*prb_id),
+            Node::Address(address) => address.clone(),
+        };
+        println!(
+            "  {} {} -> {}: median {} ms, loss {} %",
+            delta.kind,
+            probes.label(delta.prb_id),
+            target,
+            format(delta.median_delta),
+            format(delta.loss_delta)
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use chrono::Utc;
+use clap::Args;
+use reqwest::Client;
+use std::{
+    error::Error,
+    path::PathBuf,
+    sync::{Arc, PoisonError, RwLock},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    commands::{IncrementalFetch, ProbeArgs, parse_duration},
+    enrichment::ProbeDirectory,
+    io::{self, prometheus::Metrics},
+};
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    #[clap(short, long)]
+    pub measurements: String,
+    #[clap(flatten)]
+    pub probes: ProbeArgs,
+    /// Directory the probe metadata cache is kept in
+    #[clap(short = 'd', long, default_value = ".")]
+    pub output_dir: PathBuf,
+    /// Address the metrics endpoint listens on, scraped at /metrics
+    #[clap(short, long, default_value = "127.0.0.1:9464")]
+    pub listen: String,
+    /// Time between two polls of the API, e.g. 90s, 5m or 1h
+    #[clap(short, long, default_value = "5m", value_parser = parse_duration)]
+    pub every: i64,
+    /// How far back the first poll looks for results
+    #[clap(long, default_value = "1h", value_parser = parse_duration)]
+    pub lookback: i64,
+    /// How far back every later poll looks again for results that probes uploaded late
+    #[clap(long, default_value = "1h", value_parser = parse_duration)]
+    pub overlap: i64,
+}
+
+pub async fn run(args: ExportArgs, client: &Client) -> Result<(), Box<dyn Error>> {
+    let measurement_ids = io::read_measurement_ids_from_file(&args.measurements)?;
+    let listener = TcpListener::bind(&args.listen).await?;
+    println!("Serving metrics at http://{}/metrics", args.listen);
+
+    let page = Arc::new(RwLock::new(String::new()));
+    tokio::spawn(serve(listener, page.clone()));
+
+    let mut fetch =
+        IncrementalFetch::since(&measurement_ids.ids, Utc::now().timestamp() - args.lookback)
+            .overlap(args.overlap);
+    let mut metrics = Metrics::default();
+    // Loaded once, with --enrich every poll adds the probes it has not seen yet. Without
+    // metadata the metrics only lack the country labels, so cache errors do not stop the
+    // exporter.
+    let cache = args.probes.cache(&args.output_dir);
+    let mut probes = ProbeDirectory::load(&cache).unwrap_or_else(|error| {
+        println!("Error: Probe cache {}: {}", cache.display(), error);
+        ProbeDirectory::default()
+    });
+
+    loop {
+        let (data, _) = fetch.fetch(client, &measurement_ids.ids).await;
+        if args.probes.enrich {
+            let failed = probes.resolve(client, &data.measurements).await;
+            if failed > 0 {
+                println!(
+                    "Warning: {} probe metadata lookups failed, retrying next poll",
+                    failed
+                );
+            }
+            if let Err(error) = probes.save(&cache) {
+                println!("Error: Probe cache {}: {}", cache.display(), error);
+            }
+        }
+        metrics.update(&data.measurements);
+        let rendered = metrics.render(&probes);
+        // The page is only ever replaced as a whole, so it is intact even if a writer panicked.
+        *page.write().unwrap_or_else(PoisonError::into_inner) = rendered;
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(args.every as u64)) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+/// Answers every connection with the current metrics, or a 404 for anything but `/metrics`.
+async fn serve(listener: TcpListener, page: Arc<RwLock<String>>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let page = page.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = respond(stream, &page).await {
+                        println!("Error: Metrics request: {}", error);
+                    }
+                });
+            }
+            Err(error) => println!("Error: Metrics listener: {}", error),
+        }
+    }
+}
+
+async fn respond(mut stream: TcpStream, page: &RwLock<String>) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let request_line = String::from_utf8_lossy(&request);
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            page.read().unwrap_or_else(PoisonError::into_inner).clone(),
+        ),
+        _ => (
+            "404 Not Found",
+            "Not found, metrics are at /metrics\n".to_string(),
+        ),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(address: &str, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, address);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn serves_metrics_after_a_writer_panicked() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let page = Arc::new(RwLock::new("ripe_atlas_results_total 1\n".to_string()));
+        let writer = page.clone();
+        let _ = std::thread::spawn(move || {
+            let _page = writer.write().unwrap();
+            panic!("poisons the lock");
+        })
+        .join();
+        assert!(page.is_poisoned());
+        tokio::spawn(serve(listener, page));
+
+        let response = get(&address, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.ends_with("\r\n\r\nripe_atlas_results_total 1\n"));
+        let response = get(&address, "/").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
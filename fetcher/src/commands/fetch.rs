@@ -0,0 +1,83 @@
+use clap::Args;
+use reqwest::Client;
+use std::{error::Error, fs, path::PathBuf, sync::Arc};
+
+use crate::{analysis, commands, io};
+
+// clap leaves the group of a struct with flattened fields empty, but needs its members to tell
+// whether the top level fetch arguments were given.
+#[derive(Debug, Args)]
+#[group(id = "FetchArgs", multiple = true, args = ["measurements"])]
+pub struct FetchArgs {
+    #[clap(short, long)]
+    pub measurements: String,
+    #[clap(flatten)]
+    pub output: commands::OutputArgs,
+    #[clap(flatten)]
+    pub cache: commands::ResultCacheArgs,
+    /// File for results that could not be parsed, defaults to {campaign}_quarantine.jsonl in the
+    /// output directory
+    #[clap(long)]
+    pub quarantine: Option<PathBuf>,
+    /// Append country, ASN, coordinates, anchor flag and fqdn of source probes and targets, the
+    /// distance between them and the RTT inflation over fibre, and write a per-pair summary
+    #[clap(long)]
+    pub enrich: bool,
+    /// Probe metadata cache used by --enrich, defaults to probe_metadata.json in the output
+    /// directory
+    #[clap(long)]
+    pub probe_cache: Option<PathBuf>,
+}
+
+pub async fn run(args: FetchArgs, client: &Client) -> Result<(), Box<dyn Error>> {
+    args.output.check()?;
+    let measurement_ids = io::read_measurement_ids_from_file(&args.measurements)?;
+
+    let campaign = args.output.campaign(&args.measurements);
+    let quarantine_path = args.quarantine.clone().unwrap_or_else(|| {
+        args.output
+            .output_dir
+            .join(format!("{}_quarantine.jsonl", campaign))
+    });
+    let layout = args.output.layout(campaign);
+
+    let data =
+        commands::fetch_measurements(client, &measurement_ids.ids, None, None, &args.cache).await;
+    let measurements = data.measurements;
+
+    let probes = if args.enrich {
+        let probe_args = commands::ProbeArgs {
+            enrich: true,
+            probe_cache: args.probe_cache.clone(),
+        };
+        let directory = probe_args
+            .load(client, &measurements, layout.directory())
+            .await?;
+
+        fs::create_dir_all(layout.directory())?;
+        let summary =
+            io::layout::with_extension(&layout.single_file("distances", &measurements), "csv");
+        io::write_csv(
+            &summary,
+            &analysis::distance::pair_distances(&measurements, &directory),
+        )?;
+
+        Some(Arc::new(directory))
+    } else {
+        None
+    };
+
+    let output = args.output.saver(layout, probes, false)?;
+    output.save_by_type(&measurements)?;
+
+    if !data.skipped.is_empty() {
+        io::write_quarantine(&quarantine_path, &data.skipped, false)?;
+        println!(
+            "Wrote {} unparseable results to {}",
+            data.skipped.len(),
+            quarantine_path.display()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use clap::Args;
+use reqwest::Client;
+use std::{error::Error, fs, path::PathBuf};
+
+use crate::{
+    analysis::matrix::{self, LatencyMatrix},
+    commands::{InputArgs, ProbeArgs},
+};
+
+#[derive(Debug, Args)]
+pub struct MatrixArgs {
+    #[clap(flatten)]
+    pub input: InputArgs,
+    #[clap(flatten)]
+    pub probes: ProbeArgs,
+    /// Measurement type to aggregate: ping, http or traceroute
+    #[clap(short = 't', long = "type", default_value = "ping")]
+    pub kind: String,
+    #[clap(short = 'd', long, default_value = ".")]
+    pub output_dir: PathBuf,
+    /// Prefix of the written files, followed by the measurement type
+    #[clap(short = 'n', long, default_value = "matrix")]
+    pub name: String,
+}
+
+pub async fn run(args: MatrixArgs, client: &Client) -> Result<(), Box<dyn Error>> {
+    if !["ping", "http", "traceroute"].contains(&args.kind.as_str()) {
+        return Err(format!("Unsupported measurement type: {}", args.kind).into());
+    }
+
+    let measurements = args.input.load(client).await?;
+    let probes = args
+        .probes
+        .load(client, &measurements, &args.output_dir)
+        .await?;
+
+    let mut matrix = matrix::latency_matrix(&args.kind, &measurements, &probes);
+    matrix.start = args.input.start;
+    matrix.end = args.input.end;
+
+    fs::create_dir_all(&args.output_dir)?;
+    let stem = format!("{}_{}", args.name, args.kind);
+    fs::write(
+        args.output_dir.join(format!("{}.json", stem)),
+        serde_json::to_string_pretty(&matrix)?,
+    )?;
+    write_grid(&args, &stem, "median", &matrix, &matrix.median)?;
+    write_grid(&args, &stem, "p95", &matrix, &matrix.p95)?;
+    write_grid(&args, &stem, "loss", &matrix, &matrix.loss)?;
+
+    println!(
+        "Wrote {}x{} {} matrix to {}",
+        matrix.labels.len(),
+        matrix.labels.len(),
+        args.kind,
+        args.output_dir.display()
+    );
+    Ok(())
+}
+
+/// Writes one statistic as a grid with the source labels in the first column and the target
+/// labels as header, the layout heatmap tools expect.
+fn write_grid(
+    args: &MatrixArgs,
+    stem: &str,
+    statistic: &str,
+    matrix: &LatencyMatrix,
+    values: &[Vec<Option<f32>>],
+) -> Result<(), Box<dyn Error>> {
+    let path = args.output_dir.join(format!("{}_{}.csv", stem, statistic));
+    let mut writer = csv::Writer::from_path(path)?;
+
+    writer
+        .write_record(std::iter::once("source").chain(matrix.labels.iter().map(String::as_str)))?;
+    for (label, row) in matrix.labels.iter().zip(values) {
+        let cells = row
+            .iter()
+            .map(|value| value.map(|v| v.to_string()).unwrap_or_default());
+        writer.write_record(std::iter::once(label.clone()).chain(cells))?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
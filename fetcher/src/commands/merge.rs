@@ -0,0 +1,39 @@
+use clap::Args;
+use reqwest::Client;
+use std::{error::Error, fs, path::PathBuf};
+
+use crate::{
+    analysis::merge,
+    commands::{InputArgs, parse_duration},
+    io,
+};
+
+#[derive(Debug, Args)]
+pub struct MergeArgs {
+    #[clap(flatten)]
+    pub input: InputArgs,
+    /// Width of the time buckets results are aligned to, e.g. 300, 90s, 5m or 1h
+    #[clap(short, long, default_value = "5m", value_parser = parse_duration)]
+    pub bucket: i64,
+    /// Output format, csv or json
+    #[clap(short, long, default_value = "csv")]
+    pub output_format: String,
+    #[clap(short = 'd', long, default_value = ".")]
+    pub output_dir: PathBuf,
+    #[clap(short = 'n', long, default_value = "merged")]
+    pub name: String,
+}
+
+pub async fn run(args: MergeArgs, client: &Client) -> Result<(), Box<dyn Error>> {
+    let measurements = args.input.load(client).await?;
+    let rows = merge::merge(&measurements, args.bucket);
+
+    fs::create_dir_all(&args.output_dir)?;
+    let path = args
+        .output_dir
+        .join(format!("{}.{}", args.name, args.output_format));
+    io::write_table(&path, &args.output_format, &rows)?;
+
+    println!("Wrote {} rows to {}", rows.len(), path.display());
+    Ok(())
+}
@@ -0,0 +1,679 @@
+use chrono::DateTime;
+use clap::Args;
+use futures::future::join_all;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    analysis::alert::{AlertRules, Evaluator},
+    api::{
+        self,
+        fetch_measurement_data::{MeasurementData, ResultSource, SkippedResult},
+        results::AggregatedMeasurement,
+    },
+    enrichment::ProbeDirectory,
+    io::{
+        self, MeasurementSaver, alert_sink::AlertSink, cache::ResultCache, csv_saver::CsvSaver,
+        jsonl_saver::JsonLinesSaver, layout::OutputLayout, parquet_saver::ParquetSaver,
+        sqlite_saver::SqliteSaver,
+    },
+};
+
+pub mod alert;
+pub mod anomalies;
+pub mod aspaths;
+pub mod cache;
+pub mod compare;
+pub mod export;
+pub mod fetch;
+pub mod matrix;
+pub mod merge;
+pub mod paths;
+pub mod report;
+pub mod stream;
+pub mod summarize;
+pub mod watch;
+
+/// Where an analysis command takes its results from: JSON Lines files written by
+/// `--output-format jsonl`, or the API for the ids in a measurement ids file.
+#[derive(Debug, Args)]
+pub struct InputArgs {
+    /// Measurement ids file to fetch results for
+    #[clap(short, long, required_unless_present = "input")]
+    pub measurements: Option<String>,
+    /// JSON Lines files with previously fetched results
+    #[clap(short, long, num_args = 1..)]
+    pub input: Vec<PathBuf>,
+    /// Only use results at or after this time, unix seconds or RFC 3339
+    #[clap(long, value_parser = parse_time)]
+    pub start: Option<i64>,
+    /// Only use results at or before this time, unix seconds or RFC 3339
+    #[clap(long, value_parser = parse_time)]
+    pub end: Option<i64>,
+    #[clap(flatten)]
+    pub cache: ResultCacheArgs,
+}
+
+/// Whether downloaded results are cached on disk and reused by later runs. Nothing is cached
+/// unless a cache directory is given.
+#[derive(Debug, Clone, Args)]
+pub struct ResultCacheArgs {
+    /// Cache downloaded result pages in this directory and reuse them in later runs, by default
+    /// every run downloads all results again
+    #[clap(long)]
+    pub cache_dir: Option<PathBuf>,
+    /// Only use results cached in --cache-dir and never contact the API
+    #[clap(long, requires = "cache_dir")]
+    pub offline: bool,
+    /// Results newer than this are downloaded every time instead of cached, as probes that were
+    /// offline upload theirs late
+    #[clap(long, default_value = "1d", value_parser = parse_duration)]
+    pub settle_time: i64,
+}
+
+/// Where and in which format fetched results are written.
+#[derive(Debug, Args)]
+pub struct OutputArgs {
+    #[clap(short, long, default_value = "csv")]
+    pub output_format: String,
+    #[clap(short = 'd', long, default_value = ".")]
+    pub output_dir: PathBuf,
+    /// File name template, supports {campaign}, {start}, {end}, {type} and {msm_id}
+    #[clap(short = 'n', long, default_value = "{type}")]
+    pub file_name: String,
+    /// Campaign name used for {campaign}, defaults to the measurement ids file name
+    #[clap(short, long)]
+    pub campaign: Option<String>,
+    /// Write one file per measurement id instead of one per measurement type
+    #[clap(long)]
+    pub split_by_id: bool,
+    /// Also write one row per ping reply into a separate CSV file
+    #[clap(long)]
+    pub ping_packets: bool,
+    /// Also write one row per traceroute reply into a separate CSV file
+    #[clap(long)]
+    pub hop_replies: bool,
+    /// Write CSV files with the columns of earlier versions and their placeholders like "timeout"
+    /// and 0.0 for missing values
+    #[clap(long)]
+    pub legacy_sentinels: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ProbeArgs {
+    /// Fetch metadata for probes and targets missing from the probe cache
+    #[clap(long)]
+    pub enrich: bool,
+    /// Probe metadata cache, defaults to probe_metadata.json in the output directory
+    #[clap(long)]
+    pub probe_cache: Option<PathBuf>,
+}
+
+/// Alert rules checked against every result, and where the alerts go.
+#[derive(Debug, Args)]
+pub struct AlertRuleArgs {
+    /// Alert rule file, a TOML list of [[rule]] tables
+    #[clap(long)]
+    pub rules: Option<PathBuf>,
+    /// Also append alerts as JSON Lines to this file
+    #[clap(long)]
+    pub alert_file: Option<PathBuf>,
+    /// Also post every alert as JSON to this URL
+    #[clap(long)]
+    pub webhook: Option<String>,
+    /// Keeps which alerts are firing across runs, so they are neither repeated nor lost
+    #[clap(long)]
+    pub alert_state: Option<PathBuf>,
+}
+
+/// Loaded alert rules with their state and sink.
+pub struct Alerting {
+    evaluator: Evaluator,
+    sink: AlertSink,
+    state: Option<PathBuf>,
+}
+
+pub fn parse_time(value: &str) -> Result<i64, String> {
+    if let Ok(seconds) = value.parse::<i64>() {
+        return Ok(seconds);
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.timestamp())
+        .map_err(|error| format!("expected unix seconds or an RFC 3339 time: {}", error))
+}
+
+/// Parses a duration in seconds, either plain or with an `s`, `m`, `h` or `d` suffix.
+pub fn parse_duration(value: &str) -> Result<i64, String> {
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+    let factor = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        _ => return Err(format!("unknown unit {:?}, expected s, m, h or d", unit)),
+    };
+    match number.parse::<i64>() {
+        Ok(number) if number > 0 => Ok(number * factor),
+        _ => Err(format!(
+            "expected a positive duration such as 90s or 5m: {}",
+            value
+        )),
+    }
+}
+
+/// Fetches the results of every measurement between `start` and `end`, printing how many
+/// results were parsed and skipped for each. Measurements that fail entirely are reported and
+/// left out.
+pub async fn fetch_measurements(
+    client: &Client,
+    ids: &[String],
+    start: Option<i64>,
+    end: Option<i64>,
+    cache: &ResultCacheArgs,
+) -> MeasurementData {
+    let result_cache = cache
+        .cache_dir
+        .clone()
+        .map(|dir| ResultCache::new(dir).settle_time(cache.settle_time));
+    let source = match &result_cache {
+        Some(result_cache) if cache.offline => ResultSource::Offline(result_cache),
+        Some(result_cache) => ResultSource::Cache(result_cache),
+        None => ResultSource::Api,
+    };
+    let futures = ids.iter().map(|id| {
+        api::fetch_measurement_data::get_measurement_data(client, id, start, end, source)
+    });
+    let results = join_all(futures).await;
+
+    let mut all = MeasurementData::default();
+    for (id, result) in ids.iter().zip(results) {
+        match result {
+            Ok(data) => {
+                report(id, &data);
+                all.measurements.extend(data.measurements);
+                all.skipped.extend(data.skipped);
+            }
+            Err(error) => println!("Error: {}", error),
+        }
+    }
+    all
+}
+
+/// Remembers the newest result timestamp of every probe of every measurement, so later fetches
+/// only return results that are new. Probes upload results late when they were offline or
+/// the controller was busy, so every fetch asks for an overlap window before the newest result
+/// seen and drops what it already returned.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct IncrementalFetch {
+    progress: BTreeMap<String, Progress>,
+    #[serde(skip)]
+    overlap: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Progress {
+    /// Results before this time are not wanted.
+    start: Option<i64>,
+    /// Newest result timestamp per probe.
+    probes: BTreeMap<u32, i64>,
+}
+
+impl Progress {
+    /// The overlap window before the newest result seen, but not before `start`.
+    fn next_start(&self, overlap: i64) -> Option<i64> {
+        let newest = self
+            .probes
+            .values()
+            .max()
+            .map(|timestamp| timestamp - overlap);
+        newest.max(self.start)
+    }
+
+    /// Whether a result of `prb_id` at `timestamp` has not been returned yet.
+    fn is_new(&self, prb_id: u32, timestamp: i64) -> bool {
+        self.start.is_none_or(|start| timestamp >= start)
+            && self
+                .probes
+                .get(&prb_id)
+                .is_none_or(|last| timestamp > *last)
+    }
+
+    fn record(&mut self, prb_id: u32, timestamp: i64) {
+        let last = self.probes.entry(prb_id).or_insert(timestamp);
+        *last = (*last).max(timestamp);
+    }
+}
+
+/// Probe and time of an unparseable result, if it has them.
+fn skipped_key(skipped: &SkippedResult) -> Option<(u32, i64)> {
+    let prb_id = skipped.raw["prb_id"].as_u64()?;
+    Some((prb_id as u32, skipped.raw["timestamp"].as_i64()?))
+}
+
+impl IncrementalFetch {
+    /// Reads the state file, an absent file starts from the first result.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(IncrementalFetch::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Starts every measurement at `start` instead of at its first result.
+    pub fn since(ids: &[String], start: i64) -> Self {
+        IncrementalFetch {
+            progress: ids
+                .iter()
+                .map(|id| {
+                    let progress = Progress {
+                        start: Some(start),
+                        probes: BTreeMap::new(),
+                    };
+                    (id.clone(), progress)
+                })
+                .collect(),
+            overlap: 0,
+        }
+    }
+
+    /// How far before the newest result seen every fetch starts, in seconds. Results that
+    /// probes upload later than that are missed.
+    pub fn overlap(mut self, val: i64) -> Self {
+        self.overlap = val;
+        self
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Fetches the results of every measurement that were not returned before. Also returns the
+    /// ids whose fetch failed; they are retried from the same point next time.
+    pub async fn fetch(
+        &mut self,
+        client: &Client,
+        ids: &[String],
+    ) -> (MeasurementData, Vec<String>) {
+        let polls = join_all(ids.iter().map(|id| {
+            let start = self
+                .progress
+                .get(id)
+                .and_then(|progress| progress.next_start(self.overlap));
+            api::fetch_measurement_data::get_measurement_data_since(client, id, start)
+        }))
+        .await;
+
+        let mut data = MeasurementData::default();
+        let mut failed = Vec::new();
+        for (id, poll) in ids.iter().zip(polls) {
+            let poll = match poll {
+                Ok(poll) => self.retain_new(id, poll),
+                Err(error) => {
+                    println!("Error: {}", error);
+                    failed.push(id.clone());
+                    continue;
+                }
+            };
+            println!(
+                "Measurement {}: {} new results, skipped {}",
+                id,
+                poll.measurements.len(),
+                poll.skipped.len()
+            );
+            data.measurements.extend(poll.measurements);
+            data.skipped.extend(poll.skipped);
+        }
+        (data, failed)
+    }
+
+    /// Drops the results of `id` that were returned before and records the rest. Unparseable
+    /// results count too, so they are quarantined only once; those without probe or timestamp
+    /// are always kept.
+    fn retain_new(&mut self, id: &str, mut poll: MeasurementData) -> MeasurementData {
+        let progress = self.progress.entry(id.to_string()).or_default();
+        poll.measurements
+            .retain(|m| progress.is_new(m.prb_id(), m.timestamp() as i64));
+        poll.skipped.retain(|skipped| {
+            skipped_key(skipped)
+                .is_none_or(|(prb_id, timestamp)| progress.is_new(prb_id, timestamp))
+        });
+
+        for measurement in &poll.measurements {
+            progress.record(measurement.prb_id(), measurement.timestamp() as i64);
+        }
+        for (prb_id, timestamp) in poll.skipped.iter().filter_map(skipped_key) {
+            progress.record(prb_id, timestamp);
+        }
+        poll
+    }
+}
+
+fn report(source: &str, data: &MeasurementData) {
+    println!(
+        "Measurement {}: parsed {} results, skipped {}",
+        source,
+        data.measurements.len(),
+        data.skipped.len()
+    );
+}
+
+impl InputArgs {
+    /// Loads the results and drops those outside of `--start` and `--end`.
+    pub async fn load(
+        &self,
+        client: &Client,
+    ) -> Result<Vec<AggregatedMeasurement>, Box<dyn Error>> {
+        let mut data = match &self.measurements {
+            Some(path) => {
+                let ids = io::read_measurement_ids_from_file(path)?;
+                fetch_measurements(client, &ids.ids, self.start, self.end, &self.cache).await
+            }
+            None => MeasurementData::default(),
+        };
+        for path in &self.input {
+            let file = io::read_jsonl(path)?;
+            report(&path.display().to_string(), &file);
+            data.measurements.extend(file.measurements);
+            data.skipped.extend(file.skipped);
+        }
+        warn_skipped(&data.skipped);
+
+        Ok(data
+            .measurements
+            .into_iter()
+            .filter(|m| self.contains(m.timestamp() as i64))
+            .collect())
+    }
+
+    pub fn contains(&self, timestamp: i64) -> bool {
+        self.start.is_none_or(|start| timestamp >= start)
+            && self.end.is_none_or(|end| timestamp <= end)
+    }
+}
+
+fn warn_skipped(skipped: &[SkippedResult]) {
+    if !skipped.is_empty() {
+        println!("Skipped {} results that could not be parsed", skipped.len());
+    }
+}
+
+impl OutputArgs {
+    pub fn campaign(&self, measurements: &str) -> String {
+        self.campaign.clone().unwrap_or_else(|| {
+            Path::new(measurements)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "campaign".to_string())
+        })
+    }
+
+    pub fn layout(&self, campaign: String) -> OutputLayout {
+        OutputLayout::new(
+            self.output_dir.clone(),
+            self.file_name.clone(),
+            campaign,
+            self.split_by_id,
+        )
+    }
+
+    /// Rejects flags the output format does not support, so that is known before fetching.
+    pub fn check(&self) -> Result<(), Box<dyn Error>> {
+        // SQLite always stores packets and replies in tables of their own, JSONL keeps them in
+        // the raw results.
+        if (self.ping_packets || self.hop_replies) && self.output_format != "csv" {
+            return Err(format!(
+                "--ping-packets and --hop-replies are only supported with csv output, not {}",
+                self.output_format
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Builds the saver for `--output-format`. With `append`, later saves add to the files
+    /// instead of replacing them; keep `{start}` and `{end}` out of the file name then, or every
+    /// batch ends up in a file of its own.
+    pub fn saver(
+        &self,
+        layout: OutputLayout,
+        probes: Option<Arc<ProbeDirectory>>,
+        append: bool,
+    ) -> Result<Box<dyn MeasurementSaver>, Box<dyn Error>> {
+        self.check()?;
+        Ok(match self.output_format.as_str() {
+            "csv" => Box::new(
+                CsvSaver::new(layout)
+                    .ping_packets(self.ping_packets)
+                    .hop_replies(self.hop_replies)
+                    .legacy_sentinels(self.legacy_sentinels)
+                    .append(append)
+                    .probe_metadata(probes),
+            ),
+            "jsonl" => Box::new(
+                JsonLinesSaver::new(layout)
+                    .append(append)
+                    .probe_metadata(probes),
+            ),
+            "sqlite" => Box::new(SqliteSaver::new(layout).probe_metadata(probes)),
+            "parquet" => Box::new(
+                ParquetSaver::new(layout)
+                    .append(append)
+                    .probe_metadata(probes),
+            ),
+            _ => return Err(format!("Unsupported output format: {}", self.output_format).into()),
+        })
+    }
+}
+
+impl ProbeArgs {
+    /// `--probe-cache`, or probe_metadata.json in `default_dir`.
+    pub fn cache(&self, default_dir: &Path) -> PathBuf {
+        self.probe_cache
+            .clone()
+            .unwrap_or_else(|| default_dir.join("probe_metadata.json"))
+    }
+
+    /// Loads the probe cache and, with `--enrich`, fetches whatever is missing and updates it.
+    pub async fn load(
+        &self,
+        client: &Client,
+        measurements: &[AggregatedMeasurement],
+        default_dir: &Path,
+    ) -> Result<ProbeDirectory, Box<dyn Error>> {
+        let cache = self.cache(default_dir);
+        let mut directory = ProbeDirectory::load(&cache)?;
+        if self.enrich {
+            let failed = directory.resolve(client, measurements).await;
+            if failed > 0 {
+                println!(
+                    "Warning: {} probe metadata lookups failed, those columns stay empty",
+                    failed
+                );
+            }
+            directory.save(&cache)?;
+        }
+        Ok(directory)
+    }
+}
+
+impl AlertRuleArgs {
+    /// `None` if no rule file was given.
+    pub fn load(&self) -> Result<Option<Alerting>, Box<dyn Error>> {
+        let Some(rules) = &self.rules else {
+            return Ok(None);
+        };
+        let mut evaluator = Evaluator::new(AlertRules::load(rules)?);
+        if let Some(state) = &self.alert_state {
+            evaluator.load_state(state)?;
+        }
+        Ok(Some(Alerting {
+            evaluator,
+            sink: AlertSink::new()
+                .file(self.alert_file.clone())
+                .webhook(self.webhook.clone()),
+            state: self.alert_state.clone(),
+        }))
+    }
+}
+
+impl Alerting {
+    /// Evaluates new results, emits the resulting alerts and saves the state. Returns the
+    /// number of alerts. Alerts that were not delivered are retracted, so they are raised again
+    /// with the next results. Watch and stream only report errors, the alerts were printed
+    /// already and the state is saved again with the next results.
+    pub async fn check(
+        &mut self,
+        client: &Client,
+        measurements: &[AggregatedMeasurement],
+    ) -> Result<usize, Box<dyn Error>> {
+        let alerts = self.evaluator.evaluate(measurements);
+        let undelivered = match self.sink.emit(client, &alerts).await {
+            Ok(undelivered) => undelivered,
+            Err(error) => {
+                for alert in &alerts {
+                    self.evaluator.retract(alert);
+                }
+                return Err(error);
+            }
+        };
+        for alert in undelivered {
+            self.evaluator.retract(alert);
+        }
+        if let Some(state) = &self.state {
+            self.evaluator.save_state(state)?;
+        }
+        Ok(alerts.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::{
+        api::fetch_measurement_data::parse_results,
+        test_support::{ping, ping_json, temp_dir},
+    };
+
+    fn poll(results: &[(u32, usize)]) -> MeasurementData {
+        parse_results(
+            "1001",
+            results
+                .iter()
+                .map(|(prb_id, timestamp)| ping_json(1001, *prb_id, *timestamp, &[Some(1.0)]))
+                .collect(),
+        )
+    }
+
+    fn keys(data: &MeasurementData) -> Vec<(u32, usize)> {
+        data.measurements
+            .iter()
+            .map(|m| (m.prb_id(), m.timestamp()))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_results_that_probes_upload_late() {
+        let mut fetch = IncrementalFetch::default().overlap(600);
+        let id = "1001";
+        assert_eq!(fetch.progress.get(id).and_then(|p| p.next_start(600)), None);
+
+        let first = fetch.retain_new(id, poll(&[(10, 1000), (11, 1000), (10, 1300)]));
+        assert_eq!(keys(&first), [(10, 1000), (11, 1000), (10, 1300)]);
+        assert_eq!(fetch.progress[id].next_start(600), Some(700));
+
+        // Probe 12 uploads results older than the newest one seen, the overlap returns the
+        // results of the others again.
+        let second = fetch.retain_new(
+            id,
+            poll(&[(10, 1000), (10, 1300), (11, 1000), (12, 1100), (11, 1400)]),
+        );
+        assert_eq!(keys(&second), [(12, 1100), (11, 1400)]);
+
+        // Results of a probe arrive out of order within one poll.
+        let third = fetch.retain_new(id, poll(&[(12, 1500), (12, 1200)]));
+        assert_eq!(keys(&third), [(12, 1500), (12, 1200)]);
+        assert_eq!(fetch.progress[id].probes[&12], 1500);
+    }
+
+    #[test]
+    fn quarantines_unparseable_results_once() {
+        let mut fetch = IncrementalFetch::default();
+        let broken = || {
+            parse_results(
+                "1001",
+                vec![
+                    json!({"type": "ping", "prb_id": 10, "timestamp": 1000}),
+                    json!({"type": "ping"}),
+                ],
+            )
+        };
+        assert_eq!(fetch.retain_new("1001", broken()).skipped.len(), 2);
+        // The one without probe and timestamp cannot be told apart from a new one.
+        assert_eq!(fetch.retain_new("1001", broken()).skipped.len(), 1);
+    }
+
+    #[test]
+    fn since_ignores_earlier_results_and_survives_a_restart() {
+        let path = crate::test_support::temp_dir("incremental").join("watch.json");
+        let mut fetch = IncrementalFetch::since(&["1001".to_string()], 1000).overlap(600);
+        assert_eq!(fetch.progress["1001"].next_start(600), Some(1000));
+        let first = fetch.retain_new("1001", poll(&[(10, 900), (10, 1000), (11, 1200)]));
+        assert_eq!(keys(&first), [(10, 1000), (11, 1200)]);
+        // The overlap does not reach before the start.
+        assert_eq!(fetch.progress["1001"].next_start(600), Some(1000));
+        fetch.save(&path).unwrap();
+
+        let mut restarted = IncrementalFetch::load(&path).unwrap().overlap(600);
+        let second = restarted.retain_new("1001", poll(&[(10, 1000), (11, 1200), (10, 1300)]));
+        assert_eq!(keys(&second), [(10, 1300)]);
+    }
+
+    #[tokio::test]
+    async fn undelivered_alerts_are_raised_again() {
+        // Bound and dropped again, so nothing listens on the port.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let webhook = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+        let dir = temp_dir("alerting-undelivered");
+        let rules = dir.join("rules.toml");
+        fs::write(
+            &rules,
+            "[[rule]]\nname = \"slow\"\nmetric = \"rtt\"\nop = \">\"\nvalue = 50\n",
+        )
+        .unwrap();
+        let args = AlertRuleArgs {
+            rules: Some(rules),
+            alert_file: None,
+            webhook: Some(webhook),
+            alert_state: Some(dir.join("state.json")),
+        };
+
+        let client = Client::new();
+        let mut alerting = args.load().unwrap().unwrap();
+        let first = [ping(1001, 10, 100, &[Some(60.0)])];
+        assert_eq!(alerting.check(&client, &first).await.unwrap(), 1);
+
+        // The saved state does not claim the alert was raised, a restart raises it again.
+        let mut restarted = args.load().unwrap().unwrap();
+        assert_eq!(restarted.check(&client, &first).await.unwrap(), 0);
+        let second = [ping(1001, 10, 200, &[Some(70.0)])];
+        assert_eq!(restarted.check(&client, &second).await.unwrap(), 1);
+    }
+}
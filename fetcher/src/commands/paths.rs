@@ -0,0 +1,43 @@
+use clap::Args;
+use reqwest::Client;
+use std::{error::Error, fs, path::PathBuf};
+
+use crate::{analysis::paths, commands::InputArgs, io};
+
+#[derive(Debug, Args)]
+pub struct PathsArgs {
+    #[clap(flatten)]
+    pub input: InputArgs,
+    /// Output format, csv or json
+    #[clap(short, long, default_value = "csv")]
+    pub output_format: String,
+    #[clap(short = 'd', long, default_value = ".")]
+    pub output_dir: PathBuf,
+    /// Prefix of the written change log and stability files
+    #[clap(short = 'n', long, default_value = "paths")]
+    pub name: String,
+}
+
+pub async fn run(args: PathsArgs, client: &Client) -> Result<(), Box<dyn Error>> {
+    let measurements = args.input.load(client).await?;
+    let (changes, stability) = paths::path_changes(&measurements);
+
+    fs::create_dir_all(&args.output_dir)?;
+    let changes_path = args
+        .output_dir
+        .join(format!("{}_changes.{}", args.name, args.output_format));
+    io::write_table(&changes_path, &args.output_format, &changes)?;
+    let stability_path = args
+        .output_dir
+        .join(format!("{}_stability.{}", args.name, args.output_format));
+    io::write_table(&stability_path, &args.output_format, &stability)?;
+
+    println!(
+        "Detected {} path changes over {} pairs, wrote {} and {}",
+        changes.len(),
+        stability.len(),
+        changes_path.display(),
+        stability_path.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use clap::Args;
+use futures::future::join_all;
+use reqwest::Client;
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    api,
+    commands::{InputArgs, ProbeArgs},
+    io, report,
+};
+
+#[derive(Debug, Args)]
+pub struct ReportArgs {
+    #[clap(flatten)]
+    pub input: InputArgs,
+    #[clap(flatten)]
+    pub probes: ProbeArgs,
+    #[clap(short = 'd', long, default_value = "report")]
+    pub output_dir: PathBuf,
+    /// Also write a self-contained report.html with configuration, probes, statistics and charts
+    #[clap(long)]
+    pub html: bool,
+    /// Measurement ids file of the campaign for the HTML report, defaults to --measurements
+    #[clap(long)]
+    pub ids: Option<String>,
+    /// Executor configuration of the campaign to include in the HTML report
+    #[clap(long)]
+    pub config: Option<PathBuf>,
+}
+
+pub async fn run(args: ReportArgs, client: &Client) -> Result<(), Box<dyn Error>> {
+    let measurements = args.input.load(client).await?;
+    let probes = args
+        .probes
+        .load(client, &measurements, &args.output_dir)
+        .await?;
+
+    let charts = report::standard_charts(&measurements, &probes);
+    for chart in &charts {
+        let path = args.output_dir.join(&chart.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, &chart.svg)?;
+    }
+
+    println!(
+        "Wrote {} charts to {}",
+        charts.len(),
+        args.output_dir.display()
+    );
+
+    if args.html {
+        let ids_file = args.ids.as_ref().or(args.input.measurements.as_ref());
+        let definitions = match ids_file {
+            Some(path) => {
+                let ids = io::read_measurement_ids_from_file(path)?;
+                let results = join_all(ids.ids.iter().map(|id| {
+                    api::fetch_measurement_definition::get_measurement_definition(client, id)
+                }))
+                .await;
+                results
+                    .into_iter()
+                    .filter_map(|result| result.map_err(|error| println!("Error: {}", error)).ok())
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        let configuration = args.config.as_ref().map(fs::read_to_string).transpose()?;
+        let name = ids_file
+            .and_then(|path| Path::new(path).file_stem())
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Campaign report".to_string());
+
+        let campaign = report::html::Campaign {
+            name: &name,
+            definitions: &definitions,
+            configuration: configuration.as_deref(),
+        };
+        let path = args.output_dir.join("report.html");
+        fs::write(
+            &path,
+            report::html::render(&campaign, &measurements, &probes, &charts),
+        )?;
+        println!("Wrote {}", path.display());
+    }
+
+    Ok(())
+}
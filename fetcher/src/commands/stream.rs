@@ -0,0 +1,286 @@
+use clap::Args;
+use reqwest::Client;
+use std::{error::Error, time::Duration};
+use tokio::time::{self, Instant};
+
+use crate::{
+    analysis::rolling::{RollingRow, RollingSummary},
+    api::{
+        results::AggregatedMeasurement,
+        stream::{self, ResultStream, StreamError, StreamEvent},
+    },
+    commands::{AlertRuleArgs, OutputArgs, parse_duration},
+    io::{self, MeasurementSaver},
+};
+
+#[derive(Debug, Args)]
+pub struct StreamArgs {
+    #[clap(short, long)]
+    pub measurements: String,
+    #[clap(flatten)]
+    pub output: OutputArgs,
+    #[clap(flatten)]
+    pub alerts: AlertRuleArgs,
+    /// Websocket URL of the result stream, e.g. a local stand-in server for testing
+    #[clap(long, default_value = stream::STREAM_URL)]
+    pub url: String,
+    /// How often buffered results are appended to the output and the summary is printed
+    #[clap(long, default_value = "30s", value_parser = parse_duration)]
+    pub flush_every: i64,
+    /// Number of most recent results per pair the rolling summary covers
+    #[clap(long, default_value_t = 20)]
+    pub window: usize,
+    /// Stop after this long instead of running until interrupted
+    #[clap(long, value_parser = parse_duration)]
+    pub duration: Option<i64>,
+}
+
+pub async fn run(args: StreamArgs, client: &Client) -> Result<(), Box<dyn Error>> {
+    let measurement_ids = io::read_measurement_ids_from_file(&args.measurements)?;
+    let layout = args.output.layout(args.output.campaign(&args.measurements));
+    let saver = args.output.saver(layout, None, true)?;
+    let mut alerting = args.alerts.load()?;
+
+    let mut summary = RollingSummary::new(args.window);
+    let mut buffer: Vec<AggregatedMeasurement> = Vec::new();
+    let mut received = 0;
+    let mut flush = time::interval(Duration::from_secs(args.flush_every as u64));
+    flush.tick().await;
+    let deadline = args
+        .duration
+        .map(|seconds| Instant::now() + Duration::from_secs(seconds as u64));
+    let mut backoff = Duration::from_secs(1);
+
+    'connection: loop {
+        let mut results = match ResultStream::subscribe(&args.url, &measurement_ids.ids).await {
+            Ok(results) => {
+                println!(
+                    "Subscribed to {} measurements at {}",
+                    measurement_ids.ids.len(),
+                    args.url
+                );
+                backoff = Duration::from_secs(1);
+                results
+            }
+            Err(error) => {
+                println!("Error: {}, retrying in {}s", error, backoff.as_secs());
+                if interrupted(backoff, deadline).await {
+                    break 'connection;
+                }
+                backoff = (backoff * 2).min(Duration::from_secs(60));
+                continue;
+            }
+        };
+
+        loop {
+            tokio::select! {
+                event = results.next() => match event {
+                    Some(Ok(StreamEvent::Results(data))) => {
+                        for skipped in &data.skipped {
+                            println!("Skipped result of {}: {}", skipped.msm_id, skipped.error);
+                        }
+                        received += data.measurements.len();
+                        for measurement in &data.measurements {
+                            summary.add(measurement);
+                        }
+                        if let Some(alerting) = &mut alerting
+                            && let Err(error) = alerting.check(client, &data.measurements).await
+                        {
+                            println!("Error: Alerts: {}", error);
+                        }
+                        buffer.extend(data.measurements);
+                    }
+                    Some(Ok(StreamEvent::Subscribed(Some(id)))) => {
+                        println!("Subscription to measurement {} confirmed", id)
+                    }
+                    Some(Ok(StreamEvent::Subscribed(None))) => {}
+                    Some(Ok(StreamEvent::Warning(warning))) => println!("Warning: {}", warning),
+                    // The server rejected a subscription, reconnecting would only repeat that.
+                    Some(Err(error @ StreamError::Api(_))) => {
+                        results.close().await;
+                        save(saver.as_ref(), &mut buffer)?;
+                        return Err(error.into());
+                    }
+                    Some(Err(error)) => {
+                        println!("Error: {}, reconnecting in {}s", error, backoff.as_secs());
+                        break;
+                    }
+                    None => {
+                        println!("Stream closed, reconnecting in {}s", backoff.as_secs());
+                        break;
+                    }
+                },
+                _ = flush.tick() => {
+                    save(saver.as_ref(), &mut buffer)?;
+                    print_summary(received, &summary.rows());
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    results.close().await;
+                    break 'connection;
+                }
+                _ = sleep_until(deadline) => {
+                    results.close().await;
+                    break 'connection;
+                }
+            }
+        }
+
+        if interrupted(backoff, deadline).await {
+            break;
+        }
+        backoff = (backoff * 2).min(Duration::from_secs(60));
+    }
+
+    save(saver.as_ref(), &mut buffer)?;
+    print_summary(received, &summary.rows());
+    Ok(())
+}
+
+/// Waits `delay` before the next connection attempt. Returns `true` if the user interrupted or
+/// the deadline passed in the meantime.
+async fn interrupted(delay: Duration, deadline: Option<Instant>) -> bool {
+    tokio::select! {
+        _ = time::sleep(delay) => false,
+        _ = tokio::signal::ctrl_c() => true,
+        _ = sleep_until(deadline) => true,
+    }
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+fn save(
+    saver: &dyn MeasurementSaver,
+    buffer: &mut Vec<AggregatedMeasurement>,
+) -> Result<(), Box<dyn Error>> {
+    if !buffer.is_empty() {
+        saver.save_by_type(buffer)?;
+        buffer.clear();
+    }
+    Ok(())
+}
+
+fn print_summary(received: usize, rows: &[RollingRow]) {
+    let format = |value: Option<f32>| {
+        value
+            .map(|v| format!("{:.2}", v))
+            .unwrap_or_else(|| "-".to_string())
+    };
+
+    println!("{} results received", received);
+    println!(
+        "{:<10} {:>8} {:<40} {:>7} {:>9} {:>9} {:>9} {:>7}",
+        "type", "probe", "target", "results", "last", "median", "p95", "loss %"
+    );
+    for row in rows {
+        println!(
+            "{:<10} {:>8} {:<40} {:>7} {:>9} {:>9} {:>9} {:>7}",
+            row.kind,
+            row.prb_id,
+            row.dst_addr,
+            row.results,
+            format(row.last_rtt),
+            format(row.median),
+            format(row.p95),
+            format(row.loss)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use serde_json::json;
+    use std::fs;
+
+    use crate::test_support::{ping_json, stream_server, temp_dir};
+
+    #[derive(Parser)]
+    struct Cli {
+        #[clap(flatten)]
+        args: StreamArgs,
+    }
+
+    #[tokio::test]
+    async fn reconnects_after_the_stream_closes() {
+        let (url, server) = stream_server(
+            1,
+            vec![
+                vec![json!([
+                    "atlas_result",
+                    ping_json(1001, 10, 100, &[Some(1.0)])
+                ])],
+                vec![json!([
+                    "atlas_result",
+                    ping_json(1001, 10, 200, &[Some(2.0)])
+                ])],
+            ],
+        )
+        .await;
+        let dir = temp_dir("stream-reconnect");
+        let ids = dir.join("campaign.toml");
+        fs::write(&ids, r#"ids = ["1001"]"#).unwrap();
+
+        let cli = Cli::parse_from([
+            "stream",
+            "--measurements",
+            ids.to_str().unwrap(),
+            "--output-format",
+            "jsonl",
+            "--output-dir",
+            dir.to_str().unwrap(),
+            "--url",
+            &url,
+            "--duration",
+            "2s",
+        ]);
+        run(cli.args, &Client::new()).await.unwrap();
+
+        // Both connections subscribed, the second one after the first was closed.
+        assert_eq!(server.await.unwrap().len(), 2);
+        let data = io::read_jsonl(&dir.join("ping.jsonl")).unwrap();
+        let timestamps: Vec<usize> = data.measurements.iter().map(|m| m.timestamp()).collect();
+        assert_eq!(timestamps, [100, 200]);
+    }
+
+    #[tokio::test]
+    async fn stops_when_the_stream_rejects_a_subscription() {
+        let (url, server) = stream_server(
+            1,
+            vec![vec![
+                json!(["atlas_result", ping_json(1001, 10, 100, &[Some(1.0)])]),
+                json!(["atlas_error", "The measurement 9999 does not exist"]),
+            ]],
+        )
+        .await;
+        let dir = temp_dir("stream-rejected");
+        let ids = dir.join("campaign.toml");
+        fs::write(&ids, r#"ids = ["1001"]"#).unwrap();
+
+        let cli = Cli::parse_from([
+            "stream",
+            "--measurements",
+            ids.to_str().unwrap(),
+            "--output-format",
+            "jsonl",
+            "--output-dir",
+            dir.to_str().unwrap(),
+            "--url",
+            &url,
+            "--duration",
+            "5s",
+        ]);
+        let error = run(cli.args, &Client::new()).await.unwrap_err();
+
+        assert!(error.to_string().contains("9999 does not exist"), "{error}");
+        assert_eq!(server.await.unwrap().len(), 1);
+        // What arrived before the error is still written.
+        let data = io::read_jsonl(&dir.join("ping.jsonl")).unwrap();
+        assert_eq!(data.measurements.len(), 1);
+    }
+}
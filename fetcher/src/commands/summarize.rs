@@ -0,0 +1,71 @@
+use clap::Args;
+use reqwest::Client;
+use std::{error::Error, fs, path::PathBuf};
+
+use crate::{
+    analysis::summary::{self, Bucket, GroupKey},
+    commands::InputArgs,
+    io,
+};
+
+#[derive(Debug, Args)]
+pub struct SummarizeArgs {
+    #[clap(flatten)]
+    pub input: InputArgs,
+    /// Keys to group by. Without it a per-pair (type, source, target) and a per-probe
+    /// (type, source) summary are written
+    #[clap(short, long, value_enum, value_delimiter = ',')]
+    pub group_by: Vec<GroupKey>,
+    /// Additionally split every group into hourly or daily buckets
+    #[clap(short, long, value_enum)]
+    pub bucket: Option<Bucket>,
+    /// Output format, csv or json
+    #[clap(short, long, default_value = "csv")]
+    pub output_format: String,
+    #[clap(short = 'd', long, default_value = ".")]
+    pub output_dir: PathBuf,
+    /// Prefix of the written files
+    #[clap(short = 'n', long, default_value = "summary")]
+    pub name: String,
+}
+
+pub async fn run(args: SummarizeArgs, client: &Client) -> Result<(), Box<dyn Error>> {
+    let measurements = args.input.load(client).await?;
+
+    let summaries = if args.group_by.is_empty() {
+        vec![
+            (
+                format!("{}_pairs", args.name),
+                summary::summarize(
+                    &measurements,
+                    &[GroupKey::Type, GroupKey::Source, GroupKey::Target],
+                    args.bucket,
+                ),
+            ),
+            (
+                format!("{}_probes", args.name),
+                summary::summarize(
+                    &measurements,
+                    &[GroupKey::Type, GroupKey::Source],
+                    args.bucket,
+                ),
+            ),
+        ]
+    } else {
+        vec![(
+            args.name.clone(),
+            summary::summarize(&measurements, &args.group_by, args.bucket),
+        )]
+    };
+
+    fs::create_dir_all(&args.output_dir)?;
+    for (stem, rows) in summaries {
+        let path = args
+            .output_dir
+            .join(format!("{}.{}", stem, args.output_format));
+        io::write_table(&path, &args.output_format, &rows)?;
+        println!("Wrote {} rows to {}", rows.len(), path.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+use clap::Args;
+use futures::future::join_all;
+use reqwest::Client;
+use std::{error::Error, path::PathBuf, time::Duration};
+
+use crate::{
+    api::fetch_measurement_definition::{self, MeasurementDefinition},
+    commands::{AlertRuleArgs, IncrementalFetch, OutputArgs, parse_duration},
+    io,
+};
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    #[clap(short, long)]
+    pub measurements: String,
+    #[clap(flatten)]
+    pub output: OutputArgs,
+    #[clap(flatten)]
+    pub alerts: AlertRuleArgs,
+    /// Time between two polls, e.g. 90s, 5m or 1h
+    #[clap(short, long, default_value = "5m", value_parser = parse_duration)]
+    pub every: i64,
+    /// How long after its stop time a measurement is still polled for late results
+    #[clap(long, default_value = "10m", value_parser = parse_duration)]
+    pub grace: i64,
+    /// How far back every poll looks again for results that probes uploaded late
+    #[clap(long, default_value = "1h", value_parser = parse_duration)]
+    pub overlap: i64,
+    /// Last seen result timestamp per probe and measurement, defaults to {campaign}_watch.json
+    /// in the output directory. Lets an interrupted watch continue without duplicating results
+    #[clap(long)]
+    pub state: Option<PathBuf>,
+}
+
+/// Measurements that stopped on their own or were stopped by someone, rather than still waiting
+/// to run or running.
+const STOPPED: [&str; 7] = [
+    "Stopped",
+    "Forced to stop",
+    "No suitable probes",
+    "Failed",
+    "Archived",
+    "Denied",
+    "Canceled",
+];
+
+/// A measurement is done once its stop time plus `grace` has passed, or, without a stop time,
+/// once its status says it stopped.
+fn finished(definition: &MeasurementDefinition, now: i64, grace: i64) -> bool {
+    match definition.stop_time {
+        Some(stop_time) => now >= stop_time + grace,
+        None => definition
+            .status
+            .as_ref()
+            .is_some_and(|status| STOPPED.contains(&status.name.as_str())),
+    }
+}
+
+pub async fn run(args: WatchArgs, client: &Client) -> Result<(), Box<dyn Error>> {
+    let measurement_ids = io::read_measurement_ids_from_file(&args.measurements)?;
+    let campaign = args.output.campaign(&args.measurements);
+    let state_path = args.state.clone().unwrap_or_else(|| {
+        args.output
+            .output_dir
+            .join(format!("{}_watch.json", campaign))
+    });
+    let quarantine_path = args
+        .output
+        .output_dir
+        .join(format!("{}_quarantine.jsonl", campaign));
+    let saver = args
+        .output
+        .saver(args.output.layout(campaign), None, true)?;
+
+    let mut alerting = args.alerts.load()?;
+    let mut fetch = IncrementalFetch::load(&state_path)?.overlap(args.overlap);
+    let mut pending: Vec<String> = measurement_ids.ids.clone();
+
+    loop {
+        // Taken before polling, so a measurement only counts as finished if this poll already
+        // covered everything up to its stop time and grace period.
+        let now = Utc::now().timestamp();
+        let definitions = join_all(
+            pending
+                .iter()
+                .map(|id| fetch_measurement_definition::get_measurement_definition(client, id)),
+        )
+        .await;
+        let (data, failed) = fetch.fetch(client, &pending).await;
+
+        let mut done = Vec::new();
+        for (id, definition) in pending.iter().zip(definitions) {
+            // Not finished yet, the results are polled again next time.
+            if failed.contains(id) {
+                continue;
+            }
+            match definition {
+                Ok(definition) if finished(&definition, now, args.grace) => done.push(id.clone()),
+                Ok(_) => {}
+                Err(error) => println!("Error: {}", error),
+            }
+        }
+
+        saver.save_by_type(&data.measurements)?;
+        if let Some(alerting) = &mut alerting
+            && let Err(error) = alerting.check(client, &data.measurements).await
+        {
+            println!("Error: Alerts: {}", error);
+        }
+        if !data.skipped.is_empty() {
+            io::write_quarantine(&quarantine_path, &data.skipped, true)?;
+        }
+        fetch.save(&state_path)?;
+
+        for id in &done {
+            println!("Measurement {} reached its stop time", id);
+        }
+        pending.retain(|id| !done.contains(id));
+        if pending.is_empty() {
+            println!("All measurements finished");
+            return Ok(());
+        }
+
+        let next = DateTime::from_timestamp(Utc::now().timestamp() + args.every, 0)
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_default();
+        println!(
+            "{} measurements still running, next poll at {}",
+            pending.len(),
+            next
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(args.every as u64)) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use futures::{StreamExt, stream};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, error::Error, fs, path::Path};
+
+use crate::{
+    analysis::distance,
+    api::{
+        fetch_probe_metadata::{self, ProbeMetadata},
+        results::AggregatedMeasurement,
+    },
+};
+
+/// Probe metadata requests in flight at a time, so large campaigns do not flood the API.
+const LOOKUPS: usize = 8;
+
+/// Metadata of the source probes and target addresses of a campaign. It is cached on disk, so
+/// later fetches only ask the API about probes and addresses they have not seen yet.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ProbeDirectory {
+    probes: BTreeMap<u32, ProbeMetadata>,
+    /// Target address to the probe that owns it, `None` if no probe does.
+    addresses: BTreeMap<String, Option<u32>>,
+}
+
+/// Probe metadata appended to every output row, once for the source probe and once for the
+/// probe behind the target address, followed by the distance between both and how the RTT of
+/// the row compares to the speed of light in fibre.
+#[derive(Debug, Default, Serialize)]
+pub struct ProbeColumns {
+    pub src_country: Option<String>,
+    pub src_asn_v4: Option<u32>,
+    pub src_asn_v6: Option<u32>,
+    pub src_latitude: Option<f64>,
+    pub src_longitude: Option<f64>,
+    pub src_is_anchor: Option<bool>,
+    pub src_fqdn: Option<String>,
+    pub dst_prb_id: Option<u32>,
+    pub dst_country: Option<String>,
+    pub dst_asn_v4: Option<u32>,
+    pub dst_asn_v6: Option<u32>,
+    pub dst_latitude: Option<f64>,
+    pub dst_longitude: Option<f64>,
+    pub dst_is_anchor: Option<bool>,
+    pub dst_fqdn: Option<String>,
+    pub distance_km: Option<f64>,
+    pub fibre_rtt: Option<f64>,
+    pub rtt_inflation: Option<f64>,
+}
+
+impl ProbeDirectory {
+    /// Reads the cache file, an absent file is an empty cache.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(ProbeDirectory::default());
+        }
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Fetches the metadata of every probe and target address in `measurements` that is not
+    /// cached yet, probes in batches and at most [`LOOKUPS`] requests at a time. Lookups that
+    /// fail are reported and left out, so they are retried next time. Returns how many failed.
+    pub async fn resolve(
+        &mut self,
+        client: &Client,
+        measurements: &[AggregatedMeasurement],
+    ) -> usize {
+        let mut prb_ids: Vec<u32> = Vec::new();
+        let mut addresses: Vec<&str> = Vec::new();
+        for measurement in measurements {
+            let prb_id = measurement.prb_id();
+            if !self.probes.contains_key(&prb_id) && !prb_ids.contains(&prb_id) {
+                prb_ids.push(prb_id);
+            }
+            for address in target_addresses(measurement) {
+                if !self.addresses.contains_key(address) && !addresses.contains(&address) {
+                    addresses.push(address);
+                }
+            }
+        }
+
+        let mut failed = 0;
+        let mut batches =
+            stream::iter(prb_ids.chunks(fetch_probe_metadata::PROBE_BATCH))
+                .map(|batch| async move {
+                    (batch, fetch_probe_metadata::get_probes(client, batch).await)
+                })
+                .buffer_unordered(LOOKUPS);
+        while let Some((batch, result)) = batches.next().await {
+            match result {
+                Ok(probes) => {
+                    for probe in probes {
+                        self.probes.insert(probe.prb_id, probe);
+                    }
+                }
+                Err(error) => {
+                    println!("Error: Batch of {} probes: {}", batch.len(), error);
+                    failed += batch.len();
+                }
+            }
+        }
+
+        let mut owners = stream::iter(addresses)
+            .map(|address| async move {
+                (
+                    address,
+                    fetch_probe_metadata::find_probe_by_address(client, address).await,
+                )
+            })
+            .buffer_unordered(LOOKUPS);
+        while let Some((address, result)) = owners.next().await {
+            match result {
+                Ok(probe) => {
+                    let prb_id = probe.map(|probe| {
+                        let id = probe.prb_id;
+                        self.probes.entry(id).or_insert(probe);
+                        id
+                    });
+                    self.addresses.insert(address.to_string(), prb_id);
+                }
+                Err(error) => {
+                    println!("Error: Address {}: {}", address, error);
+                    failed += 1;
+                }
+            }
+        }
+
+        failed
+    }
+
+    pub fn probe(&self, prb_id: u32) -> Option<&ProbeMetadata> {
+        self.probes.get(&prb_id)
+    }
+
+    /// Short name for tables and charts, the probe id followed by its country if known.
+    pub fn label(&self, prb_id: u32) -> String {
+        match self.probe(prb_id).and_then(|p| p.country_code.as_deref()) {
+            Some(country) => format!("{} ({})", prb_id, country),
+            None => prb_id.to_string(),
+        }
+    }
+
+    pub fn by_address(&self, address: &str) -> Option<&ProbeMetadata> {
+        self.addresses
+            .get(address)
+            .copied()
+            .flatten()
+            .and_then(|prb_id| self.probes.get(&prb_id))
+    }
+
+    /// Every target address and the probe owning it, if any.
+    pub fn addresses(&self) -> impl Iterator<Item = (&str, Option<&ProbeMetadata>)> {
+        self.addresses.iter().map(|(address, prb_id)| {
+            (
+                address.as_str(),
+                prb_id.and_then(|prb_id| self.probes.get(&prb_id)),
+            )
+        })
+    }
+
+    pub fn probes(&self) -> impl Iterator<Item = &ProbeMetadata> {
+        self.probes.values()
+    }
+
+    /// Great-circle distance between a source probe and the probe behind a target address,
+    /// if both locations are known.
+    pub fn distance_km(&self, prb_id: u32, dst_addr: Option<&str>) -> Option<f64> {
+        let location = |probe: &ProbeMetadata| probe.latitude.zip(probe.longitude);
+        let src = self.probe(prb_id).and_then(location)?;
+        let dst = dst_addr
+            .and_then(|address| self.by_address(address))
+            .and_then(location)?;
+        Some(distance::great_circle_km(src, dst))
+    }
+
+    /// `rtt` is the value compared against the fibre bound, see [`distance::reference_rtt`].
+    pub fn columns(&self, prb_id: u32, dst_addr: Option<&str>, rtt: Option<f32>) -> ProbeColumns {
+        let src = self.probe(prb_id);
+        let dst = dst_addr.and_then(|address| self.by_address(address));
+        let distance_km = self.distance_km(prb_id, dst_addr);
+        let fibre_rtt = distance_km.map(distance::fibre_rtt_ms);
+
+        ProbeColumns {
+            src_country: src.and_then(|p| p.country_code.clone()),
+            src_asn_v4: src.and_then(|p| p.asn_v4),
+            src_asn_v6: src.and_then(|p| p.asn_v6),
+            src_latitude: src.and_then(|p| p.latitude),
+            src_longitude: src.and_then(|p| p.longitude),
+            src_is_anchor: src.map(|p| p.is_anchor),
+            src_fqdn: src.and_then(|p| p.fqdn.clone()),
+            dst_prb_id: dst.map(|p| p.prb_id),
+            dst_country: dst.and_then(|p| p.country_code.clone()),
+            dst_asn_v4: dst.and_then(|p| p.asn_v4),
+            dst_asn_v6: dst.and_then(|p| p.asn_v6),
+            dst_latitude: dst.and_then(|p| p.latitude),
+            dst_longitude: dst.and_then(|p| p.longitude),
+            dst_is_anchor: dst.map(|p| p.is_anchor),
+            dst_fqdn: dst.and_then(|p| p.fqdn.clone()),
+            distance_km,
+            fibre_rtt,
+            rtt_inflation: fibre_rtt
+                .zip(rtt)
+                .and_then(|(fibre_rtt, rtt)| distance::inflation(rtt, fibre_rtt)),
+        }
+    }
+}
+
+fn target_addresses(measurement: &AggregatedMeasurement) -> Vec<&str> {
+    match measurement {
+        AggregatedMeasurement::Http(h) => h
+            .result
+            .iter()
+            .filter_map(|r| r.dst_addr.as_deref())
+            .collect(),
+        _ => measurement.dst_addr().into_iter().collect(),
+    }
+}
@@ -0,0 +1,254 @@
+use chrono::DateTime;
+use reqwest::Client;
+use std::{
+    error::Error,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use crate::{
+    analysis::alert::{Alert, AlertStatus},
+    io,
+};
+
+/// Where alerts go. They are always printed, and optionally appended to a JSON Lines file and
+/// posted as JSON to a webhook.
+#[derive(Debug, Default)]
+pub struct AlertSink {
+    file: Option<PathBuf>,
+    webhook: Option<String>,
+}
+
+impl AlertSink {
+    pub fn new() -> Self {
+        AlertSink::default()
+    }
+
+    pub fn file(mut self, val: Option<PathBuf>) -> Self {
+        self.file = val;
+        self
+    }
+
+    pub fn webhook(mut self, val: Option<String>) -> Self {
+        self.webhook = val;
+        self
+    }
+
+    /// A webhook that cannot be reached is reported without failing, so one unavailable
+    /// receiver does not stop a long-running watch or stream. Returns the alerts the webhook
+    /// did not accept.
+    pub async fn emit<'a>(
+        &self,
+        client: &Client,
+        alerts: &'a [Alert],
+    ) -> Result<Vec<&'a Alert>, Box<dyn Error>> {
+        let mut undelivered = Vec::new();
+        if alerts.is_empty() {
+            return Ok(undelivered);
+        }
+
+        for alert in alerts {
+            println!("{}", describe(alert));
+        }
+
+        if let Some(path) = &self.file {
+            let (file, _) = io::open_output(path, true)?;
+            let mut writer = BufWriter::new(file);
+            for alert in alerts {
+                serde_json::to_writer(&mut writer, alert)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+        }
+
+        if let Some(url) = &self.webhook {
+            for alert in alerts {
+                match client.post(url).json(alert).send().await {
+                    Ok(response) if !response.status().is_success() => {
+                        println!("Error: Webhook returned {}", response.status());
+                        undelivered.push(alert);
+                    }
+                    Ok(_) => {}
+                    Err(error) => {
+                        println!("Error: Webhook: {}", error);
+                        undelivered.push(alert);
+                    }
+                }
+            }
+        }
+
+        Ok(undelivered)
+    }
+}
+
+fn describe(alert: &Alert) -> String {
+    let time = |timestamp: usize| {
+        DateTime::from_timestamp(timestamp as i64, 0)
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_default()
+    };
+    match alert.status {
+        AlertStatus::Firing => format!(
+            "[FIRING] {}: {} {} probe {} -> {}: {} {} {} ({}) since {}",
+            alert.rule,
+            alert.kind,
+            alert.msm_id,
+            alert.prb_id,
+            alert.dst_addr,
+            alert.metric.as_str(),
+            alert.op.as_str(),
+            alert.threshold,
+            alert.value,
+            time(alert.since)
+        ),
+        AlertStatus::Resolved => format!(
+            "[RESOLVED] {}: {} {} probe {} -> {}: {} is {} at {}",
+            alert.rule,
+            alert.kind,
+            alert.msm_id,
+            alert.prb_id,
+            alert.dst_addr,
+            alert.metric.as_str(),
+            alert.value,
+            time(alert.timestamp)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::fs;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use crate::{
+        analysis::alert::{Comparison, Metric},
+        test_support::temp_dir,
+    };
+
+    fn alert(status: AlertStatus, value: f64) -> Alert {
+        Alert {
+            rule: "slow".to_string(),
+            status,
+            kind: "ping".to_string(),
+            msm_id: 1001,
+            prb_id: 10,
+            dst_addr: "192.0.2.1".to_string(),
+            timestamp: 300,
+            since: 100,
+            metric: Metric::Rtt,
+            op: Comparison::Greater,
+            threshold: 50.0,
+            value,
+        }
+    }
+
+    /// Accepts `requests` HTTP requests, answers each with `200 OK` and returns them.
+    async fn webhook(requests: usize) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut received = Vec::new();
+            for _ in 0..requests {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                loop {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|line| {
+                                line.to_ascii_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|value| value.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if body.len() >= length {
+                            break;
+                        }
+                    }
+                    if read == 0 {
+                        break;
+                    }
+                }
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                    .await
+                    .unwrap();
+                received.push(String::from_utf8(request).unwrap());
+            }
+            received
+        });
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn posts_alerts_to_the_webhook_and_appends_them_to_the_file() {
+        let (url, server) = webhook(2).await;
+        let file = temp_dir("alert-sink").join("alerts.jsonl");
+        let sink = AlertSink::new().file(Some(file.clone())).webhook(Some(url));
+
+        let alerts = [
+            alert(AlertStatus::Firing, 80.0),
+            alert(AlertStatus::Resolved, 10.0),
+        ];
+        assert!(
+            sink.emit(&Client::new(), &alerts[..1])
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            sink.emit(&Client::new(), &alerts[1..])
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let requests = server.await.unwrap();
+        let bodies: Vec<Value> = requests
+            .iter()
+            .map(|request| {
+                assert!(
+                    request.starts_with("POST /hook HTTP/1.1\r\n"),
+                    "{}",
+                    request
+                );
+                let (_, body) = request.split_once("\r\n\r\n").unwrap();
+                serde_json::from_str(body).unwrap()
+            })
+            .collect();
+        assert_eq!(bodies[0]["status"], "firing");
+        assert_eq!(bodies[0]["rule"], "slow");
+        assert_eq!(bodies[0]["op"], ">");
+        assert_eq!(bodies[0]["value"], 80.0);
+        assert_eq!(bodies[1]["status"], "resolved");
+
+        let lines: Vec<Value> = fs::read_to_string(&file)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines, bodies);
+    }
+
+    #[tokio::test]
+    async fn unreachable_webhook_returns_the_alerts() {
+        // Bound and dropped again, so nothing listens on the port.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+
+        let sink = AlertSink::new().webhook(Some(url));
+        let alerts = [alert(AlertStatus::Firing, 80.0)];
+        let undelivered = sink.emit(&Client::new(), &alerts).await.unwrap();
+        assert_eq!(undelivered.len(), 1);
+    }
+}
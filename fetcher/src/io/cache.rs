@@ -0,0 +1,209 @@
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::Serialize;
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// How old results have to be before they are cached, one day.
+pub const DEFAULT_SETTLE_TIME: i64 = 86_400;
+
+/// Raw result pages as the API returned them, gzip compressed, one file per measurement and
+/// time window at `{dir}/{msm_id}/{start}-{stop}.json.gz`.
+#[derive(Debug)]
+pub struct ResultCache {
+    dir: PathBuf,
+    settle_time: i64,
+}
+
+/// One cached window of results of a measurement, `start` and `stop` inclusive.
+#[derive(Debug, Serialize)]
+pub struct CachedPage {
+    pub msm_id: String,
+    pub start: i64,
+    pub stop: i64,
+    /// Compressed size in bytes
+    pub size: u64,
+    /// When the page was downloaded, unix seconds
+    pub fetched: i64,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl ResultCache {
+    pub fn new(dir: PathBuf) -> Self {
+        ResultCache {
+            dir,
+            settle_time: DEFAULT_SETTLE_TIME,
+        }
+    }
+
+    /// Seconds after which results are no longer expected to change. Probes that were offline
+    /// upload their results late, so only windows that ended longer ago than this are cached.
+    pub fn settle_time(mut self, val: i64) -> Self {
+        self.settle_time = val;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The newest time a cached window may end at `now`.
+    pub fn settled(&self, now: i64) -> i64 {
+        now - self.settle_time
+    }
+
+    /// The cached pages of a measurement, ordered by the start of their window. Files that do
+    /// not look like pages are ignored.
+    pub fn pages(&self, msm_id: &str) -> io::Result<Vec<CachedPage>> {
+        let directory = self.dir.join(msm_id);
+        if !directory.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut pages = Vec::new();
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some((start, stop)) = name
+                .to_str()
+                .and_then(|name| name.strip_suffix(".json.gz"))
+                .and_then(|window| window.split_once('-'))
+                .and_then(|(start, stop)| Some((start.parse().ok()?, stop.parse().ok()?)))
+            else {
+                continue;
+            };
+            let metadata = entry.metadata()?;
+            let fetched = metadata
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |age| age.as_secs() as i64);
+            pages.push(CachedPage {
+                msm_id: msm_id.to_string(),
+                start,
+                stop,
+                size: metadata.len(),
+                fetched,
+                path: entry.path(),
+            });
+        }
+        pages.sort_by_key(|page| (page.start, page.stop));
+        Ok(pages)
+    }
+
+    /// The pages of every cached measurement.
+    pub fn all_pages(&self) -> io::Result<Vec<CachedPage>> {
+        if !self.dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut ids: Vec<String> = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                ids.extend(entry.file_name().to_str().map(str::to_string));
+            }
+        }
+        ids.sort();
+
+        let mut pages = Vec::new();
+        for id in ids {
+            pages.extend(self.pages(&id)?);
+        }
+        Ok(pages)
+    }
+
+    pub fn read(&self, page: &CachedPage) -> io::Result<String> {
+        let mut text = String::new();
+        GzDecoder::new(fs::File::open(&page.path)?).read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    /// Writes to a temporary file first, so an interrupted write never leaves a truncated page
+    /// behind.
+    pub fn write(&self, msm_id: &str, start: i64, stop: i64, text: &str) -> io::Result<()> {
+        let directory = self.dir.join(msm_id);
+        fs::create_dir_all(&directory)?;
+        let path = directory.join(format!("{}-{}.json.gz", start, stop));
+        let partial = path.with_extension("partial");
+
+        let mut encoder = GzEncoder::new(fs::File::create(&partial)?, Compression::default());
+        encoder.write_all(text.as_bytes())?;
+        encoder.finish()?;
+        fs::rename(partial, path)
+    }
+
+    /// Deletes a page, and the directory of its measurement once it is empty.
+    pub fn remove(&self, page: &CachedPage) -> io::Result<()> {
+        fs::remove_file(&page.path)?;
+        let directory = self.dir.join(&page.msm_id);
+        if fs::read_dir(&directory)?.next().is_none() {
+            fs::remove_dir(directory)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    fn windows(pages: &[CachedPage]) -> Vec<(&str, i64, i64)> {
+        pages
+            .iter()
+            .map(|page| (page.msm_id.as_str(), page.start, page.stop))
+            .collect()
+    }
+
+    #[test]
+    fn writes_lists_reads_and_removes_pages() {
+        let cache = ResultCache::new(temp_dir("cache-pages"));
+        cache.write("1001", 200, 299, "[2]").unwrap();
+        cache.write("1001", 100, 199, "[1]").unwrap();
+        cache.write("1002", 0, 99, "[]").unwrap();
+
+        let pages = cache.pages("1001").unwrap();
+        assert_eq!(windows(&pages), [("1001", 100, 199), ("1001", 200, 299)]);
+        assert_eq!(cache.read(&pages[0]).unwrap(), "[1]");
+        assert_eq!(cache.read(&pages[1]).unwrap(), "[2]");
+        assert!(pages.iter().all(|page| page.size > 0));
+        assert!(cache.pages("1003").unwrap().is_empty());
+        assert_eq!(
+            windows(&cache.all_pages().unwrap()),
+            [("1001", 100, 199), ("1001", 200, 299), ("1002", 0, 99)]
+        );
+
+        for page in &pages {
+            cache.remove(page).unwrap();
+        }
+        assert!(!cache.dir().join("1001").exists());
+        assert_eq!(windows(&cache.all_pages().unwrap()), [("1002", 0, 99)]);
+    }
+
+    #[test]
+    fn ignores_interrupted_writes() {
+        let cache = ResultCache::new(temp_dir("cache-partial"));
+        cache.write("1001", 100, 199, "[1]").unwrap();
+        let directory = cache.dir().join("1001");
+        // Left behind by a write that did not get to the rename.
+        fs::write(directory.join("200-299.json.partial"), "[2").unwrap();
+        fs::write(directory.join("notes.txt"), "").unwrap();
+
+        let names: Vec<String> = fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert!(!names.contains(&"100-199.json.partial".to_string()));
+        assert_eq!(windows(&cache.pages("1001").unwrap()), [("1001", 100, 199)]);
+
+        // A rewrite of the same window replaces it.
+        cache.write("1001", 100, 199, "[3]").unwrap();
+        let pages = cache.pages("1001").unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(cache.read(&pages[0]).unwrap(), "[3]");
+    }
+}
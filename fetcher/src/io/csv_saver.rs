@@ -0,0 +1,268 @@
+use csv::{Writer, WriterBuilder};
+use serde::Serialize;
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    analysis::distance,
+    api::results::{
+        AggregatedMeasurement, FlattenedHopReply, FlattenedHttpMeasurement,
+        FlattenedPingMeasurement, FlattenedPingPacket, FlattenedTraceRouteMeasurement,
+    },
+    enrichment::ProbeDirectory,
+    io::{
+        self, MeasurementSaver,
+        layout::{OutputLayout, with_extension},
+    },
+};
+
+pub struct CsvSaver {
+    layout: OutputLayout,
+    ping_packets: bool,
+    hop_replies: bool,
+    legacy_sentinels: bool,
+    append: bool,
+    probes: Option<Arc<ProbeDirectory>>,
+}
+
+impl CsvSaver {
+    pub fn new(layout: OutputLayout) -> Self {
+        CsvSaver {
+            layout,
+            ping_packets: false,
+            hop_replies: false,
+            legacy_sentinels: false,
+            append: false,
+            probes: None,
+        }
+    }
+
+    /// Write only the columns of the old format, with placeholder values such as `timeout` or
+    /// `0.0` instead of empty cells for missing data, for scripts that still expect it.
+    pub fn legacy_sentinels(mut self, val: bool) -> Self {
+        self.legacy_sentinels = val;
+        self
+    }
+
+    /// Append rows to existing files instead of replacing them. The header is only written to
+    /// new files.
+    pub fn append(mut self, val: bool) -> Self {
+        self.append = val;
+        self
+    }
+
+    fn writer(&self, path: &Path) -> Result<Writer<fs::File>, Box<dyn Error>> {
+        let (file, existing) = io::open_output(path, self.append)?;
+        Ok(WriterBuilder::new()
+            .has_headers(!existing)
+            .from_writer(file))
+    }
+
+    /// Additionally write every ping reply as its own row into a `_packets` file.
+    pub fn ping_packets(mut self, val: bool) -> Self {
+        self.ping_packets = val;
+        self
+    }
+
+    /// Append source and target probe metadata columns to every row.
+    pub fn probe_metadata(mut self, val: Option<Arc<ProbeDirectory>>) -> Self {
+        self.probes = val;
+        self
+    }
+
+    fn write_row<W: std::io::Write>(
+        &self,
+        writer: &mut Writer<W>,
+        row: impl Serialize,
+        prb_id: u32,
+        dst_addr: Option<&str>,
+        rtt: Option<f32>,
+    ) -> csv::Result<()> {
+        match &self.probes {
+            Some(probes) => writer.serialize((row, probes.columns(prb_id, dst_addr, rtt))),
+            None => writer.serialize(row),
+        }
+    }
+
+    /// Additionally write every traceroute reply as its own row into a `_hops` file.
+    pub fn hop_replies(mut self, val: bool) -> Self {
+        self.hop_replies = val;
+        self
+    }
+
+    fn save_ping_packets(
+        &self,
+        path: &Path,
+        measurements: &[&AggregatedMeasurement],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut writer = self.writer(path)?;
+
+        for entry in measurements {
+            if let AggregatedMeasurement::Ping(p) = entry {
+                for row in FlattenedPingPacket::from_ping_measurement(p) {
+                    let rtt = row.rtt;
+                    self.write_row(&mut writer, row, p.prb_id, Some(&p.dst_addr), rtt)?;
+                }
+            }
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn save_hop_replies(
+        &self,
+        path: &Path,
+        measurements: &[&AggregatedMeasurement],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut writer = self.writer(path)?;
+
+        for entry in measurements {
+            if let AggregatedMeasurement::TraceRoute(t) = entry {
+                for row in FlattenedHopReply::from_traceroute_measurement(t) {
+                    self.write_row(&mut writer, row, t.prb_id, Some(&t.dst_addr), None)?;
+                }
+            }
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn with_suffix(stem: &Path, suffix: &str) -> PathBuf {
+    let mut path = stem.as_os_str().to_owned();
+    path.push(suffix);
+    with_extension(&PathBuf::from(path), "csv")
+}
+
+impl MeasurementSaver for CsvSaver {
+    fn save_by_type(&self, measurements: &[AggregatedMeasurement]) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(self.layout.directory())?;
+
+        for (stem, bucket) in self.layout.partition(measurements) {
+            self.save_to(&with_extension(&stem, "csv"), &bucket)?;
+
+            let has_pings = bucket
+                .iter()
+                .any(|m| matches!(m, AggregatedMeasurement::Ping(_)));
+            if self.ping_packets && has_pings {
+                self.save_ping_packets(&with_suffix(&stem, "_packets"), &bucket)?;
+            }
+
+            let has_traceroutes = bucket
+                .iter()
+                .any(|m| matches!(m, AggregatedMeasurement::TraceRoute(_)));
+            if self.hop_replies && has_traceroutes {
+                self.save_hop_replies(&with_suffix(&stem, "_hops"), &bucket)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn save_to(
+        &self,
+        path: &Path,
+        measurements: &[&AggregatedMeasurement],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut writer = self.writer(path)?;
+
+        for entry in measurements {
+            match *entry {
+                AggregatedMeasurement::Ping(p) => {
+                    let row = FlattenedPingMeasurement::from_ping_measurement(p);
+                    let rtt = row.min;
+                    if self.legacy_sentinels {
+                        let row = row.with_legacy_sentinels();
+                        self.write_row(&mut writer, row, p.prb_id, Some(&p.dst_addr), rtt)?;
+                    } else {
+                        self.write_row(&mut writer, row, p.prb_id, Some(&p.dst_addr), rtt)?;
+                    }
+                }
+                AggregatedMeasurement::Http(p) => {
+                    for row in FlattenedHttpMeasurement::from_http_measurement(p) {
+                        let (dst_addr, rtt) = (row.dst_addr.clone(), row.ttc);
+                        let dst_addr = dst_addr.as_deref();
+                        if self.legacy_sentinels {
+                            let row = row.with_legacy_sentinels();
+                            self.write_row(&mut writer, row, p.prb_id, dst_addr, rtt)?;
+                        } else {
+                            self.write_row(&mut writer, row, p.prb_id, dst_addr, rtt)?;
+                        }
+                    }
+                }
+                AggregatedMeasurement::TraceRoute(t) => {
+                    let rows = FlattenedTraceRouteMeasurement::from_traceroute_measurement(t);
+                    for row in rows {
+                        let rtt = distance::final_hop_rtt(&row);
+                        if self.legacy_sentinels {
+                            let row = row.with_legacy_sentinels();
+                            self.write_row(&mut writer, row, t.prb_id, Some(&t.dst_addr), rtt)?;
+                        } else {
+                            self.write_row(&mut writer, row, t.prb_id, Some(&t.dst_addr), rtt)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{http, ping, temp_dir, traceroute};
+
+    #[test]
+    fn legacy_sentinels_keep_the_old_columns() {
+        let dir = temp_dir("csv-legacy");
+        let layout = OutputLayout::new(dir.clone(), "{type}".to_string(), "c".to_string(), false);
+        let measurements = [
+            ping(1001, 10, 100, &[None, None]),
+            http(3001, 10, 100, None),
+            traceroute(2001, 10, 100, &["198.51.100.254", "192.0.2.1"], true),
+        ];
+        CsvSaver::new(layout)
+            .legacy_sentinels(true)
+            .save_by_type(&measurements)
+            .unwrap();
+
+        let read = |kind: &str| fs::read_to_string(dir.join(format!("{kind}.csv"))).unwrap();
+        // The headers written before the new columns were added.
+        let ping = read("ping");
+        let mut lines = ping.lines();
+        assert_eq!(
+            lines.next(),
+            Some("dst_addr,src_addr,proto,rcvd,sent,min,max,avg,msm_id,timestamp,prb_id")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("192.0.2.1,198.51.100.1,ICMP,0,2,-1.0,-1.0,-1.0,1001,100,10")
+        );
+        let http = read("http");
+        let mut lines = http.lines();
+        assert_eq!(
+            lines.next(),
+            Some("ttr,method,dst_addr,src_addr,rt,res,ver,hsize,bsize,msm_id,timestamp,prb_id")
+        );
+        assert_eq!(
+            lines.next(),
+            Some(",GET,192.0.2.1,198.51.100.1,0.0,408,timeout,0,0,3001,100,10")
+        );
+        assert_eq!(
+            read("traceroute").lines().next(),
+            Some(
+                "msm_id,src_addr,dst_addr,hop,sent,received,min,avg,max,from,timed_out,timestamp,\
+                 prb_id,endtime,proto,paris_id,destination_ip_responded"
+            )
+        );
+    }
+}
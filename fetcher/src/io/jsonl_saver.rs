@@ -0,0 +1,162 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    error::Error,
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{
+    analysis::distance,
+    api::{fetch_probe_metadata::ProbeMetadata, results::AggregatedMeasurement},
+    enrichment::ProbeDirectory,
+    io::{
+        self, MeasurementSaver,
+        layout::{OutputLayout, with_extension},
+    },
+};
+
+/// Writes the results as JSON Lines without flattening them, so hop and packet arrays survive
+/// and downstream tools can reprocess them without downloading them again. Results are written as
+/// received from the API, including fields the parser does not know.
+pub struct JsonLinesSaver {
+    layout: OutputLayout,
+    append: bool,
+    probes: Option<Arc<ProbeDirectory>>,
+}
+
+/// The metadata of the source probe and target, added next to the original fields.
+#[derive(Serialize)]
+struct Enrichment<'a> {
+    src_probe: Option<&'a ProbeMetadata>,
+    dst_probe: Option<&'a ProbeMetadata>,
+    distance_km: Option<f64>,
+    fibre_rtt: Option<f64>,
+    rtt_inflation: Option<f64>,
+}
+
+impl JsonLinesSaver {
+    pub fn new(layout: OutputLayout) -> Self {
+        JsonLinesSaver {
+            layout,
+            append: false,
+            probes: None,
+        }
+    }
+
+    /// Append lines to existing files instead of replacing them.
+    pub fn append(mut self, val: bool) -> Self {
+        self.append = val;
+        self
+    }
+
+    /// Add `src_probe` and `dst_probe` objects with probe metadata to every line.
+    pub fn probe_metadata(mut self, val: Option<Arc<ProbeDirectory>>) -> Self {
+        self.probes = val;
+        self
+    }
+}
+
+impl MeasurementSaver for JsonLinesSaver {
+    fn save_by_type(&self, measurements: &[AggregatedMeasurement]) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(self.layout.directory())?;
+
+        for (stem, bucket) in self.layout.partition(measurements) {
+            self.save_to(&with_extension(&stem, "jsonl"), &bucket)?;
+        }
+
+        Ok(())
+    }
+
+    fn save_to(
+        &self,
+        path: &Path,
+        measurements: &[&AggregatedMeasurement],
+    ) -> Result<(), Box<dyn Error>> {
+        let (file, _) = io::open_output(path, self.append)?;
+        let mut writer = BufWriter::new(file);
+
+        for entry in measurements {
+            let mut line = match entry.raw() {
+                Some(raw) => raw.clone(),
+                None => serde_json::to_value(entry)?,
+            };
+            if let Some(probes) = &self.probes {
+                let columns = probes.columns(
+                    entry.prb_id(),
+                    entry.dst_addr(),
+                    distance::reference_rtt(entry),
+                );
+                let enrichment = serde_json::to_value(Enrichment {
+                    src_probe: probes.probe(entry.prb_id()),
+                    dst_probe: entry.dst_addr().and_then(|a| probes.by_address(a)),
+                    distance_km: columns.distance_km,
+                    fibre_rtt: columns.fibre_rtt,
+                    rtt_inflation: columns.rtt_inflation,
+                })?;
+                // Replaces the metadata of files that were enriched before.
+                if let (Some(line), Value::Object(enrichment)) = (line.as_object_mut(), enrichment)
+                {
+                    line.extend(enrichment);
+                }
+            }
+            serde_json::to_writer(&mut writer, &line)?;
+            writer.write_all(b"\n")?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api::fetch_measurement_data::parse_results, test_support::temp_dir};
+    use serde_json::json;
+
+    fn lines(path: &Path) -> Vec<Value> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn writes_results_as_received() {
+        let dir = temp_dir("jsonl-raw");
+        let http = json!({
+            "type": "http", "fw": 5020, "lts": 12, "uri": "http://192.0.2.1/",
+            "result": [{ "method": "GET", "dst_addr": "192.0.2.1", "res": 200, "rt": 20.0 }],
+            "msm_id": 3001, "timestamp": 100, "prb_id": 10,
+        });
+        let data = parse_results("3001", vec![http.clone()]);
+
+        let layout =
+            || OutputLayout::new(dir.clone(), "{type}".to_string(), "c".to_string(), false);
+        JsonLinesSaver::new(layout())
+            .save_by_type(&data.measurements)
+            .unwrap();
+        assert_eq!(lines(&dir.join("http.jsonl")), [http]);
+
+        let probes: ProbeDirectory = serde_json::from_value(json!({
+            "probes": { "10": { "prb_id": 10, "is_anchor": false, "country_code": "NL" } },
+            "addresses": {},
+        }))
+        .unwrap();
+        let saver = JsonLinesSaver::new(layout()).probe_metadata(Some(Arc::new(probes)));
+        saver.save_by_type(&data.measurements).unwrap();
+        // Enriching a file that was enriched before replaces the metadata.
+        let enriched = io::read_jsonl(&dir.join("http.jsonl")).unwrap();
+        saver.save_by_type(&enriched.measurements).unwrap();
+
+        let line = &lines(&dir.join("http.jsonl"))[0];
+        assert_eq!(line["fw"], 5020);
+        assert_eq!(line["lts"], 12);
+        assert_eq!(line["src_probe"]["country_code"], "NL");
+        assert_eq!(line["dst_probe"], Value::Null);
+    }
+}
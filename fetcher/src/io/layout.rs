@@ -0,0 +1,176 @@
+use chrono::DateTime;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::api::results::AggregatedMeasurement;
+
+/// Decides where the output files of a fetch end up and how they are named.
+///
+/// The file name template supports the placeholders `{campaign}`, `{start}`, `{end}`, `{type}`
+/// and `{msm_id}`. `{start}` and `{end}` are the first and last result timestamps of a file.
+/// `_{type}` is appended to templates without `{type}`, and `_{msm_id}` when splitting by id.
+pub struct OutputLayout {
+    directory: PathBuf,
+    template: String,
+    campaign: String,
+    split_by_id: bool,
+}
+
+impl OutputLayout {
+    pub fn new(directory: PathBuf, template: String, campaign: String, split_by_id: bool) -> Self {
+        OutputLayout {
+            directory,
+            template,
+            campaign,
+            split_by_id,
+        }
+    }
+
+    /// Groups the measurements into the files they should be written to. The returned paths
+    /// have no extension, every saver appends its own.
+    pub fn partition<'a>(
+        &self,
+        measurements: &'a [AggregatedMeasurement],
+    ) -> Vec<(PathBuf, Vec<&'a AggregatedMeasurement>)> {
+        let mut buckets: HashMap<(&str, Option<u32>), Vec<&AggregatedMeasurement>> = HashMap::new();
+
+        for item in measurements {
+            let msm_id = self.split_by_id.then(|| item.msm_id());
+            buckets.entry((item.kind(), msm_id)).or_default().push(item);
+        }
+
+        buckets
+            .into_iter()
+            .map(|((kind, msm_id), bucket)| (self.file_stem(kind, msm_id, &bucket), bucket))
+            .collect()
+    }
+
+    /// Names a single file holding every measurement, for outputs that do not split by type.
+    pub fn single_file(&self, kind: &str, measurements: &[AggregatedMeasurement]) -> PathBuf {
+        let all: Vec<&AggregatedMeasurement> = measurements.iter().collect();
+        self.file_stem(kind, None, &all)
+    }
+
+    pub fn campaign(&self) -> &str {
+        &self.campaign
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    fn file_stem(
+        &self,
+        kind: &str,
+        msm_id: Option<u32>,
+        bucket: &[&AggregatedMeasurement],
+    ) -> PathBuf {
+        let start = bucket.iter().map(|m| m.timestamp()).min();
+        let end = bucket.iter().map(|m| m.timestamp()).max();
+
+        // Every type gets a file of its own, so a template without {type} would have the savers
+        // overwrite one type with the next.
+        let mut template = self.template.clone();
+        if !template.contains("{type}") {
+            template.push_str("_{type}");
+        }
+        if msm_id.is_some() && !template.contains("{msm_id}") {
+            template.push_str("_{msm_id}");
+        }
+
+        let msm_id = match msm_id {
+            Some(id) => id.to_string(),
+            None => match bucket.first().map(|m| m.msm_id()) {
+                Some(id) if bucket.iter().all(|m| m.msm_id() == id) => id.to_string(),
+                _ => "all".to_string(),
+            },
+        };
+
+        let name = template
+            .replace("{campaign}", &self.campaign)
+            .replace("{start}", &format_timestamp(start))
+            .replace("{end}", &format_timestamp(end))
+            .replace("{type}", kind)
+            .replace("{msm_id}", &msm_id);
+
+        self.directory.join(name)
+    }
+}
+
+/// Appends an extension without touching dots the rendered template may already contain.
+pub fn with_extension(stem: &Path, extension: &str) -> PathBuf {
+    let mut path = stem.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+fn format_timestamp(timestamp: Option<usize>) -> String {
+    timestamp
+        .and_then(|t| DateTime::from_timestamp(t as i64, 0))
+        .map(|t| t.format("%Y%m%dT%H%M%SZ").to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ping, traceroute};
+
+    fn layout(template: &str, split_by_id: bool) -> OutputLayout {
+        OutputLayout::new(
+            PathBuf::from("out"),
+            template.to_string(),
+            "campaign".to_string(),
+            split_by_id,
+        )
+    }
+
+    fn stems(layout: &OutputLayout, measurements: &[AggregatedMeasurement]) -> Vec<PathBuf> {
+        let mut stems: Vec<PathBuf> = layout
+            .partition(measurements)
+            .into_iter()
+            .map(|(stem, _)| stem)
+            .collect();
+        stems.sort();
+        stems
+    }
+
+    #[test]
+    fn every_type_gets_its_own_file() {
+        let measurements = [
+            ping(1001, 10, 1_700_000_000, &[Some(1.0)]),
+            ping(1002, 10, 1_700_000_060, &[Some(1.0)]),
+            traceroute(2001, 10, 1_700_000_000, &["192.0.2.1"], true),
+        ];
+
+        assert_eq!(
+            stems(&layout("{campaign}", false), &measurements),
+            [
+                PathBuf::from("out/campaign_ping"),
+                PathBuf::from("out/campaign_traceroute")
+            ]
+        );
+        assert_eq!(
+            stems(&layout("{type}_{start}_{end}", false), &measurements),
+            [
+                PathBuf::from("out/ping_20231114T221320Z_20231114T221420Z"),
+                PathBuf::from("out/traceroute_20231114T221320Z_20231114T221320Z")
+            ]
+        );
+        assert_eq!(
+            stems(&layout("{campaign}", true), &measurements),
+            [
+                PathBuf::from("out/campaign_ping_1001"),
+                PathBuf::from("out/campaign_ping_1002"),
+                PathBuf::from("out/campaign_traceroute_2001")
+            ]
+        );
+        assert_eq!(
+            layout("{campaign}", false).single_file("distances", &measurements),
+            PathBuf::from("out/campaign_distances")
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    error::Error,
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use common::measurement_ids::MeasurementIds;
+use serde::Serialize;
+
+use crate::api::{
+    fetch_measurement_data::{self, MeasurementData, SkippedResult},
+    results::AggregatedMeasurement,
+};
+
+pub mod alert_sink;
+pub mod cache;
+pub mod csv_saver;
+pub mod jsonl_saver;
+pub mod layout;
+pub mod parquet_saver;
+pub mod prometheus;
+pub mod sqlite_saver;
+
+pub trait MeasurementSaver {
+    fn save_by_type(&self, measurements: &[AggregatedMeasurement]) -> Result<(), Box<dyn Error>>;
+    fn save_to(
+        &self,
+        path: &Path,
+        measurement: &[&AggregatedMeasurement],
+    ) -> Result<(), Box<dyn Error>>;
+}
+
+/// Opens an output file, truncating it or, with `append`, appending to it. Also returns whether
+/// the file already had content, so writers know to leave out headers.
+pub fn open_output(path: &Path, append: bool) -> Result<(fs::File, bool), std::io::Error> {
+    let existing = append && fs::metadata(path).is_ok_and(|metadata| metadata.len() > 0);
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)?;
+    Ok((file, existing))
+}
+
+pub fn read_measurement_ids_from_file(file_path: &str) -> Result<MeasurementIds, Box<dyn Error>> {
+    let content = fs::read_to_string(file_path)?;
+    let measurement_ids: MeasurementIds = toml::from_str(&content)?;
+    Ok(measurement_ids)
+}
+
+/// Reads results written by the JSON Lines saver. Lines that do not parse are returned as
+/// skipped instead of failing the whole file.
+pub fn read_jsonl(path: &Path) -> Result<MeasurementData, Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+    let entries = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+    Ok(fetch_measurement_data::parse_results(
+        &path.display().to_string(),
+        entries,
+    ))
+}
+
+/// Writes analysis output such as summary tables as CSV, one row per item.
+pub fn write_csv<T: Serialize>(path: &Path, rows: &[T]) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes analysis output in the given format, `csv` or pretty printed `json`.
+pub fn write_table<T: Serialize>(
+    path: &Path,
+    format: &str,
+    rows: &[T],
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        "csv" => write_csv(path, rows),
+        "json" => Ok(fs::write(path, serde_json::to_string_pretty(rows)?)?),
+        _ => Err(format!("Unsupported output format: {}", format).into()),
+    }
+}
+
+/// Writes results that failed to parse as JSON Lines, one object per result holding the
+/// measurement id, the parse error and the raw result.
+pub fn write_quarantine(
+    path: &Path,
+    skipped: &[SkippedResult],
+    append: bool,
+) -> Result<(), Box<dyn Error>> {
+    let (file, _) = open_output(path, append)?;
+    let mut writer = BufWriter::new(file);
+
+    for entry in skipped {
+        serde_json::to_writer(&mut writer, entry)?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,557 @@
+use arrow_array::{
+    Array, ArrayRef, BooleanArray, Float32Array, Float64Array, RecordBatch, StringArray,
+    TimestampSecondArray, UInt32Array,
+};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    analysis::distance,
+    api::results::{
+        AggregatedMeasurement, FlattenedHttpMeasurement, FlattenedPingMeasurement,
+        FlattenedTraceRouteMeasurement, Outcome,
+    },
+    enrichment::{ProbeColumns, ProbeDirectory},
+    io::{
+        MeasurementSaver,
+        layout::{OutputLayout, with_extension},
+    },
+};
+
+/// Writes one Parquet file per measurement type with typed columns. Timestamps are stored as
+/// UTC timestamps and missing RTTs as nulls.
+pub struct ParquetSaver {
+    layout: OutputLayout,
+    append: bool,
+    probes: Option<Arc<ProbeDirectory>>,
+}
+
+impl ParquetSaver {
+    pub fn new(layout: OutputLayout) -> Self {
+        ParquetSaver {
+            layout,
+            append: false,
+            probes: None,
+        }
+    }
+
+    /// Keep existing files. Parquet files cannot be extended, so every later batch goes into
+    /// the next free `_partN` file next to the first one.
+    pub fn append(mut self, val: bool) -> Self {
+        self.append = val;
+        self
+    }
+
+    /// Append source and target probe metadata columns to every file.
+    pub fn probe_metadata(mut self, val: Option<Arc<ProbeDirectory>>) -> Self {
+        self.probes = val;
+        self
+    }
+
+    fn probe_columns<'a>(
+        &self,
+        rows: impl Iterator<Item = (u32, Option<&'a str>, Option<f32>)>,
+    ) -> Vec<Column> {
+        match &self.probes {
+            Some(probes) => {
+                let rows: Vec<ProbeColumns> = rows
+                    .map(|(prb_id, dst_addr, rtt)| probes.columns(prb_id, dst_addr, rtt))
+                    .collect();
+                probe_columns(&rows)
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+type Column = (&'static str, ArrayRef, bool);
+
+impl MeasurementSaver for ParquetSaver {
+    fn save_by_type(&self, measurements: &[AggregatedMeasurement]) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(self.layout.directory())?;
+
+        for (stem, bucket) in self.layout.partition(measurements) {
+            self.save_to(&with_extension(&stem, "parquet"), &bucket)?;
+        }
+
+        Ok(())
+    }
+
+    fn save_to(
+        &self,
+        path: &Path,
+        measurements: &[&AggregatedMeasurement],
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(first) = measurements.first() else {
+            return Ok(());
+        };
+        if measurements.iter().any(|m| m.kind() != first.kind()) {
+            return Err("A Parquet file can only hold a single measurement type".into());
+        }
+
+        let columns = match first {
+            AggregatedMeasurement::Ping(_) => {
+                let rows: Vec<FlattenedPingMeasurement> = measurements
+                    .iter()
+                    .filter_map(|m| match m {
+                        AggregatedMeasurement::Ping(p) => {
+                            Some(FlattenedPingMeasurement::from_ping_measurement(p))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                let mut columns = ping_columns(&rows);
+                columns.extend(
+                    self.probe_columns(
+                        rows.iter()
+                            .map(|r| (r.prb_id, Some(r.dst_addr.as_str()), r.min)),
+                    ),
+                );
+                columns
+            }
+            AggregatedMeasurement::Http(_) => {
+                let rows: Vec<FlattenedHttpMeasurement> = measurements
+                    .iter()
+                    .filter_map(|m| match m {
+                        AggregatedMeasurement::Http(h) => {
+                            Some(FlattenedHttpMeasurement::from_http_measurement(h))
+                        }
+                        _ => None,
+                    })
+                    .flatten()
+                    .collect();
+                let mut columns = http_columns(&rows);
+                columns.extend(
+                    self.probe_columns(
+                        rows.iter()
+                            .map(|r| (r.prb_id, r.dst_addr.as_deref(), r.ttc)),
+                    ),
+                );
+                columns
+            }
+            AggregatedMeasurement::TraceRoute(_) => {
+                let rows: Vec<FlattenedTraceRouteMeasurement> = measurements
+                    .iter()
+                    .filter_map(|m| match m {
+                        AggregatedMeasurement::TraceRoute(t) => Some(
+                            FlattenedTraceRouteMeasurement::from_traceroute_measurement(t),
+                        ),
+                        _ => None,
+                    })
+                    .flatten()
+                    .collect();
+                let mut columns = traceroute_columns(&rows);
+                columns.extend(self.probe_columns(rows.iter().map(|r| {
+                    (
+                        r.prb_id,
+                        Some(r.dst_addr.as_str()),
+                        distance::final_hop_rtt(r),
+                    )
+                })));
+                columns
+            }
+        };
+        let batch = RecordBatch::try_from_iter_with_nullable(columns)?;
+
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let path = if self.append {
+            free_part(path)
+        } else {
+            path.to_path_buf()
+        };
+        let file = fs::File::create(path)?;
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties))?;
+        writer.write(&batch)?;
+        writer.close()?;
+
+        Ok(())
+    }
+}
+
+/// `path` itself if it does not exist yet, otherwise the first `{stem}_partN.parquet` that does
+/// not.
+fn free_part(path: &Path) -> PathBuf {
+    let stem = path.with_extension("");
+    (0..)
+        .map(|n| match n {
+            0 => path.to_path_buf(),
+            n => {
+                let mut part = stem.as_os_str().to_owned();
+                part.push(format!("_part{}", n));
+                with_extension(&PathBuf::from(part), "parquet")
+            }
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+fn required(name: &'static str, array: impl Array + 'static) -> Column {
+    (name, Arc::new(array), false)
+}
+
+fn nullable(name: &'static str, array: impl Array + 'static) -> Column {
+    (name, Arc::new(array), true)
+}
+
+fn timestamps(values: Vec<i64>) -> TimestampSecondArray {
+    TimestampSecondArray::from(values).with_timezone("UTC")
+}
+
+fn outcomes<'a>(values: impl Iterator<Item = &'a Outcome>) -> StringArray {
+    StringArray::from_iter_values(values.map(Outcome::as_str))
+}
+
+fn ping_columns(rows: &[FlattenedPingMeasurement]) -> Vec<Column> {
+    vec![
+        required(
+            "dst_addr",
+            StringArray::from_iter_values(rows.iter().map(|r| &r.dst_addr)),
+        ),
+        required(
+            "src_addr",
+            StringArray::from_iter_values(rows.iter().map(|r| &r.src_addr)),
+        ),
+        required(
+            "proto",
+            StringArray::from_iter_values(rows.iter().map(|r| &r.proto)),
+        ),
+        required(
+            "rcvd",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.rcvd)),
+        ),
+        required(
+            "sent",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.sent)),
+        ),
+        nullable("min", Float32Array::from_iter(rows.iter().map(|r| r.min))),
+        nullable("max", Float32Array::from_iter(rows.iter().map(|r| r.max))),
+        nullable("avg", Float32Array::from_iter(rows.iter().map(|r| r.avg))),
+        required(
+            "msm_id",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.msm_id)),
+        ),
+        required(
+            "timestamp",
+            timestamps(rows.iter().map(|r| r.timestamp as i64).collect()),
+        ),
+        required(
+            "prb_id",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.prb_id)),
+        ),
+        required("outcome", outcomes(rows.iter().map(|r| &r.outcome))),
+        nullable("loss", Float32Array::from_iter(rows.iter().map(|r| r.loss))),
+        nullable(
+            "stddev",
+            Float32Array::from_iter(rows.iter().map(|r| r.stddev)),
+        ),
+        nullable(
+            "jitter",
+            Float32Array::from_iter(rows.iter().map(|r| r.jitter)),
+        ),
+        required(
+            "duplicates",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.duplicates)),
+        ),
+        required(
+            "timeouts",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.timeouts)),
+        ),
+        required(
+            "errors",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.errors)),
+        ),
+    ]
+}
+
+fn http_columns(rows: &[FlattenedHttpMeasurement]) -> Vec<Column> {
+    vec![
+        nullable("ttr", Float32Array::from_iter(rows.iter().map(|r| r.ttr))),
+        nullable(
+            "method",
+            StringArray::from_iter(rows.iter().map(|r| r.method.as_deref())),
+        ),
+        nullable(
+            "dst_addr",
+            StringArray::from_iter(rows.iter().map(|r| r.dst_addr.as_deref())),
+        ),
+        nullable(
+            "src_addr",
+            StringArray::from_iter(rows.iter().map(|r| r.src_addr.as_deref())),
+        ),
+        nullable("rt", Float32Array::from_iter(rows.iter().map(|r| r.rt))),
+        nullable("res", UInt32Array::from_iter(rows.iter().map(|r| r.res))),
+        nullable(
+            "ver",
+            StringArray::from_iter(rows.iter().map(|r| r.ver.as_deref())),
+        ),
+        nullable(
+            "hsize",
+            UInt32Array::from_iter(rows.iter().map(|r| r.hsize)),
+        ),
+        nullable(
+            "bsize",
+            UInt32Array::from_iter(rows.iter().map(|r| r.bsize)),
+        ),
+        required(
+            "msm_id",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.msm_id)),
+        ),
+        required(
+            "timestamp",
+            timestamps(rows.iter().map(|r| r.timestamp as i64).collect()),
+        ),
+        required(
+            "prb_id",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.prb_id)),
+        ),
+        required("outcome", outcomes(rows.iter().map(|r| &r.outcome))),
+        required(
+            "result_index",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.result_index)),
+        ),
+        nullable(
+            "uri",
+            StringArray::from_iter(rows.iter().map(|r| r.uri.as_deref())),
+        ),
+        nullable("ttc", Float32Array::from_iter(rows.iter().map(|r| r.ttc))),
+        nullable("ttfb", Float32Array::from_iter(rows.iter().map(|r| r.ttfb))),
+        nullable(
+            "read_time",
+            Float32Array::from_iter(rows.iter().map(|r| r.read_time)),
+        ),
+        nullable(
+            "error_kind",
+            StringArray::from_iter(rows.iter().map(|r| r.error_kind.map(|k| k.as_str()))),
+        ),
+        nullable(
+            "error",
+            StringArray::from_iter(rows.iter().map(|r| r.error.as_deref())),
+        ),
+    ]
+}
+
+fn traceroute_columns(rows: &[FlattenedTraceRouteMeasurement]) -> Vec<Column> {
+    vec![
+        required(
+            "msm_id",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.msm_id)),
+        ),
+        required(
+            "src_addr",
+            StringArray::from_iter_values(rows.iter().map(|r| &r.src_addr)),
+        ),
+        required(
+            "dst_addr",
+            StringArray::from_iter_values(rows.iter().map(|r| &r.dst_addr)),
+        ),
+        required(
+            "hop",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.hop)),
+        ),
+        required(
+            "sent",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.sent)),
+        ),
+        required(
+            "received",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.received)),
+        ),
+        nullable("min", Float32Array::from_iter(rows.iter().map(|r| r.min))),
+        nullable("avg", Float32Array::from_iter(rows.iter().map(|r| r.avg))),
+        nullable("max", Float32Array::from_iter(rows.iter().map(|r| r.max))),
+        nullable(
+            "from",
+            StringArray::from_iter(rows.iter().map(|r| r.from.as_deref())),
+        ),
+        required(
+            "timed_out",
+            BooleanArray::from_iter(rows.iter().map(|r| Some(r.timed_out))),
+        ),
+        required("outcome", outcomes(rows.iter().map(|r| &r.outcome))),
+        required(
+            "timestamp",
+            timestamps(rows.iter().map(|r| r.timestamp as i64).collect()),
+        ),
+        required(
+            "prb_id",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.prb_id)),
+        ),
+        required(
+            "endtime",
+            timestamps(rows.iter().map(|r| r.endtime as i64).collect()),
+        ),
+        required(
+            "proto",
+            StringArray::from_iter_values(rows.iter().map(|r| &r.proto)),
+        ),
+        required(
+            "paris_id",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.paris_id)),
+        ),
+        required(
+            "destination_ip_responded",
+            BooleanArray::from_iter(rows.iter().map(|r| Some(r.destination_ip_responded))),
+        ),
+        nullable(
+            "addresses",
+            StringArray::from_iter(rows.iter().map(|r| r.addresses.as_deref())),
+        ),
+        required(
+            "address_count",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.address_count)),
+        ),
+        required(
+            "late",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.late)),
+        ),
+        required(
+            "duplicates",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.duplicates)),
+        ),
+        required(
+            "errors",
+            UInt32Array::from_iter_values(rows.iter().map(|r| r.errors)),
+        ),
+        nullable(
+            "mpls",
+            StringArray::from_iter(rows.iter().map(|r| r.mpls.as_deref())),
+        ),
+        nullable(
+            "error",
+            StringArray::from_iter(rows.iter().map(|r| r.error.as_deref())),
+        ),
+    ]
+}
+
+fn probe_columns(rows: &[ProbeColumns]) -> Vec<Column> {
+    vec![
+        nullable(
+            "src_country",
+            StringArray::from_iter(rows.iter().map(|r| r.src_country.as_deref())),
+        ),
+        nullable(
+            "src_asn_v4",
+            UInt32Array::from_iter(rows.iter().map(|r| r.src_asn_v4)),
+        ),
+        nullable(
+            "src_asn_v6",
+            UInt32Array::from_iter(rows.iter().map(|r| r.src_asn_v6)),
+        ),
+        nullable(
+            "src_latitude",
+            Float64Array::from_iter(rows.iter().map(|r| r.src_latitude)),
+        ),
+        nullable(
+            "src_longitude",
+            Float64Array::from_iter(rows.iter().map(|r| r.src_longitude)),
+        ),
+        nullable(
+            "src_is_anchor",
+            BooleanArray::from_iter(rows.iter().map(|r| r.src_is_anchor)),
+        ),
+        nullable(
+            "src_fqdn",
+            StringArray::from_iter(rows.iter().map(|r| r.src_fqdn.as_deref())),
+        ),
+        nullable(
+            "dst_prb_id",
+            UInt32Array::from_iter(rows.iter().map(|r| r.dst_prb_id)),
+        ),
+        nullable(
+            "dst_country",
+            StringArray::from_iter(rows.iter().map(|r| r.dst_country.as_deref())),
+        ),
+        nullable(
+            "dst_asn_v4",
+            UInt32Array::from_iter(rows.iter().map(|r| r.dst_asn_v4)),
+        ),
+        nullable(
+            "dst_asn_v6",
+            UInt32Array::from_iter(rows.iter().map(|r| r.dst_asn_v6)),
+        ),
+        nullable(
+            "dst_latitude",
+            Float64Array::from_iter(rows.iter().map(|r| r.dst_latitude)),
+        ),
+        nullable(
+            "dst_longitude",
+            Float64Array::from_iter(rows.iter().map(|r| r.dst_longitude)),
+        ),
+        nullable(
+            "dst_is_anchor",
+            BooleanArray::from_iter(rows.iter().map(|r| r.dst_is_anchor)),
+        ),
+        nullable(
+            "dst_fqdn",
+            StringArray::from_iter(rows.iter().map(|r| r.dst_fqdn.as_deref())),
+        ),
+        nullable(
+            "distance_km",
+            Float64Array::from_iter(rows.iter().map(|r| r.distance_km)),
+        ),
+        nullable(
+            "fibre_rtt",
+            Float64Array::from_iter(rows.iter().map(|r| r.fibre_rtt)),
+        ),
+        nullable(
+            "rtt_inflation",
+            Float64Array::from_iter(rows.iter().map(|r| r.rtt_inflation)),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::UInt32Type;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use crate::test_support::{ping, temp_dir};
+
+    #[test]
+    fn ping_columns_match_csv_columns() {
+        let dir = temp_dir("parquet-ping");
+        let path = dir.join("ping.parquet");
+        let measurement = ping(1, 10, 100, &[Some(1.0), None, Some(3.0)]);
+        let saver = ParquetSaver::new(OutputLayout::new(
+            dir,
+            "{type}".to_string(),
+            "test".to_string(),
+            false,
+        ));
+        saver.save_to(&path, &[&measurement]).unwrap();
+
+        let batch = ParquetRecordBatchReaderBuilder::try_new(fs::File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let mut columns: Vec<String> = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        columns.sort();
+
+        let AggregatedMeasurement::Ping(p) = &measurement else {
+            unreachable!()
+        };
+        let row = serde_json::to_value(FlattenedPingMeasurement::from_ping_measurement(p)).unwrap();
+        let csv_columns: Vec<String> = row.as_object().unwrap().keys().cloned().collect();
+        assert_eq!(columns, csv_columns);
+
+        let timeouts = batch.column_by_name("timeouts").unwrap();
+        assert_eq!(timeouts.as_primitive::<UInt32Type>().value(0), 1);
+    }
+}
@@ -0,0 +1,222 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+    analysis::{paths::TracePath, samples::Samples, stats},
+    api::results::AggregatedMeasurement,
+    enrichment::ProbeDirectory,
+};
+
+/// The latest values of one pair of one measurement.
+#[derive(Debug)]
+struct Latest {
+    timestamp: usize,
+    rtt: Option<f32>,
+    loss: Option<f32>,
+    http_status: Option<u32>,
+    hop_count: Option<usize>,
+}
+
+/// Latest result per measurement, source probe and target, rendered in the Prometheus text
+/// exposition format.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    latest: BTreeMap<(String, u32, u32, String), Latest>,
+    results: BTreeMap<(String, u32), u64>,
+}
+
+const GAUGES: [(&str, &str); 5] = [
+    (
+        "ripe_atlas_rtt_milliseconds",
+        "Median RTT of the latest result of the pair",
+    ),
+    (
+        "ripe_atlas_loss_ratio",
+        "Share of probes without an answer in the latest result of the pair",
+    ),
+    (
+        "ripe_atlas_http_status",
+        "HTTP status code of the latest request of the pair",
+    ),
+    (
+        "ripe_atlas_traceroute_hops",
+        "Number of hops of the latest traceroute of the pair",
+    ),
+    (
+        "ripe_atlas_result_timestamp_seconds",
+        "Unix time of the latest result of the pair",
+    ),
+];
+
+impl Metrics {
+    /// Takes over every result that is newer than what is known for its pair.
+    pub fn update(&mut self, measurements: &[AggregatedMeasurement]) {
+        for measurement in measurements {
+            *self
+                .results
+                .entry((measurement.kind().to_string(), measurement.msm_id()))
+                .or_default() += 1;
+
+            let Some(dst_addr) = measurement.dst_addr() else {
+                continue;
+            };
+            let key = (
+                measurement.kind().to_string(),
+                measurement.msm_id(),
+                measurement.prb_id(),
+                dst_addr.to_string(),
+            );
+            if self
+                .latest
+                .get(&key)
+                .is_some_and(|latest| latest.timestamp > measurement.timestamp())
+            {
+                continue;
+            }
+
+            let samples = Samples::from_measurement(measurement);
+            self.latest.insert(
+                key,
+                Latest {
+                    timestamp: measurement.timestamp(),
+                    rtt: stats::median(&samples.rtts),
+                    loss: samples.loss().map(|loss| loss / 100.0),
+                    http_status: match measurement {
+                        AggregatedMeasurement::Http(h) => h.result.iter().find_map(|r| r.res),
+                        _ => None,
+                    },
+                    hop_count: match measurement {
+                        AggregatedMeasurement::TraceRoute(t) => {
+                            Some(TracePath::from_traceroute_measurement(t).length())
+                        }
+                        _ => None,
+                    },
+                },
+            );
+        }
+    }
+
+    /// Renders all gauges, labelled with type, msm_id, source probe and its country, and
+    /// target address and the country of the probe behind it.
+    pub fn render(&self, probes: &ProbeDirectory) -> String {
+        let mut out = String::new();
+
+        for (index, (name, help)) in GAUGES.iter().enumerate() {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            for ((kind, msm_id, prb_id, dst_addr), latest) in &self.latest {
+                let value = match index {
+                    0 => latest.rtt.map(f64::from),
+                    1 => latest.loss.map(f64::from),
+                    2 => latest.http_status.map(f64::from),
+                    3 => latest.hop_count.map(|hops| hops as f64),
+                    _ => Some(latest.timestamp as f64),
+                };
+                let Some(value) = value else {
+                    continue;
+                };
+                let country = probes
+                    .probe(*prb_id)
+                    .and_then(|p| p.country_code.as_deref());
+                let target_country = probes
+                    .by_address(dst_addr)
+                    .and_then(|p| p.country_code.as_deref());
+                let _ = writeln!(
+                    out,
+                    "{}{{type=\"{}\",msm_id=\"{}\",prb_id=\"{}\",country=\"{}\",target=\"{}\",target_country=\"{}\"}} {}",
+                    name,
+                    escape(kind),
+                    msm_id,
+                    prb_id,
+                    escape(country.unwrap_or_default()),
+                    escape(dst_addr),
+                    escape(target_country.unwrap_or_default()),
+                    value
+                );
+            }
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP ripe_atlas_results_total Results received since the exporter started"
+        );
+        let _ = writeln!(out, "# TYPE ripe_atlas_results_total counter");
+        for ((kind, msm_id), count) in &self.results {
+            let _ = writeln!(
+                out,
+                "ripe_atlas_results_total{{type=\"{}\",msm_id=\"{}\"}} {}",
+                escape(kind),
+                msm_id,
+                count
+            );
+        }
+
+        out
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{http, ping};
+    use serde_json::json;
+
+    fn samples(rendered: &str) -> Vec<&str> {
+        rendered
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect()
+    }
+
+    #[test]
+    fn renders_the_latest_result_of_every_pair() {
+        let probes: ProbeDirectory = serde_json::from_value(json!({
+            "probes": {
+                "10": { "prb_id": 10, "is_anchor": false, "country_code": "DE" },
+                "20": { "prb_id": 20, "is_anchor": true, "country_code": "NL" },
+            },
+            "addresses": { "192.0.2.1": 20 },
+        }))
+        .unwrap();
+        let mut metrics = Metrics::default();
+        metrics.update(&[
+            ping(1001, 10, 100, &[Some(10.0), None]),
+            http(3001, 10, 100, Some(200)),
+        ]);
+        let labels = r#"{type="ping",msm_id="1001",prb_id="10",country="DE",target="192.0.2.1",target_country="NL"}"#;
+        let rendered = metrics.render(&probes);
+        assert!(rendered.contains(&format!("ripe_atlas_rtt_milliseconds{labels} 10\n")));
+        assert!(rendered.contains(&format!("ripe_atlas_loss_ratio{labels} 0.5\n")));
+
+        // A newer result replaces the values of its pair, an older one is ignored.
+        metrics.update(&[ping(1001, 10, 300, &[Some(20.0), Some(30.0)])]);
+        metrics.update(&[ping(1001, 10, 200, &[None, None])]);
+        let rendered = metrics.render(&probes);
+        let http = r#"{type="http",msm_id="3001",prb_id="10",country="DE",target="192.0.2.1",target_country="NL"}"#;
+        assert_eq!(
+            samples(&rendered),
+            [
+                format!("ripe_atlas_rtt_milliseconds{http} 20"),
+                format!("ripe_atlas_rtt_milliseconds{labels} 25"),
+                format!("ripe_atlas_loss_ratio{http} 0"),
+                format!("ripe_atlas_loss_ratio{labels} 0"),
+                format!("ripe_atlas_http_status{http} 200"),
+                format!("ripe_atlas_result_timestamp_seconds{http} 100"),
+                format!("ripe_atlas_result_timestamp_seconds{labels} 300"),
+                r#"ripe_atlas_results_total{type="http",msm_id="3001"} 1"#.to_string(),
+                r#"ripe_atlas_results_total{type="ping",msm_id="1001"} 3"#.to_string(),
+            ]
+        );
+
+        // Without metadata the country labels stay empty.
+        assert!(metrics.render(&ProbeDirectory::default()).contains(
+            r#"ripe_atlas_http_status{type="http",msm_id="3001",prb_id="10",country="",target="192.0.2.1",target_country=""} 200"#
+        ));
+    }
+}
@@ -0,0 +1,837 @@
+use rusqlite::{Connection, Transaction, params};
+use std::{error::Error, fs, path::Path, sync::Arc};
+
+use crate::{
+    api::results::{
+        AggregatedMeasurement, FlattenedHopReply, FlattenedHttpMeasurement, FlattenedPingPacket,
+        FlattenedTraceRouteMeasurement, HttpMeasurement, PingMeasurement, TraceRouteMeasurement,
+    },
+    enrichment::ProbeDirectory,
+    io::{
+        MeasurementSaver,
+        layout::{OutputLayout, with_extension},
+    },
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS measurements (
+    msm_id INTEGER PRIMARY KEY,
+    type TEXT NOT NULL,
+    campaign TEXT NOT NULL,
+    first_timestamp INTEGER NOT NULL,
+    last_timestamp INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS probes (
+    prb_id INTEGER PRIMARY KEY,
+    src_addr TEXT NOT NULL,
+    last_seen INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS probe_metadata (
+    prb_id INTEGER PRIMARY KEY,
+    country_code TEXT,
+    asn_v4 INTEGER,
+    asn_v6 INTEGER,
+    latitude REAL,
+    longitude REAL,
+    is_anchor INTEGER NOT NULL,
+    fqdn TEXT
+);
+
+CREATE TABLE IF NOT EXISTS target_addresses (
+    address TEXT PRIMARY KEY,
+    prb_id INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS ping_results (
+    msm_id INTEGER NOT NULL,
+    prb_id INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    src_addr TEXT NOT NULL,
+    dst_addr TEXT NOT NULL,
+    proto TEXT NOT NULL,
+    sent INTEGER NOT NULL,
+    rcvd INTEGER NOT NULL,
+    min REAL,
+    avg REAL,
+    max REAL,
+    PRIMARY KEY (msm_id, prb_id, timestamp)
+);
+
+CREATE TABLE IF NOT EXISTS ping_packets (
+    msm_id INTEGER NOT NULL,
+    prb_id INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    seq INTEGER NOT NULL,
+    from_addr TEXT NOT NULL,
+    rtt REAL,
+    ttl INTEGER,
+    dup INTEGER NOT NULL,
+    timed_out INTEGER NOT NULL,
+    error TEXT,
+    PRIMARY KEY (msm_id, prb_id, timestamp, seq)
+);
+
+CREATE TABLE IF NOT EXISTS http_results (
+    msm_id INTEGER NOT NULL,
+    prb_id INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    seq INTEGER NOT NULL,
+    uri TEXT,
+    ttr REAL,
+    method TEXT,
+    src_addr TEXT,
+    dst_addr TEXT,
+    rt REAL,
+    res INTEGER,
+    ver TEXT,
+    hsize INTEGER,
+    bsize INTEGER,
+    ttc REAL,
+    ttfb REAL,
+    read_time REAL,
+    outcome TEXT NOT NULL,
+    error_kind TEXT,
+    error TEXT,
+    PRIMARY KEY (msm_id, prb_id, timestamp, seq)
+);
+
+CREATE TABLE IF NOT EXISTS traceroutes (
+    msm_id INTEGER NOT NULL,
+    prb_id INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    endtime INTEGER NOT NULL,
+    src_addr TEXT NOT NULL,
+    dst_addr TEXT NOT NULL,
+    proto TEXT NOT NULL,
+    paris_id INTEGER NOT NULL,
+    destination_ip_responded INTEGER NOT NULL,
+    PRIMARY KEY (msm_id, prb_id, timestamp)
+);
+
+CREATE TABLE IF NOT EXISTS traceroute_hops (
+    msm_id INTEGER NOT NULL,
+    prb_id INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    hop INTEGER NOT NULL,
+    sent INTEGER NOT NULL,
+    received INTEGER NOT NULL,
+    min REAL,
+    avg REAL,
+    max REAL,
+    addresses TEXT,
+    address_count INTEGER NOT NULL,
+    error TEXT,
+    PRIMARY KEY (msm_id, prb_id, timestamp, hop)
+);
+
+CREATE TABLE IF NOT EXISTS hop_replies (
+    msm_id INTEGER NOT NULL,
+    prb_id INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    hop INTEGER NOT NULL,
+    seq INTEGER NOT NULL,
+    from_addr TEXT,
+    ttl INTEGER,
+    size INTEGER,
+    rtt REAL,
+    timed_out INTEGER NOT NULL,
+    late INTEGER,
+    dup INTEGER NOT NULL,
+    err TEXT,
+    itos INTEGER,
+    ittl INTEGER,
+    flags TEXT,
+    mpls TEXT,
+    PRIMARY KEY (msm_id, prb_id, timestamp, hop, seq)
+);
+
+CREATE INDEX IF NOT EXISTS ping_results_prb_id ON ping_results (prb_id);
+CREATE INDEX IF NOT EXISTS ping_results_timestamp ON ping_results (timestamp);
+CREATE INDEX IF NOT EXISTS http_results_prb_id ON http_results (prb_id);
+CREATE INDEX IF NOT EXISTS http_results_timestamp ON http_results (timestamp);
+CREATE INDEX IF NOT EXISTS traceroutes_prb_id ON traceroutes (prb_id);
+CREATE INDEX IF NOT EXISTS traceroutes_timestamp ON traceroutes (timestamp);
+";
+
+/// Version of [`SCHEMA`], kept in `PRAGMA user_version`. Tables added later need no migration,
+/// [`SCHEMA`] creates whatever is missing.
+const SCHEMA_VERSION: i32 = 4;
+
+/// The statements that bring a database from version `index + 1` to `index + 2`.
+const MIGRATIONS: [&str; 3] = [
+    // 2: ping replies with their address, TTL and flags
+    "
+    ALTER TABLE ping_packets ADD COLUMN from_addr TEXT NOT NULL DEFAULT '';
+    ALTER TABLE ping_packets ADD COLUMN ttl INTEGER;
+    ALTER TABLE ping_packets ADD COLUMN dup INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE ping_packets ADD COLUMN timed_out INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE ping_packets ADD COLUMN error TEXT;
+    UPDATE ping_packets SET timed_out = rtt IS NULL;
+    ",
+    // 3: failed HTTP requests, which lack columns that used to be NOT NULL, so the table is
+    // rebuilt
+    "
+    CREATE TABLE http_results_migrated (
+        msm_id INTEGER NOT NULL,
+        prb_id INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL,
+        seq INTEGER NOT NULL,
+        uri TEXT,
+        ttr REAL,
+        method TEXT,
+        src_addr TEXT,
+        dst_addr TEXT,
+        rt REAL,
+        res INTEGER,
+        ver TEXT,
+        hsize INTEGER,
+        bsize INTEGER,
+        ttc REAL,
+        ttfb REAL,
+        read_time REAL,
+        outcome TEXT NOT NULL,
+        error_kind TEXT,
+        error TEXT,
+        PRIMARY KEY (msm_id, prb_id, timestamp, seq)
+    );
+    INSERT INTO http_results_migrated
+        (msm_id, prb_id, timestamp, seq, ttr, method, src_addr, dst_addr, rt, res, ver, hsize,
+         bsize, outcome)
+    SELECT msm_id, prb_id, timestamp, seq, ttr, method, src_addr, dst_addr, rt, res, ver, hsize,
+           bsize, CASE WHEN res IS NULL THEN 'error' ELSE 'ok' END
+    FROM http_results;
+    DROP TABLE http_results;
+    ALTER TABLE http_results_migrated RENAME TO http_results;
+    ",
+    // 4: traceroute hop addresses and reply details
+    "
+    ALTER TABLE traceroute_hops ADD COLUMN addresses TEXT;
+    ALTER TABLE traceroute_hops ADD COLUMN address_count INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE traceroute_hops ADD COLUMN error TEXT;
+    ALTER TABLE hop_replies ADD COLUMN late INTEGER;
+    ALTER TABLE hop_replies ADD COLUMN dup INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE hop_replies ADD COLUMN err TEXT;
+    ALTER TABLE hop_replies ADD COLUMN itos INTEGER;
+    ALTER TABLE hop_replies ADD COLUMN ittl INTEGER;
+    ALTER TABLE hop_replies ADD COLUMN flags TEXT;
+    ALTER TABLE hop_replies ADD COLUMN mpls TEXT;
+    ",
+];
+
+/// Creates the schema or migrates an existing database to [`SCHEMA_VERSION`].
+fn prepare(connection: &mut Connection) -> Result<(), Box<dyn Error>> {
+    let tx = connection.transaction()?;
+    let version = match tx.pragma_query_value(None, "user_version", |row| row.get(0))? {
+        0 => legacy_version(&tx)?,
+        version => version,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "Database schema version {} is newer than the supported version {}",
+            version, SCHEMA_VERSION
+        )
+        .into());
+    }
+
+    if version > 0 {
+        for migration in &MIGRATIONS[version as usize - 1..] {
+            tx.execute_batch(migration)?;
+        }
+    }
+    tx.execute_batch(SCHEMA)?;
+    tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Databases written before the version was recorded, told apart by the columns added since.
+/// Returns 0 for an empty database.
+fn legacy_version(tx: &Transaction) -> Result<i32, rusqlite::Error> {
+    let has_column = |table: &str, column: &str| -> Result<bool, rusqlite::Error> {
+        let mut statement = tx.prepare(&format!("PRAGMA table_info({})", table))?;
+        let names = statement.query_map([], |row| row.get::<_, String>(1))?;
+        for name in names {
+            if name? == column {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    };
+
+    Ok(if !has_column("measurements", "msm_id")? {
+        0
+    } else if has_column("hop_replies", "mpls")? {
+        4
+    } else if has_column("http_results", "outcome")? {
+        3
+    } else if has_column("ping_packets", "dup")? {
+        2
+    } else {
+        1
+    })
+}
+
+/// Stores the results in a normalized SQLite database. Rows are keyed by measurement, probe and
+/// timestamp, so fetching the same results again updates them instead of duplicating them.
+pub struct SqliteSaver {
+    layout: OutputLayout,
+    probes: Option<Arc<ProbeDirectory>>,
+}
+
+impl SqliteSaver {
+    pub fn new(layout: OutputLayout) -> Self {
+        SqliteSaver {
+            layout,
+            probes: None,
+        }
+    }
+
+    /// Also fill the `probe_metadata` and `target_addresses` tables, which join on `prb_id`
+    /// and `dst_addr`.
+    pub fn probe_metadata(mut self, val: Option<Arc<ProbeDirectory>>) -> Self {
+        self.probes = val;
+        self
+    }
+
+    fn insert_measurement(
+        &self,
+        tx: &Transaction,
+        measurement: &AggregatedMeasurement,
+    ) -> Result<(), rusqlite::Error> {
+        let timestamp = measurement.timestamp() as i64;
+        tx.execute(
+            "INSERT INTO measurements (msm_id, type, campaign, first_timestamp, last_timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?4)
+             ON CONFLICT (msm_id) DO UPDATE SET
+                type = excluded.type,
+                campaign = excluded.campaign,
+                first_timestamp = MIN(first_timestamp, excluded.first_timestamp),
+                last_timestamp = MAX(last_timestamp, excluded.last_timestamp)",
+            params![
+                measurement.msm_id(),
+                measurement.kind(),
+                self.layout.campaign(),
+                timestamp
+            ],
+        )?;
+
+        match measurement {
+            AggregatedMeasurement::Ping(p) => insert_ping(tx, p),
+            AggregatedMeasurement::Http(h) => insert_http(tx, h),
+            AggregatedMeasurement::TraceRoute(t) => insert_traceroute(tx, t),
+        }
+    }
+}
+
+impl MeasurementSaver for SqliteSaver {
+    fn save_by_type(&self, measurements: &[AggregatedMeasurement]) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(self.layout.directory())?;
+
+        let all: Vec<&AggregatedMeasurement> = measurements.iter().collect();
+        let stem = self.layout.single_file("measurements", measurements);
+        self.save_to(&with_extension(&stem, "sqlite"), &all)
+    }
+
+    fn save_to(
+        &self,
+        path: &Path,
+        measurements: &[&AggregatedMeasurement],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut connection = Connection::open(path)?;
+        prepare(&mut connection)?;
+
+        let tx = connection.transaction()?;
+        for measurement in measurements {
+            self.insert_measurement(&tx, measurement)?;
+        }
+        if let Some(probes) = &self.probes {
+            insert_probe_metadata(&tx, probes)?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+}
+
+fn insert_probe(
+    tx: &Transaction,
+    prb_id: u32,
+    src_addr: &str,
+    timestamp: usize,
+) -> Result<(), rusqlite::Error> {
+    tx.execute(
+        "INSERT INTO probes (prb_id, src_addr, last_seen) VALUES (?1, ?2, ?3)
+         ON CONFLICT (prb_id) DO UPDATE SET
+            src_addr = CASE WHEN excluded.last_seen >= last_seen
+                THEN excluded.src_addr ELSE src_addr END,
+            last_seen = MAX(last_seen, excluded.last_seen)",
+        params![prb_id, src_addr, timestamp as i64],
+    )?;
+    Ok(())
+}
+
+fn insert_probe_metadata(tx: &Transaction, probes: &ProbeDirectory) -> Result<(), rusqlite::Error> {
+    for probe in probes.probes() {
+        tx.execute(
+            "INSERT INTO probe_metadata
+                (prb_id, country_code, asn_v4, asn_v6, latitude, longitude, is_anchor, fqdn)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT (prb_id) DO UPDATE SET
+                country_code = excluded.country_code,
+                asn_v4 = excluded.asn_v4,
+                asn_v6 = excluded.asn_v6,
+                latitude = excluded.latitude,
+                longitude = excluded.longitude,
+                is_anchor = excluded.is_anchor,
+                fqdn = excluded.fqdn",
+            params![
+                probe.prb_id,
+                probe.country_code,
+                probe.asn_v4,
+                probe.asn_v6,
+                probe.latitude,
+                probe.longitude,
+                probe.is_anchor,
+                probe.fqdn,
+            ],
+        )?;
+    }
+
+    for (address, probe) in probes.addresses() {
+        tx.execute(
+            "INSERT INTO target_addresses (address, prb_id) VALUES (?1, ?2)
+             ON CONFLICT (address) DO UPDATE SET prb_id = excluded.prb_id",
+            params![address, probe.map(|p| p.prb_id)],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn insert_ping(tx: &Transaction, ping: &PingMeasurement) -> Result<(), rusqlite::Error> {
+    insert_probe(tx, ping.prb_id, &ping.src_addr, ping.timestamp)?;
+
+    // RIPE Atlas reports -1 for min/avg/max when no reply arrived.
+    let rtt = |value: f32| (value >= 0.0).then_some(value);
+    tx.execute(
+        "INSERT INTO ping_results
+            (msm_id, prb_id, timestamp, src_addr, dst_addr, proto, sent, rcvd, min, avg, max)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+         ON CONFLICT (msm_id, prb_id, timestamp) DO UPDATE SET
+            src_addr = excluded.src_addr,
+            dst_addr = excluded.dst_addr,
+            proto = excluded.proto,
+            sent = excluded.sent,
+            rcvd = excluded.rcvd,
+            min = excluded.min,
+            avg = excluded.avg,
+            max = excluded.max",
+        params![
+            ping.msm_id,
+            ping.prb_id,
+            ping.timestamp as i64,
+            ping.src_addr,
+            ping.dst_addr,
+            ping.proto,
+            ping.sent,
+            ping.rcvd,
+            rtt(ping.min),
+            rtt(ping.avg),
+            rtt(ping.max),
+        ],
+    )?;
+
+    // Replies of an earlier fetch of the same result are replaced, not merged.
+    tx.execute(
+        "DELETE FROM ping_packets WHERE msm_id = ?1 AND prb_id = ?2 AND timestamp = ?3",
+        params![ping.msm_id, ping.prb_id, ping.timestamp as i64],
+    )?;
+    for packet in FlattenedPingPacket::from_ping_measurement(ping) {
+        tx.execute(
+            "INSERT INTO ping_packets
+                (msm_id, prb_id, timestamp, seq, from_addr, rtt, ttl, dup, timed_out, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+             ON CONFLICT (msm_id, prb_id, timestamp, seq) DO UPDATE SET
+                from_addr = excluded.from_addr,
+                rtt = excluded.rtt,
+                ttl = excluded.ttl,
+                dup = excluded.dup,
+                timed_out = excluded.timed_out,
+                error = excluded.error",
+            params![
+                packet.msm_id,
+                packet.prb_id,
+                packet.timestamp as i64,
+                packet.seq,
+                packet.from,
+                packet.rtt,
+                packet.ttl,
+                packet.dup,
+                packet.timed_out,
+                packet.error,
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn insert_http(tx: &Transaction, http: &HttpMeasurement) -> Result<(), rusqlite::Error> {
+    tx.execute(
+        "DELETE FROM http_results WHERE msm_id = ?1 AND prb_id = ?2 AND timestamp = ?3",
+        params![http.msm_id, http.prb_id, http.timestamp as i64],
+    )?;
+    for row in FlattenedHttpMeasurement::from_http_measurement(http) {
+        if let Some(src_addr) = &row.src_addr {
+            insert_probe(tx, row.prb_id, src_addr, row.timestamp)?;
+        }
+
+        tx.execute(
+            "INSERT INTO http_results
+                (msm_id, prb_id, timestamp, seq, uri, ttr, method, src_addr, dst_addr,
+                 rt, res, ver, hsize, bsize, ttc, ttfb, read_time, outcome, error_kind, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10,
+                     ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)
+             ON CONFLICT (msm_id, prb_id, timestamp, seq) DO UPDATE SET
+                uri = excluded.uri,
+                ttr = excluded.ttr,
+                method = excluded.method,
+                src_addr = excluded.src_addr,
+                dst_addr = excluded.dst_addr,
+                rt = excluded.rt,
+                res = excluded.res,
+                ver = excluded.ver,
+                hsize = excluded.hsize,
+                bsize = excluded.bsize,
+                ttc = excluded.ttc,
+                ttfb = excluded.ttfb,
+                read_time = excluded.read_time,
+                outcome = excluded.outcome,
+                error_kind = excluded.error_kind,
+                error = excluded.error",
+            params![
+                row.msm_id,
+                row.prb_id,
+                row.timestamp as i64,
+                row.result_index,
+                row.uri,
+                row.ttr,
+                row.method,
+                row.src_addr,
+                row.dst_addr,
+                row.rt,
+                row.res,
+                row.ver,
+                row.hsize,
+                row.bsize,
+                row.ttc,
+                row.ttfb,
+                row.read_time,
+                row.outcome.as_str(),
+                row.error_kind.map(|kind| kind.as_str()),
+                row.error,
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn insert_traceroute(
+    tx: &Transaction,
+    traceroute: &TraceRouteMeasurement,
+) -> Result<(), rusqlite::Error> {
+    insert_probe(
+        tx,
+        traceroute.prb_id,
+        &traceroute.src_addr,
+        traceroute.timestamp,
+    )?;
+
+    let key = (
+        traceroute.msm_id,
+        traceroute.prb_id,
+        traceroute.timestamp as i64,
+    );
+
+    tx.execute(
+        "INSERT INTO traceroutes
+            (msm_id, prb_id, timestamp, endtime, src_addr, dst_addr, proto, paris_id,
+             destination_ip_responded)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+         ON CONFLICT (msm_id, prb_id, timestamp) DO UPDATE SET
+            endtime = excluded.endtime,
+            src_addr = excluded.src_addr,
+            dst_addr = excluded.dst_addr,
+            proto = excluded.proto,
+            paris_id = excluded.paris_id,
+            destination_ip_responded = excluded.destination_ip_responded",
+        params![
+            key.0,
+            key.1,
+            key.2,
+            traceroute.endtime as i64,
+            traceroute.src_addr,
+            traceroute.dst_addr,
+            traceroute.proto,
+            traceroute.paris_id,
+            traceroute.destination_ip_responded,
+        ],
+    )?;
+
+    // Hops and replies of an earlier fetch of the same result are replaced, not merged.
+    for table in ["traceroute_hops", "hop_replies"] {
+        tx.execute(
+            &format!(
+                "DELETE FROM {} WHERE msm_id = ?1 AND prb_id = ?2 AND timestamp = ?3",
+                table
+            ),
+            params![key.0, key.1, key.2],
+        )?;
+    }
+
+    for hop in FlattenedTraceRouteMeasurement::from_traceroute_measurement(traceroute) {
+        tx.execute(
+            "INSERT INTO traceroute_hops
+                (msm_id, prb_id, timestamp, hop, sent, received, min, avg, max, addresses,
+                 address_count, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+             ON CONFLICT (msm_id, prb_id, timestamp, hop) DO UPDATE SET
+                sent = excluded.sent,
+                received = excluded.received,
+                min = excluded.min,
+                avg = excluded.avg,
+                max = excluded.max,
+                addresses = excluded.addresses,
+                address_count = excluded.address_count,
+                error = excluded.error",
+            params![
+                key.0,
+                key.1,
+                key.2,
+                hop.hop,
+                hop.sent,
+                hop.received,
+                hop.min,
+                hop.avg,
+                hop.max,
+                hop.addresses,
+                hop.address_count,
+                hop.error,
+            ],
+        )?;
+    }
+
+    for reply in FlattenedHopReply::from_traceroute_measurement(traceroute) {
+        tx.execute(
+            "INSERT INTO hop_replies
+                (msm_id, prb_id, timestamp, hop, seq, from_addr, ttl, size, rtt, timed_out,
+                 late, dup, err, itos, ittl, flags, mpls)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
+             ON CONFLICT (msm_id, prb_id, timestamp, hop, seq) DO UPDATE SET
+                from_addr = excluded.from_addr,
+                ttl = excluded.ttl,
+                size = excluded.size,
+                rtt = excluded.rtt,
+                timed_out = excluded.timed_out,
+                late = excluded.late,
+                dup = excluded.dup,
+                err = excluded.err,
+                itos = excluded.itos,
+                ittl = excluded.ittl,
+                flags = excluded.flags,
+                mpls = excluded.mpls",
+            params![
+                key.0,
+                key.1,
+                key.2,
+                reply.hop,
+                reply.seq,
+                reply.from,
+                reply.ttl,
+                reply.size,
+                reply.rtt,
+                reply.timed_out,
+                reply.late,
+                reply.dup,
+                reply.err,
+                reply.itos,
+                reply.ittl,
+                reply.flags,
+                reply.mpls,
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    use crate::test_support::{ping, temp_dir, traceroute};
+
+    fn saver() -> SqliteSaver {
+        SqliteSaver::new(OutputLayout::new(
+            PathBuf::new(),
+            "{type}".to_string(),
+            "test".to_string(),
+            false,
+        ))
+    }
+
+    fn count(path: &Path, table: &str) -> i64 {
+        Connection::open(path)
+            .unwrap()
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn migrates_unversioned_database() {
+        let path = temp_dir("sqlite-migrate").join("measurements.sqlite");
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "
+                CREATE TABLE measurements (
+                    msm_id INTEGER PRIMARY KEY,
+                    type TEXT NOT NULL,
+                    campaign TEXT NOT NULL,
+                    first_timestamp INTEGER NOT NULL,
+                    last_timestamp INTEGER NOT NULL
+                );
+                CREATE TABLE ping_packets (
+                    msm_id INTEGER NOT NULL,
+                    prb_id INTEGER NOT NULL,
+                    timestamp INTEGER NOT NULL,
+                    seq INTEGER NOT NULL,
+                    rtt REAL,
+                    PRIMARY KEY (msm_id, prb_id, timestamp, seq)
+                );
+                CREATE TABLE http_results (
+                    msm_id INTEGER NOT NULL,
+                    prb_id INTEGER NOT NULL,
+                    timestamp INTEGER NOT NULL,
+                    seq INTEGER NOT NULL,
+                    ttr REAL NOT NULL,
+                    method TEXT NOT NULL,
+                    src_addr TEXT,
+                    dst_addr TEXT NOT NULL,
+                    rt REAL,
+                    res INTEGER,
+                    ver TEXT,
+                    hsize INTEGER,
+                    bsize INTEGER,
+                    PRIMARY KEY (msm_id, prb_id, timestamp, seq)
+                );
+                CREATE TABLE traceroute_hops (
+                    msm_id INTEGER NOT NULL,
+                    prb_id INTEGER NOT NULL,
+                    timestamp INTEGER NOT NULL,
+                    hop INTEGER NOT NULL,
+                    sent INTEGER NOT NULL,
+                    received INTEGER NOT NULL,
+                    min REAL,
+                    avg REAL,
+                    max REAL,
+                    PRIMARY KEY (msm_id, prb_id, timestamp, hop)
+                );
+                CREATE TABLE hop_replies (
+                    msm_id INTEGER NOT NULL,
+                    prb_id INTEGER NOT NULL,
+                    timestamp INTEGER NOT NULL,
+                    hop INTEGER NOT NULL,
+                    seq INTEGER NOT NULL,
+                    from_addr TEXT,
+                    ttl INTEGER,
+                    size INTEGER,
+                    rtt REAL,
+                    timed_out INTEGER NOT NULL,
+                    PRIMARY KEY (msm_id, prb_id, timestamp, hop, seq)
+                );
+                INSERT INTO ping_packets VALUES (1, 10, 100, 0, NULL);
+                INSERT INTO http_results VALUES
+                    (2, 10, 100, 0, 1.0, 'GET', NULL, '192.0.2.1', 20.0, 200, '1.1', 1, 1);
+                ",
+            )
+            .unwrap();
+        drop(connection);
+
+        let measurements = [
+            ping(1, 10, 200, &[Some(1.0)]),
+            traceroute(3, 10, 200, &["10.0.0.1", "192.0.2.1"], true),
+        ];
+        let all: Vec<&AggregatedMeasurement> = measurements.iter().collect();
+        saver().save_to(&path, &all).unwrap();
+
+        let connection = Connection::open(&path).unwrap();
+        let version: i32 = connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+        let timed_out: bool = connection
+            .query_row(
+                "SELECT timed_out FROM ping_packets WHERE timestamp = 100",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(timed_out);
+        let outcome: String = connection
+            .query_row("SELECT outcome FROM http_results", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(outcome, "ok");
+        assert_eq!(count(&path, "hop_replies"), 2);
+        assert_eq!(count(&path, "probe_metadata"), 0);
+
+        // A second save finds the current version and changes nothing.
+        saver().save_to(&path, &all).unwrap();
+        assert_eq!(count(&path, "ping_packets"), 2);
+    }
+
+    #[test]
+    fn refetched_results_replace_their_child_rows() {
+        let path = temp_dir("sqlite-children").join("measurements.sqlite");
+
+        let first = [
+            ping(1, 10, 100, &[Some(1.0), Some(2.0), None]),
+            traceroute(2, 10, 100, &["10.0.0.1", "10.0.0.2", "192.0.2.1"], true),
+        ];
+        saver()
+            .save_to(&path, &first.iter().collect::<Vec<_>>())
+            .unwrap();
+        assert_eq!(count(&path, "ping_packets"), 3);
+        assert_eq!(count(&path, "traceroute_hops"), 3);
+        assert_eq!(count(&path, "hop_replies"), 3);
+
+        let second = [
+            ping(1, 10, 100, &[Some(1.0), Some(2.0)]),
+            traceroute(2, 10, 100, &["10.0.0.1", "192.0.2.1"], true),
+        ];
+        saver()
+            .save_to(&path, &second.iter().collect::<Vec<_>>())
+            .unwrap();
+        assert_eq!(count(&path, "ping_results"), 1);
+        assert_eq!(count(&path, "ping_packets"), 2);
+        assert_eq!(count(&path, "traceroutes"), 1);
+        assert_eq!(count(&path, "traceroute_hops"), 2);
+        assert_eq!(count(&path, "hop_replies"), 2);
+    }
+
+    #[test]
+    fn rejects_newer_schema() {
+        let path = temp_dir("sqlite-newer").join("measurements.sqlite");
+        Connection::open(&path)
+            .unwrap()
+            .pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+        assert!(saver().save_to(&path, &[]).is_err());
+    }
+}
@@ -1,45 +1,83 @@
-use clap::Parser;
-use futures::future::join_all;
+use clap::{CommandFactory, Parser, Subcommand};
 use reqwest::Client;
 
+mod analysis;
 mod api;
+mod asn;
+mod commands;
+mod enrichment;
 mod io;
+mod report;
+#[cfg(test)]
+mod test_support;
 
+/// Fetches RIPE Atlas results and analyses them. Without a subcommand the arguments of `fetch`
+/// are expected.
 #[derive(Debug, Parser)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    #[clap(short, long)]
-    measurements: String,
-    #[clap(short, long, default_value = "csv")]
-    output_format: String,
+    #[clap(subcommand)]
+    command: Option<Command>,
+    #[clap(flatten)]
+    fetch: Option<commands::fetch::FetchArgs>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Check results against an alert rule file
+    Alert(commands::alert::AlertArgs),
+    /// Detect RTT level shifts, spikes and loss bursts per pair
+    Anomalies(commands::anomalies::AnomaliesArgs),
+    /// Map traceroute hops to origin ASes and build AS-level paths from a prefix table
+    AsPaths(commands::aspaths::AsPathsArgs),
+    /// Inspect or purge the cache of downloaded result pages
+    Cache(commands::cache::CacheArgs),
+    /// Compare two campaigns pair by pair and rank the regressions and improvements
+    Compare(commands::compare::CompareArgs),
+    /// Serve the latest RTT, loss, HTTP status and hop count per pair as Prometheus metrics
+    Export(commands::export::ExportArgs),
+    /// Fetch results and save them in one of the output formats
+    Fetch(commands::fetch::FetchArgs),
+    /// Aggregate RTTs into a probe by probe latency matrix
+    Matrix(commands::matrix::MatrixArgs),
+    /// Join ping, HTTP and traceroute results per pair and time bucket into one wide table
+    Merge(commands::merge::MergeArgs),
+    /// Detect traceroute path changes and report how stable each route was
+    Paths(commands::paths::PathsArgs),
+    /// Render the standard charts of the results as SVG files and optionally an HTML report
+    Report(commands::report::ReportArgs),
+    /// Subscribe to the live result stream, append results to the output and print a rolling
+    /// summary per pair
+    Stream(commands::stream::StreamArgs),
+    /// Summarize RTT, loss, HTTP status and hop count statistics per group
+    Summarize(commands::summarize::SummarizeArgs),
+    /// Poll for new results on a schedule and append them until every measurement stopped
+    Watch(commands::watch::WatchArgs),
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Cli::parse();
-    let measurement_ids = io::read_measurement_ids_from_file(&args.measurements)?;
     let client = Client::new();
 
-    let output: Box<dyn io::MeasurementSaver> = match args.output_format.as_str() {
-        "csv" => Box::new(io::CsvSaver::new()),
-        _ => panic!("Unsupported output format"),
-    };
-
-    let futures = measurement_ids
-        .ids
-        .iter()
-        .map(|id| api::fetch_measurement_data::get_measurement_data(&client, id));
-    let results = join_all(futures).await;
-
-    let mut measurements: Vec<api::results::AggregatedMeasurement> =
-        Vec::with_capacity(results.len());
-    for result in results {
-        match result {
-            Ok(mut m) => measurements.append(&mut m),
-            Err(error) => println!("Error: {}", error),
-        }
+    match args.command {
+        Some(Command::Alert(alert)) => commands::alert::run(alert, &client).await,
+        Some(Command::Anomalies(anomalies)) => commands::anomalies::run(anomalies, &client).await,
+        Some(Command::AsPaths(aspaths)) => commands::aspaths::run(aspaths, &client).await,
+        Some(Command::Cache(cache)) => commands::cache::run(cache),
+        Some(Command::Compare(compare)) => commands::compare::run(compare, &client).await,
+        Some(Command::Export(export)) => commands::export::run(export, &client).await,
+        Some(Command::Fetch(fetch)) => commands::fetch::run(fetch, &client).await,
+        Some(Command::Matrix(matrix)) => commands::matrix::run(matrix, &client).await,
+        Some(Command::Merge(merge)) => commands::merge::run(merge, &client).await,
+        Some(Command::Paths(paths)) => commands::paths::run(paths, &client).await,
+        Some(Command::Report(report)) => commands::report::run(report, &client).await,
+        Some(Command::Stream(stream)) => commands::stream::run(stream, &client).await,
+        Some(Command::Summarize(summarize)) => commands::summarize::run(summarize, &client).await,
+        Some(Command::Watch(watch)) => commands::watch::run(watch, &client).await,
+        None => match args.fetch {
+            Some(fetch) => commands::fetch::run(fetch, &client).await,
+            None => Ok(Cli::command().print_help()?),
+        },
     }
-
-    output.save_by_type(&measurements)?;
-
-    Ok(())
 }
@@ -0,0 +1,288 @@
+use chrono::DateTime;
+
+use crate::{
+    analysis::stats,
+    report::svg::{Svg, ticks},
+};
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 400.0;
+const LEFT: f64 = 70.0;
+const RIGHT: f64 = 20.0;
+const TOP: f64 = 40.0;
+const BOTTOM: f64 = 60.0;
+
+const LINE: &str = "#1f77b4";
+const LOST: &str = "#d62728";
+const GRID: &str = "#dddddd";
+const AXIS: &str = "#333333";
+
+/// Maps data values onto the plot area.
+struct Scale {
+    min: f64,
+    max: f64,
+    from: f64,
+    to: f64,
+}
+
+impl Scale {
+    fn new(min: f64, max: f64, from: f64, to: f64) -> Self {
+        // A flat series still needs a range to be drawn in.
+        let (min, max) = if max > min {
+            (min, max)
+        } else {
+            (min - 1.0, max + 1.0)
+        };
+        Scale { min, max, from, to }
+    }
+
+    fn map(&self, value: f64) -> f64 {
+        self.from + (value - self.min) / (self.max - self.min) * (self.to - self.from)
+    }
+}
+
+fn frame(svg: &mut Svg, title: &str, y_label: &str, y: &Scale) {
+    svg.text(WIDTH / 2.0, 24.0, 16.0, "middle", title);
+    svg.vertical_text(18.0, (TOP + HEIGHT - BOTTOM) / 2.0, 12.0, "middle", y_label);
+
+    for tick in ticks(y.min, y.max, 6) {
+        let position = y.map(tick);
+        svg.line(LEFT, position, WIDTH - RIGHT, position, GRID);
+        svg.text(LEFT - 6.0, position + 4.0, 11.0, "end", &format_value(tick));
+    }
+    svg.line(LEFT, TOP, LEFT, HEIGHT - BOTTOM, AXIS);
+    svg.line(LEFT, HEIGHT - BOTTOM, WIDTH - RIGHT, HEIGHT - BOTTOM, AXIS);
+}
+
+fn format_value(value: f64) -> String {
+    if value.abs() >= 100.0 || value.fract() == 0.0 {
+        format!("{:.0}", value)
+    } else {
+        format!("{:.1}", value)
+    }
+}
+
+fn format_time(timestamp: i64, span: i64) -> String {
+    let format = if span > 2 * 86_400 {
+        "%m-%d"
+    } else if span > 86_400 {
+        "%m-%d %H:%M"
+    } else {
+        "%H:%M"
+    };
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|time| time.format(format).to_string())
+        .unwrap_or_default()
+}
+
+/// RTT over time. Results without any RTT are drawn as red marks on the time axis and break the
+/// line.
+pub fn time_series(title: &str, points: &[(i64, Option<f32>)]) -> String {
+    let mut svg = Svg::new(WIDTH, HEIGHT);
+
+    let (start, end) = points
+        .iter()
+        .fold((i64::MAX, i64::MIN), |(lo, hi), (t, _)| {
+            (lo.min(*t), hi.max(*t))
+        });
+    let rtts: Vec<f64> = points
+        .iter()
+        .filter_map(|(_, v)| *v)
+        .map(f64::from)
+        .collect();
+    let max = rtts.iter().cloned().fold(0.0, f64::max);
+
+    let x = Scale::new(start as f64, end as f64, LEFT, WIDTH - RIGHT);
+    let y = Scale::new(0.0, max * 1.1, HEIGHT - BOTTOM, TOP);
+    frame(&mut svg, title, "RTT (ms)", &y);
+
+    if points.is_empty() {
+        return svg.finish();
+    }
+    for tick in ticks(start as f64, end as f64, 6) {
+        let position = x.map(tick);
+        svg.line(
+            position,
+            HEIGHT - BOTTOM,
+            position,
+            HEIGHT - BOTTOM + 5.0,
+            AXIS,
+        );
+        svg.text(
+            position,
+            HEIGHT - BOTTOM + 20.0,
+            11.0,
+            "middle",
+            &format_time(tick as i64, end - start),
+        );
+    }
+
+    let mut segment: Vec<(f64, f64)> = Vec::new();
+    for (timestamp, rtt) in points {
+        match rtt {
+            Some(rtt) => segment.push((x.map(*timestamp as f64), y.map(*rtt as f64))),
+            None => {
+                svg.polyline(&segment, LINE);
+                segment.clear();
+                svg.circle(x.map(*timestamp as f64), HEIGHT - BOTTOM, 3.0, LOST);
+            }
+        }
+    }
+    svg.polyline(&segment, LINE);
+
+    svg.finish()
+}
+
+/// One box per group: the box spans the quartiles, the whiskers reach the furthest values
+/// within 1.5 times the interquartile range and everything beyond is drawn as a dot.
+pub fn box_plot(title: &str, groups: &[(String, Vec<f32>)]) -> String {
+    let mut svg = Svg::new(WIDTH, HEIGHT);
+
+    let max = groups
+        .iter()
+        .flat_map(|(_, values)| values)
+        .cloned()
+        .fold(0.0f32, f32::max) as f64;
+    let y = Scale::new(0.0, max * 1.1, HEIGHT - BOTTOM, TOP);
+    frame(&mut svg, title, "RTT (ms)", &y);
+
+    let slot = (WIDTH - LEFT - RIGHT) / groups.len().max(1) as f64;
+    let half = (slot * 0.3).min(30.0);
+    for (index, (label, values)) in groups.iter().enumerate() {
+        let center = LEFT + slot * (index as f64 + 0.5);
+        svg.text(center, HEIGHT - BOTTOM + 18.0, 11.0, "middle", label);
+
+        let (Some(q1), Some(median), Some(q3)) = (
+            stats::percentile(values, 25.0),
+            stats::median(values),
+            stats::percentile(values, 75.0),
+        ) else {
+            continue;
+        };
+        let fence = 1.5 * (q3 - q1);
+        let inside = values
+            .iter()
+            .filter(|v| **v >= q1 - fence && **v <= q3 + fence);
+        let low = inside.clone().cloned().fold(f32::INFINITY, f32::min);
+        let high = inside.cloned().fold(f32::NEG_INFINITY, f32::max);
+
+        let map = |value: f32| y.map(value as f64);
+        svg.line(center, map(low), center, map(q1), AXIS);
+        svg.line(center, map(q3), center, map(high), AXIS);
+        for whisker in [low, high] {
+            svg.line(
+                center - half / 2.0,
+                map(whisker),
+                center + half / 2.0,
+                map(whisker),
+                AXIS,
+            );
+        }
+        svg.outlined_rect(
+            center - half,
+            map(q3),
+            half * 2.0,
+            map(q1) - map(q3),
+            "#aec7e8",
+            AXIS,
+        );
+        svg.line(center - half, map(median), center + half, map(median), AXIS);
+
+        for outlier in values.iter().filter(|v| **v < low || **v > high) {
+            svg.circle(center, map(*outlier), 2.0, LOST);
+        }
+    }
+
+    svg.finish()
+}
+
+/// Colours every cell from green for the lowest to red for the highest value, cells without
+/// data stay grey.
+pub fn heatmap(title: &str, labels: &[String], values: &[Vec<Option<f32>>]) -> String {
+    let cell = (600.0 / labels.len().max(1) as f64).min(48.0);
+    let margin = 130.0;
+    let size = margin + cell * labels.len() as f64;
+    let mut svg = Svg::new(size + 20.0, size + 60.0);
+    svg.text((size + 20.0) / 2.0, 24.0, 16.0, "middle", title);
+
+    let present: Vec<f32> = values.iter().flatten().filter_map(|v| *v).collect();
+    let min = present.iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = present.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+
+    let top = margin + 30.0;
+    for (index, label) in labels.iter().enumerate() {
+        let center = cell * (index as f64 + 0.5);
+        svg.text(margin - 6.0, top + center + 4.0, 11.0, "end", label);
+        svg.vertical_text(margin + center + 4.0, top - 6.0, 11.0, "start", label);
+    }
+
+    for (row, cells) in values.iter().enumerate() {
+        for (column, value) in cells.iter().enumerate() {
+            let x = margin + cell * column as f64;
+            let y = top + cell * row as f64;
+            match value {
+                Some(value) => {
+                    let share = if max > min {
+                        (value - min) / (max - min)
+                    } else {
+                        0.0
+                    };
+                    svg.outlined_rect(x, y, cell, cell, &colour(share as f64), "white");
+                    if cell >= 32.0 {
+                        svg.text(
+                            x + cell / 2.0,
+                            y + cell / 2.0 + 4.0,
+                            10.0,
+                            "middle",
+                            &format_value(*value as f64),
+                        );
+                    }
+                }
+                None => {
+                    svg.outlined_rect(x, y, cell, cell, "#eeeeee", "white");
+                }
+            }
+        }
+    }
+
+    if !present.is_empty() {
+        let legend = top + cell * labels.len() as f64 + 20.0;
+        svg.rect(margin, legend - 10.0, 12.0, 12.0, &colour(0.0));
+        svg.text(
+            margin + 16.0,
+            legend,
+            11.0,
+            "start",
+            &format_value(min as f64),
+        );
+        svg.rect(margin + 80.0, legend - 10.0, 12.0, 12.0, &colour(1.0));
+        svg.text(
+            margin + 96.0,
+            legend,
+            11.0,
+            "start",
+            &format_value(max as f64),
+        );
+    }
+
+    svg.finish()
+}
+
+/// Green to yellow to red for `share` in 0..=1.
+fn colour(share: f64) -> String {
+    let stops = [
+        (26.0, 152.0, 80.0),
+        (254.0, 224.0, 139.0),
+        (215.0, 48.0, 39.0),
+    ];
+    let position = share.clamp(0.0, 1.0) * 2.0;
+    let index = (position.floor() as usize).min(1);
+    let weight = position - index as f64;
+    let (from, to) = (stops[index], stops[index + 1]);
+    format!(
+        "rgb({:.0},{:.0},{:.0})",
+        from.0 + (to.0 - from.0) * weight,
+        from.1 + (to.1 - from.1) * weight,
+        from.2 + (to.2 - from.2) * weight
+    )
+}
@@ -0,0 +1,454 @@
+use chrono::{DateTime, Utc};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Write},
+};
+
+use crate::{
+    analysis::{samples::Samples, stats},
+    api::{
+        fetch_measurement_definition::MeasurementDefinition,
+        results::{
+            AggregatedMeasurement, FlattenedHttpMeasurement, FlattenedPingMeasurement,
+            HttpErrorKind,
+        },
+    },
+    enrichment::ProbeDirectory,
+    report::{Chart, svg::escape},
+};
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em auto; max-width: 1100px; color: #222; }
+h1, h2, h3 { font-weight: 600; }
+table { border-collapse: collapse; margin: 1em 0; font-size: 0.9em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+th { background: #f3f3f3; }
+td.number { text-align: right; }
+pre { background: #f6f6f6; padding: 1em; overflow-x: auto; }
+figure { margin: 1em 0; }
+svg { max-width: 100%; height: auto; }
+";
+
+const WORST_PAIRS: usize = 10;
+
+/// What the report knows about the campaign besides its results.
+pub struct Campaign<'a> {
+    pub name: &'a str,
+    pub definitions: &'a [MeasurementDefinition],
+    /// The executor configuration the campaign was created from, shown verbatim.
+    pub configuration: Option<&'a str>,
+}
+
+/// Statistics of one source and target pair.
+struct PairStats {
+    prb_id: u32,
+    dst_addr: String,
+    results: u32,
+    median: Option<f32>,
+    p95: Option<f32>,
+    loss: Option<f32>,
+}
+
+/// Renders the whole report as a single HTML page with the charts inlined, so it can be
+/// passed around as one file.
+pub fn render(
+    campaign: &Campaign,
+    measurements: &[AggregatedMeasurement],
+    probes: &ProbeDirectory,
+    charts: &[Chart],
+) -> String {
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n",
+        escape(campaign.name),
+        STYLE
+    );
+    let _ = writeln!(html, "<h1>{}</h1>", escape(campaign.name));
+
+    let first = measurements.iter().map(|m| m.timestamp()).min();
+    let last = measurements.iter().map(|m| m.timestamp()).max();
+    let _ = writeln!(
+        html,
+        "<p>{} results from {} to {}, generated {}.</p>",
+        measurements.len(),
+        time(first.map(|t| t as i64)),
+        time(last.map(|t| t as i64)),
+        Utc::now().format("%Y-%m-%d %H:%M UTC")
+    );
+
+    configuration_section(&mut html, campaign);
+    probe_section(&mut html, measurements, probes);
+
+    let kinds: Vec<&str> = ["ping", "http", "traceroute"]
+        .into_iter()
+        .filter(|kind| measurements.iter().any(|m| m.kind() == *kind))
+        .collect();
+
+    summary_section(&mut html, &kinds, measurements);
+    failure_section(&mut html, &kinds, measurements);
+
+    for kind in &kinds {
+        let _ = writeln!(html, "<h2>{}</h2>", kind);
+        worst_pairs(&mut html, kind, measurements, probes);
+        chart_section(&mut html, kind, charts);
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn configuration_section(html: &mut String, campaign: &Campaign) {
+    html.push_str("<h2>Configuration</h2>\n");
+    if !campaign.definitions.is_empty() {
+        let rows = campaign
+            .definitions
+            .iter()
+            .map(|d| {
+                vec![
+                    d.id.to_string(),
+                    d.kind.clone(),
+                    optional(d.description.as_ref()),
+                    optional(d.target.as_ref().or(d.target_ip.as_ref())),
+                    optional(d.interval.map(|i| format!("{} s", i))),
+                    time(d.start_time),
+                    time(d.stop_time),
+                    optional(d.participant_count),
+                    optional(d.status.as_ref().map(|s| &s.name)),
+                ]
+            })
+            .collect();
+        table(
+            html,
+            &[
+                "Measurement",
+                "Type",
+                "Description",
+                "Target",
+                "Interval",
+                "Start",
+                "Stop",
+                "Probes",
+                "Status",
+            ],
+            rows,
+        );
+    }
+    if let Some(configuration) = campaign.configuration {
+        let _ = writeln!(html, "<pre>{}</pre>", escape(configuration));
+    }
+}
+
+fn probe_section(
+    html: &mut String,
+    measurements: &[AggregatedMeasurement],
+    probes: &ProbeDirectory,
+) {
+    let mut results: BTreeMap<u32, u32> = BTreeMap::new();
+    for measurement in measurements {
+        *results.entry(measurement.prb_id()).or_default() += 1;
+    }
+    for probe in probes.probes() {
+        results.entry(probe.prb_id).or_default();
+    }
+
+    html.push_str("<h2>Probes</h2>\n");
+    let rows = results
+        .into_iter()
+        .map(|(prb_id, count)| {
+            let probe = probes.probe(prb_id);
+            vec![
+                prb_id.to_string(),
+                optional(probe.and_then(|p| p.country_code.as_ref())),
+                optional(probe.and_then(|p| p.asn_v4)),
+                optional(probe.and_then(|p| p.asn_v6)),
+                optional(probe.and_then(|p| p.latitude)),
+                optional(probe.and_then(|p| p.longitude)),
+                optional(probe.map(|p| if p.is_anchor { "yes" } else { "no" })),
+                optional(probe.and_then(|p| p.fqdn.as_ref())),
+                count.to_string(),
+            ]
+        })
+        .collect();
+    table(
+        html,
+        &[
+            "Probe",
+            "Country",
+            "ASN v4",
+            "ASN v6",
+            "Latitude",
+            "Longitude",
+            "Anchor",
+            "FQDN",
+            "Results",
+        ],
+        rows,
+    );
+}
+
+fn summary_section(html: &mut String, kinds: &[&str], measurements: &[AggregatedMeasurement]) {
+    html.push_str("<h2>Summary</h2>\n");
+    let rows = kinds
+        .iter()
+        .map(|kind| {
+            let mut samples = Samples::default();
+            let mut results = 0;
+            let mut pairs: Vec<(u32, &str)> = Vec::new();
+            let mut sources: Vec<u32> = Vec::new();
+            for measurement in measurements.iter().filter(|m| m.kind() == *kind) {
+                results += 1;
+                samples.add(Samples::from_measurement(measurement));
+                if let Some(dst_addr) = measurement.dst_addr() {
+                    let pair = (measurement.prb_id(), dst_addr);
+                    if !pairs.contains(&pair) {
+                        pairs.push(pair);
+                    }
+                }
+                if !sources.contains(&measurement.prb_id()) {
+                    sources.push(measurement.prb_id());
+                }
+            }
+            vec![
+                kind.to_string(),
+                results.to_string(),
+                pairs.len().to_string(),
+                sources.len().to_string(),
+                milliseconds(stats::median(&samples.rtts)),
+                milliseconds(stats::percentile(&samples.rtts, 95.0)),
+                percent(samples.loss()),
+            ]
+        })
+        .collect();
+    table(
+        html,
+        &[
+            "Type",
+            "Results",
+            "Pairs",
+            "Sources",
+            "Median RTT",
+            "p95 RTT",
+            "Loss",
+        ],
+        rows,
+    );
+}
+
+/// How results ended, per type: pings by outcome, HTTP requests by outcome, error kind and
+/// non-success status, and traceroutes by whether they reached their target.
+fn failure_section(html: &mut String, kinds: &[&str], measurements: &[AggregatedMeasurement]) {
+    let mut categories: BTreeMap<(&str, String), u32> = BTreeMap::new();
+    for measurement in measurements {
+        let mut count = |category: String| {
+            *categories
+                .entry((measurement.kind(), category))
+                .or_default() += 1;
+        };
+        match measurement {
+            AggregatedMeasurement::Ping(p) => {
+                let row = FlattenedPingMeasurement::from_ping_measurement(p);
+                let category = match row.loss {
+                    Some(loss) if loss > 0.0 && row.rcvd > 0 => "partial loss".to_string(),
+                    _ => row.outcome.as_str().to_string(),
+                };
+                count(category);
+            }
+            AggregatedMeasurement::Http(h) => {
+                for row in FlattenedHttpMeasurement::from_http_measurement(h) {
+                    let category = match (row.error_kind, row.res) {
+                        (Some(HttpErrorKind::Timeout), _) => "timeout".to_string(),
+                        (Some(kind), _) => format!("error ({})", kind.as_str()),
+                        (None, Some(status)) if status >= 400 => format!("status {}", status),
+                        _ => row.outcome.as_str().to_string(),
+                    };
+                    count(category);
+                }
+            }
+            AggregatedMeasurement::TraceRoute(_) => {
+                let reached = Samples::from_measurement(measurement).lost == 0;
+                count(if reached { "reached" } else { "not reached" }.to_string());
+            }
+        }
+    }
+
+    html.push_str("<h2>Outcomes</h2>\n");
+    let rows = kinds
+        .iter()
+        .flat_map(|kind| {
+            let total: u32 = categories
+                .iter()
+                .filter(|((k, _), _)| k == kind)
+                .map(|(_, count)| count)
+                .sum();
+            categories.iter().filter(move |((k, _), _)| k == kind).map(
+                move |((_, category), count)| {
+                    vec![
+                        kind.to_string(),
+                        category.clone(),
+                        count.to_string(),
+                        percent(Some(*count as f32 / total as f32 * 100.0)),
+                    ]
+                },
+            )
+        })
+        .collect();
+    table(html, &["Type", "Outcome", "Count", "Share"], rows);
+}
+
+fn pair_stats(kind: &str, measurements: &[AggregatedMeasurement]) -> Vec<PairStats> {
+    let mut pairs: BTreeMap<(u32, &str), (Samples, u32)> = BTreeMap::new();
+    for measurement in measurements.iter().filter(|m| m.kind() == kind) {
+        let Some(dst_addr) = measurement.dst_addr() else {
+            continue;
+        };
+        let (samples, results) = pairs.entry((measurement.prb_id(), dst_addr)).or_default();
+        samples.add(Samples::from_measurement(measurement));
+        *results += 1;
+    }
+
+    pairs
+        .into_iter()
+        .map(|((prb_id, dst_addr), (samples, results))| PairStats {
+            prb_id,
+            dst_addr: dst_addr.to_string(),
+            results,
+            median: stats::median(&samples.rtts),
+            p95: stats::percentile(&samples.rtts, 95.0),
+            loss: samples.loss(),
+        })
+        .collect()
+}
+
+fn worst_pairs(
+    html: &mut String,
+    kind: &str,
+    measurements: &[AggregatedMeasurement],
+    probes: &ProbeDirectory,
+) {
+    let pairs = pair_stats(kind, measurements);
+    let row = |pair: &PairStats| {
+        vec![
+            probes.label(pair.prb_id),
+            probes
+                .by_address(&pair.dst_addr)
+                .map(|p| probes.label(p.prb_id))
+                .unwrap_or_else(|| pair.dst_addr.clone()),
+            pair.results.to_string(),
+            milliseconds(pair.median),
+            milliseconds(pair.p95),
+            percent(pair.loss),
+        ]
+    };
+    let headers = [
+        "Source",
+        "Target",
+        "Results",
+        "Median RTT",
+        "p95 RTT",
+        "Loss",
+    ];
+
+    let mut by_rtt: Vec<&PairStats> = pairs.iter().filter(|p| p.median.is_some()).collect();
+    by_rtt.sort_by(|a, b| {
+        b.median
+            .partial_cmp(&a.median)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    html.push_str("<h3>Highest median RTT</h3>\n");
+    table(
+        html,
+        &headers,
+        by_rtt.into_iter().take(WORST_PAIRS).map(row).collect(),
+    );
+
+    let mut by_loss: Vec<&PairStats> = pairs
+        .iter()
+        .filter(|p| p.loss.is_some_and(|loss| loss > 0.0))
+        .collect();
+    by_loss.sort_by(|a, b| {
+        b.loss
+            .partial_cmp(&a.loss)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    html.push_str("<h3>Highest loss</h3>\n");
+    if by_loss.is_empty() {
+        html.push_str("<p>No pair lost any probes.</p>\n");
+    } else {
+        table(
+            html,
+            &headers,
+            by_loss.into_iter().take(WORST_PAIRS).map(row).collect(),
+        );
+    }
+}
+
+fn chart_section(html: &mut String, kind: &str, charts: &[Chart]) {
+    let charts: Vec<&Chart> = charts.iter().filter(|c| c.kind == kind).collect();
+    for chart in charts.iter().filter(|c| !c.per_pair) {
+        figure(html, chart);
+    }
+
+    let pairs: Vec<&&Chart> = charts.iter().filter(|c| c.per_pair).collect();
+    if !pairs.is_empty() {
+        let _ = writeln!(
+            html,
+            "<details>\n<summary>RTT over time for {} pairs</summary>",
+            pairs.len()
+        );
+        for chart in pairs {
+            figure(html, chart);
+        }
+        html.push_str("</details>\n");
+    }
+}
+
+fn figure(html: &mut String, chart: &Chart) {
+    let _ = writeln!(
+        html,
+        "<figure aria-label=\"{}\">{}</figure>",
+        escape(&chart.title),
+        chart.svg
+    );
+}
+
+fn table(html: &mut String, headers: &[&str], rows: Vec<Vec<String>>) {
+    html.push_str("<table>\n<tr>");
+    for header in headers {
+        let _ = write!(html, "<th>{}</th>", escape(header));
+    }
+    html.push_str("</tr>\n");
+    for row in rows {
+        html.push_str("<tr>");
+        for cell in row {
+            let class =
+                if cell.parse::<f64>().is_ok() || cell.ends_with(" ms") || cell.ends_with('%') {
+                    " class=\"number\""
+                } else {
+                    ""
+                };
+            let _ = write!(html, "<td{}>{}</td>", class, escape(&cell));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+}
+
+fn optional(value: Option<impl Display>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn milliseconds(value: Option<f32>) -> String {
+    value.map(|v| format!("{:.1} ms", v)).unwrap_or_default()
+}
+
+fn percent(value: Option<f32>) -> String {
+    value.map(|v| format!("{:.1}%", v)).unwrap_or_default()
+}
+
+fn time(timestamp: Option<i64>) -> String {
+    timestamp
+        .and_then(|t| DateTime::from_timestamp(t, 0))
+        .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    analysis::{matrix, samples::Samples, stats},
+    api::results::AggregatedMeasurement,
+    enrichment::ProbeDirectory,
+};
+
+pub mod charts;
+pub mod html;
+pub mod svg;
+
+/// Timestamp and RTT of one result, `None` if nothing came back.
+type TimePoint = (i64, Option<f32>);
+
+/// A rendered chart and the path, relative to the report directory, it is saved under.
+pub struct Chart {
+    pub kind: String,
+    pub path: String,
+    pub title: String,
+    /// Whether the chart covers a single source and target pair rather than the whole type.
+    pub per_pair: bool,
+    pub svg: String,
+}
+
+/// Renders the standard charts for every measurement type present: RTT over time for every
+/// measurement, source and target, the RTT distribution per source probe and a heatmap of the
+/// median RTT matrix.
+pub fn standard_charts(
+    measurements: &[AggregatedMeasurement],
+    probes: &ProbeDirectory,
+) -> Vec<Chart> {
+    let mut charts = Vec::new();
+
+    for kind in ["ping", "http", "traceroute"] {
+        let mut pairs: BTreeMap<(u32, u32, &str), Vec<TimePoint>> = BTreeMap::new();
+        let mut sources: BTreeMap<u32, Vec<f32>> = BTreeMap::new();
+        for measurement in measurements.iter().filter(|m| m.kind() == kind) {
+            let Some(dst_addr) = measurement.dst_addr() else {
+                continue;
+            };
+            let samples = Samples::from_measurement(measurement);
+            pairs
+                .entry((measurement.msm_id(), measurement.prb_id(), dst_addr))
+                .or_default()
+                .push((measurement.timestamp() as i64, stats::median(&samples.rtts)));
+            sources
+                .entry(measurement.prb_id())
+                .or_default()
+                .extend(samples.rtts);
+        }
+        if pairs.is_empty() {
+            continue;
+        }
+
+        // Keyed by measurement too: several measurements, such as the IPv4 and IPv6 one, can
+        // resolve to the same target probe.
+        for ((msm_id, prb_id, dst_addr), mut points) in pairs {
+            points.sort_by_key(|(timestamp, _)| *timestamp);
+            let (target, target_label) = match probes.by_address(dst_addr) {
+                Some(probe) => (probe.prb_id.to_string(), probes.label(probe.prb_id)),
+                None => (dst_addr.replace([':', '/'], "_"), dst_addr.to_string()),
+            };
+            let title = format!(
+                "{} RTT from {} to {} (msm {})",
+                kind,
+                probes.label(prb_id),
+                target_label,
+                msm_id
+            );
+            charts.push(Chart {
+                kind: kind.to_string(),
+                path: format!("{}/rtt_{}_{}_{}.svg", kind, msm_id, prb_id, target),
+                svg: charts::time_series(&title, &points),
+                title,
+                per_pair: true,
+            });
+        }
+
+        let groups: Vec<(String, Vec<f32>)> = sources
+            .into_iter()
+            .map(|(prb_id, rtts)| (probes.label(prb_id), rtts))
+            .collect();
+        let title = format!("{} RTT by source probe", kind);
+        charts.push(Chart {
+            kind: kind.to_string(),
+            path: format!("{}/rtt_by_source.svg", kind),
+            svg: charts::box_plot(&title, &groups),
+            title,
+            per_pair: false,
+        });
+
+        let matrix = matrix::latency_matrix(kind, measurements, probes);
+        let title = format!("{} median RTT (ms), source by target", kind);
+        charts.push(Chart {
+            kind: kind.to_string(),
+            path: format!("{}/matrix_median.svg", kind),
+            svg: charts::heatmap(&title, &matrix.labels, &matrix.median),
+            title,
+            per_pair: false,
+        });
+    }
+
+    charts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::test_support::ping_json;
+
+    #[test]
+    fn pair_charts_of_measurements_to_the_same_target_do_not_collide() {
+        // Probe 20 owns both target addresses, of an IPv4 and an IPv6 measurement.
+        let probes: ProbeDirectory = serde_json::from_value(json!({
+            "probes": { "20": { "prb_id": 20, "is_anchor": true } },
+            "addresses": { "192.0.2.1": 20, "2001:db8::1": 20 },
+        }))
+        .unwrap();
+        let mut v6 = ping_json(2, 10, 100, &[Some(5.0)]);
+        v6["dst_addr"] = json!("2001:db8::1");
+        let measurements: Vec<AggregatedMeasurement> = vec![
+            serde_json::from_value(ping_json(1, 10, 100, &[Some(1.0)])).unwrap(),
+            serde_json::from_value(v6).unwrap(),
+        ];
+
+        let paths: Vec<String> = standard_charts(&measurements, &probes)
+            .into_iter()
+            .filter(|chart| chart.per_pair)
+            .map(|chart| chart.path)
+            .collect();
+        assert_eq!(paths, ["ping/rtt_1_10_20.svg", "ping/rtt_2_10_20.svg"]);
+    }
+}
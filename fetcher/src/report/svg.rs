@@ -0,0 +1,157 @@
+use std::fmt::Write;
+
+/// Minimal SVG document builder, just enough for the report charts.
+pub struct Svg {
+    width: f64,
+    height: f64,
+    body: String,
+}
+
+impl Svg {
+    pub fn new(width: f64, height: f64) -> Self {
+        Svg {
+            width,
+            height,
+            body: String::new(),
+        }
+    }
+
+    pub fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, fill: &str) -> &mut Self {
+        let _ = writeln!(
+            self.body,
+            r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}"/>"#,
+            x, y, width, height, fill
+        );
+        self
+    }
+
+    pub fn outlined_rect(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        fill: &str,
+        stroke: &str,
+    ) -> &mut Self {
+        let _ = writeln!(
+            self.body,
+            r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}" stroke="{}"/>"#,
+            x, y, width, height, fill, stroke
+        );
+        self
+    }
+
+    pub fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, stroke: &str) -> &mut Self {
+        let _ = writeln!(
+            self.body,
+            r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" stroke="{}"/>"#,
+            x1, y1, x2, y2, stroke
+        );
+        self
+    }
+
+    pub fn polyline(&mut self, points: &[(f64, f64)], stroke: &str) -> &mut Self {
+        if points.is_empty() {
+            return self;
+        }
+        let points: Vec<String> = points
+            .iter()
+            .map(|(x, y)| format!("{:.2},{:.2}", x, y))
+            .collect();
+        let _ = writeln!(
+            self.body,
+            r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="1.5"/>"#,
+            points.join(" "),
+            stroke
+        );
+        self
+    }
+
+    pub fn circle(&mut self, x: f64, y: f64, radius: f64, fill: &str) -> &mut Self {
+        let _ = writeln!(
+            self.body,
+            r#"<circle cx="{:.2}" cy="{:.2}" r="{:.2}" fill="{}"/>"#,
+            x, y, radius, fill
+        );
+        self
+    }
+
+    /// `anchor` is the SVG text-anchor, `start`, `middle` or `end`.
+    pub fn text(&mut self, x: f64, y: f64, size: f64, anchor: &str, content: &str) -> &mut Self {
+        let _ = writeln!(
+            self.body,
+            r#"<text x="{:.2}" y="{:.2}" font-size="{}" text-anchor="{}">{}</text>"#,
+            x,
+            y,
+            size,
+            anchor,
+            escape(content)
+        );
+        self
+    }
+
+    /// Text rotated by -90 degrees around its position, for vertical axis and column labels.
+    pub fn vertical_text(
+        &mut self,
+        x: f64,
+        y: f64,
+        size: f64,
+        anchor: &str,
+        content: &str,
+    ) -> &mut Self {
+        let _ = writeln!(
+            self.body,
+            r#"<text x="{:.2}" y="{:.2}" font-size="{}" text-anchor="{}" transform="rotate(-90 {:.2} {:.2})">{}</text>"#,
+            x,
+            y,
+            size,
+            anchor,
+            x,
+            y,
+            escape(content)
+        );
+        self
+    }
+
+    pub fn finish(&self) -> String {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif">
+<rect width="{w}" height="{h}" fill="white"/>
+{body}</svg>
+"#,
+            w = self.width,
+            h = self.height,
+            body = self.body
+        )
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Roughly `count` evenly spaced round values covering `min..=max`.
+pub fn ticks(min: f64, max: f64, count: usize) -> Vec<f64> {
+    if max <= min || count == 0 {
+        return vec![min];
+    }
+    let raw = (max - min) / count as f64;
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= raw)
+        .unwrap_or(10.0 * magnitude);
+
+    let mut value = (min / step).ceil() * step;
+    let mut ticks = Vec::new();
+    while value <= max + step * 1e-9 {
+        ticks.push(value);
+        value += step;
+    }
+    ticks
+}
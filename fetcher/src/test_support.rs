@@ -0,0 +1,177 @@
+use futures::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use std::{env, fs, path::PathBuf, process};
+use tokio::{net::TcpListener, task::JoinHandle};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::api::results::AggregatedMeasurement;
+
+/// An empty directory under the system temp directory, unique per test and process.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("fetcher-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A ping result in the API format, `None` RTTs are timed out replies.
+pub fn ping_json(msm_id: u32, prb_id: u32, timestamp: usize, rtts: &[Option<f32>]) -> Value {
+    let replies: Vec<Value> = rtts
+        .iter()
+        .map(|rtt| match rtt {
+            Some(rtt) => json!({ "rtt": rtt }),
+            None => json!({ "x": "*" }),
+        })
+        .collect();
+    let answered: Vec<f32> = rtts.iter().flatten().copied().collect();
+    let (min, max, avg) = if answered.is_empty() {
+        (-1.0, -1.0, -1.0)
+    } else {
+        (
+            answered.iter().copied().fold(f32::MAX, f32::min),
+            answered.iter().copied().fold(f32::MIN, f32::max),
+            answered.iter().sum::<f32>() / answered.len() as f32,
+        )
+    };
+    json!({
+        "type": "ping",
+        "dst_addr": "192.0.2.1",
+        "src_addr": "198.51.100.1",
+        "proto": "ICMP",
+        "sent": rtts.len(),
+        "rcvd": answered.len(),
+        "min": min,
+        "max": max,
+        "avg": avg,
+        "result": replies,
+        "msm_id": msm_id,
+        "timestamp": timestamp,
+        "prb_id": prb_id,
+    })
+}
+
+pub fn ping(
+    msm_id: u32,
+    prb_id: u32,
+    timestamp: usize,
+    rtts: &[Option<f32>],
+) -> AggregatedMeasurement {
+    serde_json::from_value(ping_json(msm_id, prb_id, timestamp, rtts)).unwrap()
+}
+
+/// An HTTP result in the API format with one request, `None` is a request that got no
+/// response.
+pub fn http_json(msm_id: u32, prb_id: u32, timestamp: usize, status: Option<u32>) -> Value {
+    let result = match status {
+        Some(status) => json!({
+            "method": "GET", "dst_addr": "192.0.2.1", "src_addr": "198.51.100.1",
+            "rt": 20.0, "res": status, "ver": "1.1", "hsize": 100, "bsize": 1000,
+        }),
+        None => json!({
+            "method": "GET", "dst_addr": "192.0.2.1", "src_addr": "198.51.100.1",
+            "err": "connect: Connection refused",
+        }),
+    };
+    json!({
+        "type": "http",
+        "uri": "http://192.0.2.1/",
+        "result": [result],
+        "msm_id": msm_id,
+        "timestamp": timestamp,
+        "prb_id": prb_id,
+    })
+}
+
+pub fn http(
+    msm_id: u32,
+    prb_id: u32,
+    timestamp: usize,
+    status: Option<u32>,
+) -> AggregatedMeasurement {
+    serde_json::from_value(http_json(msm_id, prb_id, timestamp, status)).unwrap()
+}
+
+/// An HTTP result whose only request failed on DNS, so it has no target address.
+pub fn http_dns_error(msm_id: u32, prb_id: u32, timestamp: usize) -> AggregatedMeasurement {
+    let mut raw = http_json(msm_id, prb_id, timestamp, None);
+    raw["uri"] = json!("http://www.example.org/");
+    raw["result"] = json!([{ "dnserr": "non-recoverable failure in name resolution" }]);
+    serde_json::from_value(raw).unwrap()
+}
+
+/// A traceroute in the API format with one reply per hop from the given addresses, the last
+/// one being the target if `reached`.
+pub fn traceroute_json(
+    msm_id: u32,
+    prb_id: u32,
+    timestamp: usize,
+    hops: &[&str],
+    reached: bool,
+) -> Value {
+    let result: Vec<Value> = hops
+        .iter()
+        .enumerate()
+        .map(|(index, from)| {
+            json!({
+                "hop": index + 1,
+                "result": [{ "from": from, "ttl": 64, "size": 76, "rtt": 1.0 + index as f32 }],
+            })
+        })
+        .collect();
+    json!({
+        "type": "traceroute",
+        "endtime": timestamp + 10,
+        "dst_addr": "192.0.2.1",
+        "src_addr": "198.51.100.1",
+        "proto": "UDP",
+        "paris_id": 1,
+        "result": result,
+        "destination_ip_responded": reached,
+        "prb_id": prb_id,
+        "msm_id": msm_id,
+        "timestamp": timestamp,
+    })
+}
+
+pub fn traceroute(
+    msm_id: u32,
+    prb_id: u32,
+    timestamp: usize,
+    hops: &[&str],
+    reached: bool,
+) -> AggregatedMeasurement {
+    serde_json::from_value(traceroute_json(msm_id, prb_id, timestamp, hops, reached)).unwrap()
+}
+
+/// A stand-in for the RIPE Atlas result stream on 127.0.0.1. Every connection reads
+/// `subscriptions` messages, sends the frames given for it and closes. Returns the websocket
+/// URL and a handle yielding the messages each connection received.
+pub async fn stream_server(
+    subscriptions: usize,
+    connections: Vec<Vec<Value>>,
+) -> (String, JoinHandle<Vec<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}/stream/", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move {
+        let mut received = Vec::new();
+        for frames in connections {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let mut messages = Vec::new();
+            while messages.len() < subscriptions {
+                match socket.next().await {
+                    Some(Ok(Message::Text(text))) => messages.push(text.to_string()),
+                    Some(Ok(_)) => continue,
+                    _ => break,
+                }
+            }
+            for frame in frames {
+                socket.send(Message::text(frame.to_string())).await.unwrap();
+            }
+            let _ = socket.close(None).await;
+            received.push(messages);
+        }
+        received
+    });
+    (url, server)
+}